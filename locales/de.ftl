@@ -0,0 +1,60 @@
+confirmation-subject = Willkommen bei unserem Newsletter!
+confirmation-text =
+    Willkommen bei unserem Newsletter!
+    Besuche { $link }, um dein Abonnement zu bestätigen.
+confirmation-heading = Willkommen bei unserem Newsletter!
+confirmation-html = Besuche <a href="{ $link }">{ $link }</a>, um dein Abonnement zu bestätigen.
+
+reminder-subject = Vergiss nicht, dein Abonnement zu bestätigen
+reminder-text =
+    Du hast dich für unseren Newsletter angemeldet, aber noch nicht bestätigt.
+    Besuche { $link }, um dein Abonnement zu bestätigen.
+reminder-heading = Vergiss nicht, dein Abonnement zu bestätigen
+reminder-html = Du hast dich für unseren Newsletter angemeldet, aber noch nicht bestätigt. Besuche <a href="{ $link }">{ $link }</a>, um dein Abonnement zu bestätigen.
+
+subscribe-success = Bitte schau in dein E-Mail-Postfach
+resend-confirmation-success = Falls diese Adresse noch auf ihre Bestätigung wartet, ist ein neuer Link unterwegs.
+newsletter-published = Der Newsletter wurde erfolgreich veröffentlicht.
+newsletter-unsubscribe-text = Du möchtest diese E-Mails nicht mehr erhalten? Hier abmelden: { $link }
+newsletter-unsubscribe-html = Du möchtest diese E-Mails nicht mehr erhalten? <a href="{ $link }">Hier abmelden</a>.
+newsletter-view-in-browser-text = Wird diese E-Mail nicht richtig angezeigt? Im Browser ansehen: { $link }
+newsletter-view-in-browser-html = Wird diese E-Mail nicht richtig angezeigt? <a href="{ $link }">Im Browser ansehen</a>.
+
+page-confirmed-title = Du bist angemeldet!
+page-confirmed-body = Danke für die Bestätigung, die nächste Ausgabe ist auf dem Weg in dein Postfach.
+page-already-confirmed-title = Du bist bereits angemeldet
+page-already-confirmed-body = Dieses Abonnement wurde schon bestätigt, es gibt nichts weiter zu tun.
+page-expired-title = Dieser Link ist abgelaufen
+page-expired-body = Bestätigungslinks gelten nur eine Weile. Melde dich erneut an oder fordere eine neue Bestätigungs-E-Mail an.
+page-invalid-title = Dieser Link funktioniert nicht
+page-invalid-body = Vielleicht wurde er durch einen neueren ersetzt oder falsch abgetippt. Melde dich erneut an oder fordere eine neue Bestätigungs-E-Mail an.
+page-failed-title = Etwas ist schiefgelaufen
+page-failed-body = Wir konnten dein Abonnement gerade nicht bestätigen. Bitte versuche es in ein paar Minuten noch einmal.
+page-unsubscribe-title = Vom Newsletter abmelden?
+page-unsubscribe-body = Du erhältst dann keine weiteren Ausgaben. Du kannst dich jederzeit wieder anmelden.
+page-unsubscribe-button = Abmelden
+page-unsubscribed-title = Du bist abgemeldet
+page-unsubscribed-body = Schade, dass du gehst. Du erhältst keine weiteren Ausgaben.
+page-unsubscribe-invalid-title = Dieser Link funktioniert nicht
+page-unsubscribe-invalid-body = Vielleicht ist er abgelaufen oder wurde falsch abgetippt. Nutze stattdessen den Abmeldelink in einer neueren Ausgabe.
+page-not-subscribed-title = Du bist nicht angemeldet
+page-not-subscribed-body = Diese Adresse erhält den Newsletter nicht, es gibt nichts abzumelden.
+page-unsubscribe-failed-title = Etwas ist schiefgelaufen
+page-unsubscribe-failed-body = Wir konnten dich gerade nicht abmelden. Bitte versuche es in ein paar Minuten noch einmal.
+
+error-invalid-name = { $name } ist kein gültiger Name.
+error-invalid-email = { $email } ist keine gültige E-Mail-Adresse.
+error-disposable-email = Adressen bei { $domain } sind Wegwerfadressen, bitte nutze eine dauerhafte.
+error-likely-typo = Meintest du { $suggestion }?
+error-undeliverable-email = { $domain } nimmt keine E-Mails an.
+error-form-token = Das Formular konnte nicht geprüft werden, bitte lade die Seite neu und versuche es noch einmal.
+error-captcha-failed = Das CAPTCHA wurde nicht gelöst.
+error-rate-limited = Zu viele Anfragen, versuche es in { $seconds } Sekunden noch einmal.
+error-missing-token = Es wird entweder ein Bestätigungstoken oder ein Link benötigt.
+error-unknown-token = Zu diesem Token gibt es kein Abonnement.
+error-unknown-subscriber = Zu diesem Link gibt es kein Abonnement.
+error-unknown-issue = Diese Ausgabe ist nicht mehr verfügbar.
+error-invalid-link = Dieser Link funktioniert nicht, vielleicht wurde er falsch abgetippt oder durch einen neueren ersetzt.
+error-expired-link = Dieser Link ist abgelaufen.
+error-status-conflict = Dieses Abonnement kann nicht mehr so geändert werden.
+error-unexpected = Bei uns ist etwas schiefgelaufen, bitte versuche es später noch einmal.
@@ -0,0 +1,60 @@
+confirmation-subject = Welcome to our newsletter!
+confirmation-text =
+    Welcome to our newsletter!
+    Visit { $link } to confirm your subscription.
+confirmation-heading = Welcome to our newsletter!
+confirmation-html = Visit <a href="{ $link }">{ $link }</a> to confirm your subscription.
+
+reminder-subject = Don't forget to confirm your subscription
+reminder-text =
+    You signed up for our newsletter but haven't confirmed yet.
+    Visit { $link } to confirm your subscription.
+reminder-heading = Don't forget to confirm your subscription
+reminder-html = You signed up for our newsletter but haven't confirmed yet. Visit <a href="{ $link }">{ $link }</a> to confirm your subscription.
+
+subscribe-success = Check your e-mail box, please
+resend-confirmation-success = If this address is waiting for confirmation, a new link is on its way.
+newsletter-published = Newsletter was published successfully.
+newsletter-unsubscribe-text = Don't want these emails any more? Unsubscribe at { $link }
+newsletter-unsubscribe-html = Don't want these emails any more? <a href="{ $link }">Unsubscribe</a>.
+newsletter-view-in-browser-text = Trouble reading this email? View it in your browser: { $link }
+newsletter-view-in-browser-html = Trouble reading this email? <a href="{ $link }">View it in your browser</a>.
+
+page-confirmed-title = You're subscribed!
+page-confirmed-body = Thanks for confirming, the next issue is on its way to your inbox.
+page-already-confirmed-title = You're already subscribed
+page-already-confirmed-body = This subscription was confirmed before, there is nothing else to do.
+page-expired-title = This link has expired
+page-expired-body = Confirmation links only work for a while. Sign up again, or ask for a new confirmation email.
+page-invalid-title = This link doesn't work
+page-invalid-body = It may have been replaced by a newer one, or mistyped. Sign up again, or ask for a new confirmation email.
+page-failed-title = Something went wrong
+page-failed-body = We couldn't confirm your subscription right now. Please try the link again in a few minutes.
+page-unsubscribe-title = Unsubscribe from the newsletter?
+page-unsubscribe-body = You won't get any more issues. You can always sign up again later.
+page-unsubscribe-button = Unsubscribe
+page-unsubscribed-title = You're unsubscribed
+page-unsubscribed-body = Sorry to see you go, you won't get any more issues.
+page-unsubscribe-invalid-title = This link doesn't work
+page-unsubscribe-invalid-body = It may have expired or been mistyped. Use the unsubscribe link in a more recent issue instead.
+page-not-subscribed-title = You're not subscribed
+page-not-subscribed-body = This address doesn't get the newsletter, there is nothing to unsubscribe from.
+page-unsubscribe-failed-title = Something went wrong
+page-unsubscribe-failed-body = We couldn't unsubscribe you right now. Please try the link again in a few minutes.
+
+error-invalid-name = { $name } is not a valid subscriber name.
+error-invalid-email = { $email } is not a valid subscriber email.
+error-disposable-email = Addresses at { $domain } are disposable, please use a permanent one.
+error-likely-typo = Did you mean { $suggestion }?
+error-undeliverable-email = { $domain } does not accept email.
+error-form-token = The form couldn't be checked, please reload the page and try again.
+error-captcha-failed = The CAPTCHA challenge was not solved.
+error-rate-limited = Too many requests, retry in { $seconds } seconds.
+error-missing-token = Either a subscription token or a link is required.
+error-unknown-token = There is no subscriber associated with the provided token.
+error-unknown-subscriber = There is no subscriber associated with the provided link.
+error-unknown-issue = This issue is no longer available.
+error-invalid-link = This link doesn't work, it may have been mistyped or replaced by a newer one.
+error-expired-link = This link has expired.
+error-status-conflict = This subscription can't be changed that way anymore.
+error-unexpected = Something went wrong on our side, please try again later.
@@ -0,0 +1,60 @@
+confirmation-subject = Ласкаво просимо до нашої розсилки!
+confirmation-text =
+    Ласкаво просимо до нашої розсилки!
+    Перейдіть за посиланням { $link }, щоб підтвердити підписку.
+confirmation-heading = Ласкаво просимо до нашої розсилки!
+confirmation-html = Перейдіть за посиланням <a href="{ $link }">{ $link }</a>, щоб підтвердити підписку.
+
+reminder-subject = Не забудьте підтвердити підписку
+reminder-text =
+    Ви підписалися на нашу розсилку, але ще не підтвердили підписку.
+    Перейдіть за посиланням { $link }, щоб підтвердити підписку.
+reminder-heading = Не забудьте підтвердити підписку
+reminder-html = Ви підписалися на нашу розсилку, але ще не підтвердили підписку. Перейдіть за посиланням <a href="{ $link }">{ $link }</a>, щоб підтвердити підписку.
+
+subscribe-success = Будь ласка, перевірте свою поштову скриньку
+resend-confirmation-success = Якщо ця адреса очікує на підтвердження, новий лист уже в дорозі.
+newsletter-published = Розсилку успішно опубліковано.
+newsletter-unsubscribe-text = Більше не хочете отримувати ці листи? Відпишіться тут: { $link }
+newsletter-unsubscribe-html = Більше не хочете отримувати ці листи? <a href="{ $link }">Відписатися</a>.
+newsletter-view-in-browser-text = Лист відображається неправильно? Відкрийте його в браузері: { $link }
+newsletter-view-in-browser-html = Лист відображається неправильно? <a href="{ $link }">Відкрити в браузері</a>.
+
+page-confirmed-title = Ви підписані!
+page-confirmed-body = Дякуємо за підтвердження, наступний випуск уже прямує до вашої скриньки.
+page-already-confirmed-title = Ви вже підписані
+page-already-confirmed-body = Цю підписку вже підтверджено, більше нічого робити не потрібно.
+page-expired-title = Термін дії посилання минув
+page-expired-body = Посилання для підтвердження діють обмежений час. Підпишіться знову або попросіть новий лист із підтвердженням.
+page-invalid-title = Це посилання не працює
+page-invalid-body = Можливо, його замінило новіше або в ньому помилка. Підпишіться знову або попросіть новий лист із підтвердженням.
+page-failed-title = Щось пішло не так
+page-failed-body = Зараз не вдалося підтвердити підписку. Спробуйте перейти за посиланням ще раз за кілька хвилин.
+page-unsubscribe-title = Відписатися від розсилки?
+page-unsubscribe-body = Ви більше не отримуватимете випусків. Підписатися знову можна будь-коли.
+page-unsubscribe-button = Відписатися
+page-unsubscribed-title = Ви відписалися
+page-unsubscribed-body = Шкода, що ви йдете. Більше випусків не надходитиме.
+page-unsubscribe-invalid-title = Це посилання не працює
+page-unsubscribe-invalid-body = Можливо, термін його дії минув або в ньому помилка. Скористайтеся посиланням для відписки з новішого випуску.
+page-not-subscribed-title = Ви не підписані
+page-not-subscribed-body = Ця адреса не отримує розсилку, відписуватися немає від чого.
+page-unsubscribe-failed-title = Щось пішло не так
+page-unsubscribe-failed-body = Зараз не вдалося вас відписати. Спробуйте перейти за посиланням ще раз за кілька хвилин.
+
+error-invalid-name = { $name } не є коректним ім'ям.
+error-invalid-email = { $email } не є коректною адресою електронної пошти.
+error-disposable-email = Адреси на { $domain } одноразові, будь ласка, використайте постійну.
+error-likely-typo = Можливо, ви мали на увазі { $suggestion }?
+error-undeliverable-email = { $domain } не приймає пошту.
+error-form-token = Не вдалося перевірити форму, оновіть сторінку та спробуйте ще раз.
+error-captcha-failed = CAPTCHA не розв'язано.
+error-rate-limited = Забагато запитів, спробуйте знову через { $seconds } с.
+error-missing-token = Потрібен токен підписки або посилання.
+error-unknown-token = Для цього токена немає підписника.
+error-unknown-subscriber = Для цього посилання немає підписника.
+error-unknown-issue = Цей випуск більше не доступний.
+error-invalid-link = Це посилання не працює: можливо, в ньому помилка або його замінило новіше.
+error-expired-link = Термін дії посилання минув.
+error-status-conflict = Цю підписку більше не можна змінити таким чином.
+error-unexpected = У нас щось пішло не так, спробуйте пізніше.
@@ -0,0 +1,2 @@
+-- The backfilled statuses can't be told apart from real ones, so they stay.
+ALTER TABLE subscriptions ALTER COLUMN status DROP NOT NULL;
@@ -0,0 +1,6 @@
+CREATE TABLE rate_limits(
+key TEXT NOT NULL,
+PRIMARY KEY (key),
+window_started_at timestamptz NOT NULL,
+hits INTEGER NOT NULL
+);
@@ -0,0 +1,2 @@
+DROP INDEX subscriptions_email_canonical_key;
+ALTER TABLE subscriptions DROP COLUMN email_canonical;
@@ -0,0 +1,17 @@
+ALTER TABLE subscriptions ADD COLUMN email_canonical TEXT NULL;
+-- Only the row we would keep out of each group of case-insensitive duplicates
+-- gets its canonical form here. The others stay NULL, which the unique index
+-- tolerates, until `newsletter dedup-subscribers` merges them.
+-- `lower` only matches `SubscriberEmail::canonical` for plain ASCII addresses
+-- without a trailing dot. `newsletter dedup-subscribers` fills in the rest, e.g.
+-- internationalised domains that need punycode.
+UPDATE subscriptions s
+SET email_canonical = lower(s.email)
+WHERE s.email ~ '^[!-~]+$' AND s.email !~ '\.$'
+AND s.id = (
+  SELECT d.id FROM subscriptions d
+  WHERE lower(d.email) = lower(s.email)
+  ORDER BY (d.status = 'confirmed') DESC, d.subscribed_at ASC, d.id ASC
+  LIMIT 1
+);
+CREATE UNIQUE INDEX subscriptions_email_canonical_key ON subscriptions (email_canonical);
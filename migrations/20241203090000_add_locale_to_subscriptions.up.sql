@@ -0,0 +1,2 @@
+-- Add Migration Script
+ALTER TABLE subscriptions ADD COLUMN locale TEXT NOT NULL DEFAULT 'en';
@@ -0,0 +1,3 @@
+-- Keep in sync with `SubscriptionStatus`.
+ALTER TABLE subscriptions ADD CONSTRAINT subscriptions_status_check
+CHECK (status IN ('pending_confirmation', 'confirmed', 'unsubscribed', 'bounced'));
@@ -0,0 +1,3 @@
+-- Digests can't be turned back into tokens, pending subscribers have to sign up again.
+DELETE FROM subscription_tokens;
+ALTER TABLE subscription_tokens RENAME COLUMN token_hash TO subscription_token;
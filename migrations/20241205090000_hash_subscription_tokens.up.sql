@@ -0,0 +1,5 @@
+-- Tokens used to be stored as sent. Hash the existing ones the way `subscription_token_digest`
+-- does, so links already sitting in inboxes keep working.
+UPDATE subscription_tokens
+SET subscription_token = encode(sha256(convert_to(subscription_token, 'UTF8')), 'hex');
+ALTER TABLE subscription_tokens RENAME COLUMN subscription_token TO token_hash;
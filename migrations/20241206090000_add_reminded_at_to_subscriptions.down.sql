@@ -0,0 +1 @@
+ALTER TABLE subscriptions DROP COLUMN reminded_at;
@@ -0,0 +1,2 @@
+-- Set once a pending subscriber has been reminded to confirm, so they only get one reminder.
+ALTER TABLE subscriptions ADD COLUMN reminded_at TIMESTAMPTZ NULL;
@@ -0,0 +1,9 @@
+-- Published issues, kept so subscribers can open them in the browser.
+CREATE TABLE newsletter_issues(
+id uuid NOT NULL,
+PRIMARY KEY (id),
+title TEXT NOT NULL,
+-- The whole payload, translations included.
+content JSONB NOT NULL,
+published_at timestamptz NOT NULL
+);
@@ -0,0 +1,6 @@
+-- When the row's window is over and it can be deleted. Rows written before don't know
+-- their window, a day outlasts every configured one.
+ALTER TABLE rate_limits ADD COLUMN expires_at TIMESTAMPTZ NULL;
+UPDATE rate_limits SET expires_at = window_started_at + INTERVAL '1 day';
+ALTER TABLE rate_limits ALTER COLUMN expires_at SET NOT NULL;
+CREATE INDEX rate_limits_expires_at_idx ON rate_limits (expires_at);
@@ -0,0 +1,57 @@
+use pulldown_cmark::{html, Parser};
+
+use crate::routes::newsletter::{Content, NewsletterPayload};
+
+/// The first `# ` heading becomes the subject. The rest is sent as rendered HTML, and as
+/// the Markdown source for clients that only show plain text.
+pub fn issue_from_markdown(markdown: &str) -> Result<NewsletterPayload, anyhow::Error> {
+    let mut lines = markdown.lines();
+    let title = lines
+        .by_ref()
+        .find_map(|line| line.strip_prefix("# "))
+        .map(str::trim)
+        .filter(|title| !title.is_empty())
+        .ok_or_else(|| anyhow::anyhow!("The issue needs a `# Title` heading"))?
+        .to_string();
+    let body = lines.collect::<Vec<_>>().join("\n").trim().to_string();
+    if body.is_empty() {
+        anyhow::bail!("The issue has no content below its title");
+    }
+
+    let mut rendered = String::new();
+    html::push_html(&mut rendered, Parser::new(&body));
+
+    Ok(NewsletterPayload {
+        title,
+        content: Content {
+            text: body,
+            html: rendered,
+        },
+        translations: Default::default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::issue_from_markdown;
+
+    #[test]
+    fn the_first_heading_is_the_subject() {
+        let issue =
+            issue_from_markdown("# Issue 42\n\nHello **readers**.\n\n- one\n- two\n").unwrap();
+
+        assert_eq!(issue.title, "Issue 42");
+        assert_eq!(issue.content.text, "Hello **readers**.\n\n- one\n- two");
+        assert_eq!(
+            issue.content.html,
+            "<p>Hello <strong>readers</strong>.</p>\n<ul>\n<li>one</li>\n<li>two</li>\n</ul>\n"
+        );
+    }
+
+    #[test]
+    fn an_issue_without_title_or_body_is_rejected() {
+        assert!(issue_from_markdown("Hello, no heading here").is_err());
+        assert!(issue_from_markdown("## Not a top-level heading\n\nbody").is_err());
+        assert!(issue_from_markdown("# Title only\n\n").is_err());
+    }
+}
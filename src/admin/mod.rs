@@ -0,0 +1,207 @@
+//! One-off tasks for operators, run through the `newsletter` binary's subcommands.
+
+pub mod issue;
+pub mod subscribers;
+
+use std::{collections::BTreeMap, sync::Arc};
+
+use anyhow::Context;
+
+use self::{
+    issue::issue_from_markdown,
+    subscribers::{add_subscriber, confirm_subscriber_by_email},
+};
+use crate::{
+    cli::{Command, MigrateCommand, SubscribersCommand},
+    configuration::{aws_credentials::StaticCredentials, config::Configuration},
+    database::{
+        db::Database,
+        dedup::merge_duplicate_subscribers,
+        issues::store_issue,
+        migrations::{migrate_down, migrate_up, schema_status},
+        subscribers::{
+            postgres::PostgresSubscriberRepository, ConfirmedSubscribers, SubscriberRepository,
+        },
+    },
+    domain::{NewSubscriber, SubscriberEmail, SubscriberName},
+    i18n::Localizer,
+    metrics::Metrics,
+    pending_cleanup::PendingCleanup,
+    routes::{
+        newsletter::{deliver_issue, IssueLinks},
+        subscriptions::send_confirmation_email,
+    },
+    ses_workflow::SESWorkflow,
+    signed_links::LinkSigner,
+    startup::{configure_sdk_config, create_aws_client},
+};
+
+/// Runs every command but `serve`, printing the outcome to stdout.
+pub async fn run(configuration: &Configuration, command: Command) -> Result<(), anyhow::Error> {
+    let db = Database::new(&configuration.database);
+    let repository = PostgresSubscriberRepository::new(db.pool.clone());
+
+    let migrates_itself = matches!(command, Command::Serve | Command::Migrate(_));
+    if configuration.database.auto_migrate && !migrates_itself {
+        migrate_up(&db.pool).await?;
+    }
+
+    match command {
+        Command::Serve => anyhow::bail!("`serve` starts the server, it isn't a one-off task"),
+        Command::Migrate(command) => {
+            match command {
+                MigrateCommand::Up => migrate_up(&db.pool).await?,
+                MigrateCommand::Down { version } => migrate_down(&db.pool, version).await?,
+                MigrateCommand::Status => {}
+            }
+            print!("{}", schema_status(&db.pool).await?);
+        }
+        Command::DedupSubscribers => {
+            let report = merge_duplicate_subscribers(&db.pool).await?;
+            print!("{}", report);
+        }
+        Command::Subscribers(command) => subscribers(configuration, &repository, command).await?,
+        Command::CleanupPending => {
+            let cleanup = PendingCleanup::new(
+                &configuration.pending_cleanup,
+                Arc::new(repository),
+                Arc::new(LinkSigner::new(&configuration.signed_links)),
+                Arc::new(ses(configuration).await?),
+                Arc::new(Localizer::new()),
+                Arc::new(Metrics::new()),
+                configuration.application.base_url.clone(),
+            );
+            println!("{}.", cleanup.run_once().await?);
+        }
+        Command::SendTestEmail { address } => {
+            let recipient = SubscriberEmail::parse(address).map_err(anyhow::Error::msg)?;
+            ses(configuration)
+                .await?
+                .send_email(
+                    &recipient,
+                    "Newsletter test email",
+                    "SES is set up correctly.",
+                    "<p>SES is set up correctly.</p>",
+                )
+                .await?;
+            println!("Sent a test email to {}.", recipient);
+        }
+        Command::Publish { file, dry_run } => {
+            let markdown = std::fs::read_to_string(&file)
+                .with_context(|| format!("Failed to read {}", file.display()))?;
+            let issue = issue_from_markdown(&markdown)?;
+
+            if dry_run {
+                let mut per_locale = BTreeMap::<String, usize>::new();
+                let mut invalid = 0;
+                let mut pages = ConfirmedSubscribers::new(&repository, 1000);
+                while let Some(page) = pages.next_page().await? {
+                    for subscriber in page {
+                        match subscriber {
+                            Ok(subscriber) => {
+                                *per_locale.entry(subscriber.locale).or_default() += 1
+                            }
+                            Err(_) => invalid += 1,
+                        }
+                    }
+                }
+                println!("Would send \"{}\" to:", issue.title);
+                for (locale, count) in per_locale {
+                    println!("  {} confirmed subscriber(s) with locale {}", count, locale);
+                }
+                if invalid > 0 {
+                    println!("  and skip {} with an invalid email", invalid);
+                }
+                return Ok(());
+            }
+
+            let issue_id = store_issue(&db.pool, &issue).await?;
+            let report = deliver_issue(
+                &ses(configuration).await?,
+                &repository,
+                &Metrics::new(),
+                &IssueLinks {
+                    links: &LinkSigner::new(&configuration.signed_links),
+                    localizer: &Localizer::new(),
+                    base_url: &configuration.application.base_url,
+                    issue_id: Some(issue_id),
+                },
+                &issue,
+            )
+            .await?;
+            println!("\"{}\" {}.", issue.title, report);
+        }
+    }
+    Ok(())
+}
+
+async fn subscribers(
+    configuration: &Configuration,
+    repository: &dyn SubscriberRepository,
+    command: SubscribersCommand,
+) -> Result<(), anyhow::Error> {
+    match command {
+        SubscribersCommand::List { status } => {
+            for subscriber in repository.list(status).await? {
+                println!("{}", subscriber);
+            }
+        }
+        SubscribersCommand::Add {
+            email,
+            name,
+            locale,
+            confirmed,
+        } => {
+            let localizer = Localizer::new();
+            let new_subscriber = NewSubscriber {
+                email: SubscriberEmail::parse(email).map_err(anyhow::Error::msg)?,
+                name: SubscriberName::parse(name).map_err(anyhow::Error::msg)?,
+                locale: localizer.negotiate([locale.as_str()]).to_string(),
+            };
+
+            let links = LinkSigner::new(&configuration.signed_links);
+            let base_url = &configuration.application.base_url;
+            match add_subscriber(repository, &links, base_url, &new_subscriber, confirmed).await? {
+                None => println!("Added {} as confirmed.", new_subscriber.email),
+                Some(confirmation_link) => {
+                    send_confirmation_email(
+                        Arc::new(ses(configuration).await?),
+                        &localizer,
+                        &new_subscriber.locale,
+                        new_subscriber.email.clone(),
+                        &confirmation_link,
+                    )
+                    .await?;
+                    println!(
+                        "Added {}, a confirmation email is on its way.",
+                        new_subscriber.email
+                    );
+                }
+            }
+        }
+        SubscribersCommand::Remove { email } => {
+            let email = SubscriberEmail::parse(email).map_err(anyhow::Error::msg)?;
+            if !repository.remove(&email).await? {
+                anyhow::bail!("{} is not subscribed", email);
+            }
+            println!("Removed {}.", email);
+        }
+        SubscribersCommand::Confirm { email } => {
+            let email = SubscriberEmail::parse(email).map_err(anyhow::Error::msg)?;
+            if !confirm_subscriber_by_email(repository, &email).await? {
+                anyhow::bail!("{} is not subscribed", email);
+            }
+            println!("Confirmed {}.", email);
+        }
+    }
+    Ok(())
+}
+
+async fn ses(configuration: &Configuration) -> Result<SESWorkflow, anyhow::Error> {
+    let static_credentials = StaticCredentials::from_configuration(&configuration.aws);
+    let sdk_config = configure_sdk_config(configuration, static_credentials).await?;
+    Ok(SESWorkflow::new(
+        create_aws_client(&sdk_config)?,
+        configuration.aws.verified_email.clone(),
+    ))
+}
@@ -0,0 +1,65 @@
+use anyhow::Context;
+
+use crate::{
+    database::subscribers::{SubscriberRepository, SubscriberRow},
+    domain::{NewSubscriber, SubscriberEmail, SubscriptionStatus},
+    routes::subscriptions::register_pending,
+    signed_links::LinkSigner,
+};
+
+impl std::fmt::Display for SubscriberRow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}\t{}\t{}\t{}\t{}",
+            self.email,
+            self.name,
+            self.status,
+            self.locale,
+            self.subscribed_at.format("%Y-%m-%d %H:%M")
+        )
+    }
+}
+
+/// Stores the subscriber, returns the link to put in their confirmation email.
+/// `confirmed` subscribers need no link and get `None`.
+pub async fn add_subscriber(
+    subscribers: &dyn SubscriberRepository,
+    links: &LinkSigner,
+    base_url: &str,
+    new_subscriber: &NewSubscriber,
+    confirmed: bool,
+) -> Result<Option<String>, anyhow::Error> {
+    let failed = || {
+        format!(
+            "Failed to add {}, is it subscribed already?",
+            new_subscriber.email
+        )
+    };
+    if confirmed {
+        subscribers
+            .insert_confirmed(new_subscriber)
+            .await
+            .with_context(failed)?;
+        return Ok(None);
+    }
+    let link = register_pending(subscribers, links, base_url, new_subscriber)
+        .await
+        .with_context(failed)?
+        .with_context(failed)?;
+    Ok(Some(link))
+}
+
+/// `false` if there is no such subscriber.
+pub async fn confirm_subscriber_by_email(
+    subscribers: &dyn SubscriberRepository,
+    email: &SubscriberEmail,
+) -> Result<bool, anyhow::Error> {
+    let Some(subscriber_id) = subscribers.subscriber_id_from_email(email).await? else {
+        return Ok(false);
+    };
+    subscribers
+        .change_status(subscriber_id, SubscriptionStatus::Confirmed)
+        .await?;
+    Ok(true)
+}
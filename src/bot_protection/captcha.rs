@@ -0,0 +1,38 @@
+use std::net::IpAddr;
+
+use async_trait::async_trait;
+
+/// A CAPTCHA provider (hCaptcha, Turnstile, reCAPTCHA, ...) able to check
+/// the response token the browser submitted alongside the form.
+#[async_trait]
+pub trait CaptchaVerifier: Send + Sync {
+    async fn verify(
+        &self,
+        response_token: &str,
+        remote_ip: Option<IpAddr>,
+    ) -> Result<bool, anyhow::Error>;
+}
+
+/// Accepts a single, known response token. Meant for local runs and tests.
+pub struct StubCaptchaVerifier {
+    expected_token: String,
+}
+
+impl StubCaptchaVerifier {
+    pub fn new(expected_token: impl Into<String>) -> Self {
+        Self {
+            expected_token: expected_token.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl CaptchaVerifier for StubCaptchaVerifier {
+    async fn verify(
+        &self,
+        response_token: &str,
+        _remote_ip: Option<IpAddr>,
+    ) -> Result<bool, anyhow::Error> {
+        Ok(response_token == self.expected_token)
+    }
+}
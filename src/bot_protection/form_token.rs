@@ -0,0 +1,150 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, SecretString};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum FormTokenError {
+    #[error("The form token is malformed.")]
+    Malformed,
+    #[error("The form token signature does not match.")]
+    BadSignature,
+    #[error("The form was submitted too quickly.")]
+    TooFast,
+    #[error("The form token has expired.")]
+    Expired,
+}
+
+/// Signs and checks the render timestamp carried by the subscription form.
+///
+/// A token looks like `<unix seconds>.<hex hmac>`, so it can be embedded in a
+/// hidden input without any server-side storage.
+pub struct FormTokenSigner {
+    secret: SecretString,
+    min_age: u64,
+    max_age: u64,
+}
+
+impl FormTokenSigner {
+    pub fn new(secret: SecretString, min_age: u64, max_age: u64) -> Self {
+        Self {
+            secret,
+            min_age,
+            max_age,
+        }
+    }
+
+    pub fn issue(&self) -> String {
+        self.issue_at(now())
+    }
+
+    pub fn issue_at(&self, issued_at: u64) -> String {
+        let signature = hex::encode(self.mac(issued_at).finalize().into_bytes());
+        format!("{}.{}", issued_at, signature)
+    }
+
+    pub fn verify(&self, token: &str) -> Result<(), FormTokenError> {
+        self.verify_at(token, now())
+    }
+
+    pub fn verify_at(&self, token: &str, now: u64) -> Result<(), FormTokenError> {
+        let (issued_at, signature) = token.split_once('.').ok_or(FormTokenError::Malformed)?;
+        let issued_at: u64 = issued_at.parse().map_err(|_| FormTokenError::Malformed)?;
+        let signature = hex::decode(signature).map_err(|_| FormTokenError::Malformed)?;
+
+        // `verify_slice` compares in constant time.
+        self.mac(issued_at)
+            .verify_slice(&signature)
+            .map_err(|_| FormTokenError::BadSignature)?;
+
+        let age = now.saturating_sub(issued_at);
+        if age < self.min_age {
+            return Err(FormTokenError::TooFast);
+        }
+        if age > self.max_age {
+            return Err(FormTokenError::Expired);
+        }
+        Ok(())
+    }
+
+    fn mac(&self, issued_at: u64) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(self.secret.expose_secret().as_bytes())
+            .expect("HMAC can take a key of any size");
+        mac.update(issued_at.to_string().as_bytes());
+        mac
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("System clock is before the UNIX epoch")
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use claim::assert_ok;
+    use secrecy::SecretString;
+
+    use super::{FormTokenError, FormTokenSigner};
+
+    fn signer() -> FormTokenSigner {
+        FormTokenSigner::new(SecretString::from("secret"), 3, 60)
+    }
+
+    #[test]
+    fn a_token_submitted_in_time_is_accepted() {
+        let token = signer().issue_at(1_000);
+        assert_ok!(signer().verify_at(&token, 1_010));
+    }
+
+    #[test]
+    fn a_token_submitted_too_quickly_is_rejected() {
+        let token = signer().issue_at(1_000);
+        assert_eq!(
+            signer().verify_at(&token, 1_001),
+            Err(FormTokenError::TooFast)
+        );
+    }
+
+    #[test]
+    fn an_old_token_is_rejected() {
+        let token = signer().issue_at(1_000);
+        assert_eq!(
+            signer().verify_at(&token, 2_000),
+            Err(FormTokenError::Expired)
+        );
+    }
+
+    #[test]
+    fn a_token_with_a_tampered_timestamp_is_rejected() {
+        let token = signer().issue_at(1_000).replacen("1000", "900", 1);
+        assert_eq!(
+            signer().verify_at(&token, 1_010),
+            Err(FormTokenError::BadSignature)
+        );
+    }
+
+    #[test]
+    fn a_token_signed_with_another_secret_is_rejected() {
+        let token = FormTokenSigner::new(SecretString::from("other"), 3, 60).issue_at(1_000);
+        assert_eq!(
+            signer().verify_at(&token, 1_010),
+            Err(FormTokenError::BadSignature)
+        );
+    }
+
+    #[test]
+    fn garbage_is_rejected_as_malformed() {
+        for token in ["", "1000", "abc.def", "1000.zz"] {
+            assert_eq!(
+                signer().verify_at(token, 1_010),
+                Err(FormTokenError::Malformed)
+            );
+        }
+    }
+}
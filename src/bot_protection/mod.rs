@@ -0,0 +1,108 @@
+pub mod captcha;
+pub mod form_token;
+
+use std::{net::IpAddr, sync::Arc, time::Duration};
+
+use captcha::CaptchaVerifier;
+use form_token::{FormTokenError, FormTokenSigner};
+
+use crate::{
+    configuration::config::BotProtectionConfiguration,
+    rate_limit::{Quota, RateLimitStore},
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum BotCheckError {
+    #[error("The form token is missing.")]
+    MissingFormToken,
+    #[error(transparent)]
+    InvalidFormToken(#[from] FormTokenError),
+    #[error("The CAPTCHA challenge was not solved.")]
+    CaptchaFailed,
+    #[error("Too many subscription attempts, retry in {} seconds.", .0.as_secs())]
+    RateLimited(Duration),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+/// Layered defences for the public subscription form.
+pub struct BotProtection {
+    pub form_tokens: FormTokenSigner,
+    require_form_token: bool,
+    limiter: Arc<dyn RateLimitStore>,
+    per_ip: Quota,
+    per_domain: Quota,
+    captcha: Option<Arc<dyn CaptchaVerifier>>,
+}
+
+impl BotProtection {
+    /// CAPTCHA verification is only enforced when a `captcha` verifier is plugged in.
+    /// Attempts are counted in `limiter`, the same store the route limits use.
+    pub fn new(
+        configuration: &BotProtectionConfiguration,
+        limiter: Arc<dyn RateLimitStore>,
+        captcha: Option<Arc<dyn CaptchaVerifier>>,
+    ) -> Self {
+        let window = Duration::from_secs(configuration.rate_limit_window_seconds);
+        Self {
+            form_tokens: FormTokenSigner::new(
+                configuration.form_secret.clone(),
+                configuration.min_submit_seconds,
+                configuration.max_form_age_seconds,
+            ),
+            require_form_token: configuration.require_form_token,
+            limiter,
+            per_ip: Quota::new(configuration.per_ip_limit, window),
+            per_domain: Quota::new(configuration.per_domain_limit, window),
+            captcha,
+        }
+    }
+
+    pub fn check_form_token(&self, token: Option<&str>) -> Result<(), BotCheckError> {
+        match token {
+            Some(token) => Ok(self.form_tokens.verify(token)?),
+            None if self.require_form_token => Err(BotCheckError::MissingFormToken),
+            None => Ok(()),
+        }
+    }
+
+    pub async fn check_captcha(
+        &self,
+        token: Option<&str>,
+        remote_ip: Option<IpAddr>,
+    ) -> Result<(), BotCheckError> {
+        let Some(captcha) = &self.captcha else {
+            return Ok(());
+        };
+        let token = token.ok_or(BotCheckError::CaptchaFailed)?;
+        if captcha.verify(token, remote_ip).await? {
+            Ok(())
+        } else {
+            Err(BotCheckError::CaptchaFailed)
+        }
+    }
+
+    /// Requests whose origin is unknown are not counted.
+    pub async fn check_ip(&self, ip: Option<IpAddr>) -> Result<(), BotCheckError> {
+        match ip {
+            Some(ip) => self.hit(&format!("bot:ip:{}", ip), self.per_ip).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Caps how many confirmation emails a single mail domain can receive.
+    pub async fn check_domain(&self, domain: &str) -> Result<(), BotCheckError> {
+        self.hit(
+            &format!("bot:domain:{}", domain.to_lowercase()),
+            self.per_domain,
+        )
+        .await
+    }
+
+    async fn hit(&self, key: &str, quota: Quota) -> Result<(), BotCheckError> {
+        self.limiter
+            .hit(key, quota)
+            .await?
+            .map_err(BotCheckError::RateLimited)
+    }
+}
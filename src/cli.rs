@@ -0,0 +1,146 @@
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
+
+use crate::domain::SubscriptionStatus;
+
+/// Newsletter service and the admin tasks that go with it.
+#[derive(Parser, Debug)]
+#[command(name = "newsletter", version)]
+pub struct Cli {
+    /// Validate the configuration and exit.
+    #[arg(long, global = true)]
+    pub check_config: bool,
+    /// Print the merged configuration, secrets redacted, and exit.
+    #[arg(long, global = true)]
+    pub print_config: bool,
+    /// Defaults to `serve`.
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug, PartialEq)]
+pub enum Command {
+    /// Run the HTTP server.
+    Serve,
+    /// Apply, inspect or revert database migrations.
+    #[command(subcommand)]
+    Migrate(MigrateCommand),
+    /// Merge subscribers that only differ in the case of their email, run once after upgrading.
+    DedupSubscribers,
+    /// Manage subscribers without going through the public form.
+    #[command(subcommand)]
+    Subscribers(SubscribersCommand),
+    /// Remind and purge stale pending subscribers once, like the background job does.
+    CleanupPending,
+    /// Send a fixed test email, to check the SES setup.
+    SendTestEmail {
+        /// Recipient, must be verified while the SES account is in the sandbox.
+        address: String,
+    },
+    /// Send a Markdown issue to every confirmed subscriber. The first `# ` heading is the subject.
+    Publish {
+        #[arg(long)]
+        file: PathBuf,
+        /// Only show who would get the issue.
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand, Debug, PartialEq)]
+pub enum MigrateCommand {
+    /// Apply every pending migration.
+    Up,
+    /// List migrations and whether they are applied.
+    Status,
+    /// Revert every migration newer than `version`, `0` reverts everything.
+    Down { version: i64 },
+}
+
+#[derive(Subcommand, Debug, PartialEq)]
+pub enum SubscribersCommand {
+    List {
+        /// `pending_confirmation`, `confirmed`, `unsubscribed` or `bounced`.
+        #[arg(long)]
+        status: Option<SubscriptionStatus>,
+    },
+    /// Add a subscriber and send them the confirmation email, unless `--confirmed`.
+    Add {
+        email: String,
+        name: String,
+        #[arg(long, default_value = "en")]
+        locale: String,
+        /// Skip the confirmation email, only for people who already agreed elsewhere.
+        #[arg(long)]
+        confirmed: bool,
+    },
+    Remove {
+        email: String,
+    },
+    /// Confirm a subscriber on their behalf.
+    Confirm {
+        email: String,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::{CommandFactory, Parser};
+
+    use super::{Cli, Command, MigrateCommand, SubscribersCommand};
+
+    #[test]
+    fn the_command_line_is_well_formed() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn no_subcommand_means_serve() {
+        let cli = Cli::try_parse_from(["newsletter"]).unwrap();
+        assert_eq!(cli.command, None);
+
+        let cli = Cli::try_parse_from(["newsletter", "--check-config"]).unwrap();
+        assert!(cli.check_config);
+    }
+
+    #[test]
+    fn subcommands_parse() {
+        let cli = Cli::try_parse_from(["newsletter", "migrate", "down", "20241202090000"]).unwrap();
+        assert_eq!(
+            cli.command,
+            Some(Command::Migrate(MigrateCommand::Down {
+                version: 20241202090000
+            }))
+        );
+
+        let cli = Cli::try_parse_from([
+            "newsletter",
+            "subscribers",
+            "add",
+            "ursula@example.com",
+            "Ursula Le Guin",
+            "--confirmed",
+        ])
+        .unwrap();
+        assert_eq!(
+            cli.command,
+            Some(Command::Subscribers(SubscribersCommand::Add {
+                email: "ursula@example.com".into(),
+                name: "Ursula Le Guin".into(),
+                locale: "en".into(),
+                confirmed: true,
+            }))
+        );
+
+        let cli = Cli::try_parse_from(["newsletter", "publish", "--file", "issue.md", "--dry-run"])
+            .unwrap();
+        assert_eq!(
+            cli.command,
+            Some(Command::Publish {
+                file: "issue.md".into(),
+                dry_run: true,
+            })
+        );
+    }
+}
@@ -1,28 +1,59 @@
+use std::sync::{Arc, RwLock};
+
 use aws_credential_types::provider::ProvideCredentials;
 use aws_sdk_sesv2::config::Credentials;
+use secrecy::{ExposeSecret, SecretString};
+
+use super::config::{AwsConfiguration, AwsCredentialsMode};
 
-#[derive(Debug)]
+/// Keys from the configuration. Clones share the keys, so `rotate` on one
+/// is picked up by the SES client holding another.
+#[derive(Debug, Clone)]
 pub struct StaticCredentials {
-    pub access_key_id: String,
-    pub secret_access_key: String,
+    credentials: Arc<RwLock<Credentials>>,
 }
 
 impl StaticCredentials {
-    pub fn new(access_key_id: String, secret_access_key: String) -> Self {
+    pub fn new(access_key_id: &SecretString, secret_access_key: &SecretString) -> Self {
         Self {
-            access_key_id: access_key_id.trim().to_string(),
-            secret_access_key: secret_access_key.trim().to_string(),
+            credentials: Arc::new(RwLock::new(Self::credentials(
+                access_key_id,
+                secret_access_key,
+            ))),
         }
     }
 
-    async fn load_credentials(&self) -> aws_credential_types::provider::Result {
-        Ok(Credentials::new(
-            self.access_key_id.clone(),
-            self.secret_access_key.clone(),
+    /// `None` unless the configuration asks for static keys and has both of them.
+    pub fn from_configuration(configuration: &AwsConfiguration) -> Option<Self> {
+        match (
+            configuration.credentials_mode,
+            &configuration.access_key_id,
+            &configuration.secret_access_key,
+        ) {
+            (AwsCredentialsMode::Static, Some(access_key_id), Some(secret_access_key)) => {
+                Some(Self::new(access_key_id, secret_access_key))
+            }
+            _ => None,
+        }
+    }
+
+    /// Requests signed from now on use the new keys.
+    pub fn rotate(&self, access_key_id: &SecretString, secret_access_key: &SecretString) {
+        *self.credentials.write().unwrap() = Self::credentials(access_key_id, secret_access_key);
+    }
+
+    fn credentials(access_key_id: &SecretString, secret_access_key: &SecretString) -> Credentials {
+        Credentials::new(
+            access_key_id.expose_secret().trim(),
+            secret_access_key.expose_secret().trim(),
             None,
             None,
             "StaticCredentials",
-        ))
+        )
+    }
+
+    async fn load_credentials(&self) -> aws_credential_types::provider::Result {
+        Ok(self.credentials.read().unwrap().clone())
     }
 }
 
@@ -36,3 +67,36 @@ impl ProvideCredentials for StaticCredentials {
         aws_credential_types::provider::future::ProvideCredentials::new(self.load_credentials())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use aws_credential_types::provider::ProvideCredentials;
+    use secrecy::SecretString;
+
+    use super::StaticCredentials;
+
+    #[tokio::test]
+    async fn rotated_keys_are_seen_by_every_clone() {
+        let credentials =
+            StaticCredentials::new(&SecretString::from("old-id"), &SecretString::from("old"));
+        let held_by_client = credentials.clone();
+
+        credentials.rotate(&SecretString::from("new-id"), &SecretString::from("new"));
+
+        let provided = held_by_client.provide_credentials().await.unwrap();
+        assert_eq!(provided.access_key_id(), "new-id");
+        assert_eq!(provided.secret_access_key(), "new");
+    }
+
+    #[test]
+    fn debug_output_does_not_leak_the_secret_key() {
+        let credentials = StaticCredentials::new(
+            &SecretString::from("AKIDEXAMPLE"),
+            &SecretString::from("s3cr3t"),
+        );
+
+        let debug = format!("{:?}", credentials);
+
+        assert!(!debug.contains("s3cr3t"), "{}", debug);
+    }
+}
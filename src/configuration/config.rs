@@ -1,19 +1,41 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
 use config::builder::DefaultState;
+use ipnet::IpNet;
 use secrecy::{ExposeSecret, SecretString};
 use serde_aux::field_attributes::deserialize_number_from_string;
-use sqlx::postgres::{PgConnectOptions, PgSslMode};
+use sqlx::{
+    postgres::{PgConnectOptions, PgPoolOptions, PgSslMode},
+    ConnectOptions,
+};
 
-use super::environment::Environment;
+use super::{
+    environment::Environment,
+    secrets::{redact_secrets, resolve_secret_files},
+};
 
 #[derive(serde::Deserialize)]
 pub struct Configuration {
     pub database: DatabaseConfiguration,
     pub application: ApplicationConfiguration,
     pub aws: AwsConfiguration,
+    pub bot_protection: BotProtectionConfiguration,
+    pub rate_limit: RateLimitConfiguration,
+    pub email_validation: EmailValidationConfiguration,
+    pub telemetry: TelemetryConfiguration,
+    pub health: HealthConfiguration,
+    pub signed_links: SignedLinksConfiguration,
+    pub pending_cleanup: PendingCleanupConfiguration,
 }
 
 #[derive(serde::Deserialize)]
 pub struct DatabaseConfiguration {
+    /// A full `postgres://` URL, replaces the connection settings below.
+    pub url: Option<SecretString>,
     pub username: String,
     pub password: SecretString,
     #[serde(deserialize_with = "deserialize_number_from_string")]
@@ -21,6 +43,28 @@ pub struct DatabaseConfiguration {
     pub host: String,
     pub database_name: String,
     pub require_ssl: bool,
+    /// Check the server certificate and host name, i.e. `sslmode=verify-full`.
+    pub ssl_verify_full: bool,
+    /// CA bundle the server certificate is checked against, the built-in roots otherwise.
+    pub ssl_root_cert: Option<String>,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_connections: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub min_connections: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub acquire_timeout_seconds: u64,
+    /// Close connections idle for longer than this, `0` keeps them open.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub idle_timeout_seconds: u64,
+    /// Postgres cancels statements running longer than this, `0` means no limit.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub statement_timeout_milliseconds: u64,
+    /// Statements slower than this are logged as warnings.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub slow_query_milliseconds: u64,
+    /// Apply pending migrations on startup. Turn off to run `newsletter migrate up` as a
+    /// separate deploy step, with a role that has DDL rights.
+    pub auto_migrate: bool,
 }
 
 #[derive(serde::Deserialize)]
@@ -31,53 +75,361 @@ pub struct ApplicationConfiguration {
     pub base_url: String,
     pub logger_name: String,
     pub default_env_filter: String,
+    /// How long in-flight requests and workers get to finish once a shutdown signal arrives.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub shutdown_timeout_seconds: u64,
+    /// Re-read the configuration on SIGHUP and rotate the database and SES credentials.
+    pub reload_on_sighup: bool,
+    /// Where browsers go once they have confirmed, instead of our own page.
+    pub confirmed_redirect_url: Option<String>,
 }
 
 #[derive(serde::Deserialize, Debug)]
 pub struct AwsConfiguration {
     pub region: String,
     pub verified_email: String,
-    pub access_key_id: String,
-    pub secret_access_key: String,
+    /// Send SES calls here instead of AWS, e.g. to a local SES emulator.
+    pub endpoint_url: Option<String>,
+    pub credentials_mode: AwsCredentialsMode,
+    /// Only read in `static` mode.
+    pub access_key_id: Option<SecretString>,
+    pub secret_access_key: Option<SecretString>,
+    /// Only read in `assume_role` mode.
+    pub role_arn: Option<String>,
+    pub role_session_name: String,
+    pub external_id: Option<String>,
+}
+
+#[derive(serde::Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AwsCredentialsMode {
+    /// `access_key_id` and `secret_access_key` from this configuration.
+    Static,
+    /// Environment, shared profile, web identity token, then ECS/EC2 instance metadata.
+    DefaultChain,
+    /// Assume `role_arn` with credentials from the default chain.
+    AssumeRole,
+}
+
+#[derive(serde::Deserialize)]
+pub struct BotProtectionConfiguration {
+    /// Key used to sign the timestamp embedded in the subscription form.
+    pub form_secret: SecretString,
+    /// Reject submissions that do not carry a valid signed form token.
+    pub require_form_token: bool,
+    /// Humans need a few seconds to fill the form, bots usually don't.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub min_submit_seconds: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_form_age_seconds: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub per_ip_limit: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub per_domain_limit: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub rate_limit_window_seconds: u64,
+}
+
+#[derive(serde::Deserialize)]
+pub struct RateLimitConfiguration {
+    pub backend: RateLimitBackend,
+    /// Networks whose `X-Forwarded-For` header we believe.
+    #[serde(default)]
+    pub trusted_proxies: Vec<IpNet>,
+    pub subscriptions: RouteRateLimitConfiguration,
+    pub subscriptions_confirm: RouteRateLimitConfiguration,
+    pub resend_confirmation: RouteRateLimitConfiguration,
+    /// How long an address waits between two resent confirmation emails.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub resend_cooldown_seconds: u64,
+    pub newsletters: RouteRateLimitConfiguration,
+}
+
+#[derive(serde::Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RateLimitBackend {
+    Memory,
+    /// Share counters between instances.
+    Postgres,
+}
+
+#[derive(serde::Deserialize)]
+pub struct RouteRateLimitConfiguration {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub limit: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub window_seconds: u64,
+}
+
+#[derive(serde::Deserialize)]
+pub struct EmailValidationConfiguration {
+    pub reject_disposable: bool,
+    pub suggest_typos: bool,
+    /// Look up the MX records of every new subscriber's domain.
+    pub check_mx: bool,
+}
+
+#[derive(serde::Deserialize)]
+pub struct TelemetryConfiguration {
+    /// Export spans to an OpenTelemetry collector on top of the bunyan logs.
+    pub otlp_enabled: bool,
+    /// OTLP/HTTP traces endpoint, e.g. `http://localhost:4318/v1/traces`.
+    pub otlp_endpoint: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct HealthConfiguration {
+    /// Upper bound for each readiness check.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub check_timeout_milliseconds: u64,
+    /// Call SES `GetAccount` on readiness probes, it counts against the API quota.
+    pub check_ses: bool,
+}
+
+#[derive(serde::Deserialize)]
+pub struct SignedLinksConfiguration {
+    /// Send signed confirmation links instead of storing a token per subscriber.
+    pub confirmation_links: bool,
+    /// HMAC keys by id. Links signed with any of them are accepted: keep a retired key
+    /// until the links it signed have expired.
+    #[serde(default)]
+    pub keys: HashMap<String, SecretString>,
+    /// Id of the key new links are signed with.
+    pub signing_key: Option<String>,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub confirm_ttl_seconds: u64,
+    /// Also how long view-in-browser and tracked links in issues work.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub unsubscribe_ttl_seconds: u64,
+    /// Send links in issues through `/newsletters/click` to count clicks.
+    pub track_clicks: bool,
+}
+
+#[derive(serde::Deserialize)]
+pub struct PendingCleanupConfiguration {
+    /// Run the job in the background while serving. `cleanup-pending` runs it on demand.
+    pub enabled: bool,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub interval_seconds: u64,
+    /// How long after signing up an unconfirmed subscriber gets their one reminder.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub remind_after_seconds: u64,
+    /// How long after signing up an unconfirmed subscriber is deleted.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub purge_after_seconds: u64,
 }
 
 impl DatabaseConfiguration {
     pub fn without_db(&self) -> PgConnectOptions {
-        // Try an encrypted connection, fallback to unencrypted if it fails
-        let ssl_mode = if self.require_ssl {
-            PgSslMode::Require
-        } else {
-            PgSslMode::Prefer
+        let mut options = match &self.url {
+            Some(url) => url
+                .expose_secret()
+                .parse::<PgConnectOptions>()
+                .expect("database.url is checked by `validate`"),
+            None => PgConnectOptions::new()
+                .host(&self.host)
+                .username(&self.username)
+                .password(self.password.expose_secret())
+                .port(self.port),
         };
-        PgConnectOptions::new()
-            .host(&self.host)
-            .username(&self.username)
-            .password(self.password.expose_secret())
-            .port(self.port)
-            .ssl_mode(ssl_mode)
+
+        // A URL keeps its own `sslmode` unless the settings ask for more.
+        if self.url.is_none() || self.require_ssl || self.ssl_verify_full {
+            options = options.ssl_mode(self.ssl_mode());
+        }
+        if let Some(ssl_root_cert) = &self.ssl_root_cert {
+            options = options.ssl_root_cert(ssl_root_cert);
+        }
+        if self.statement_timeout_milliseconds > 0 {
+            options = options.options([("statement_timeout", self.statement_timeout_milliseconds)]);
+        }
+        options.log_slow_statements(
+            log::LevelFilter::Warn,
+            Duration::from_millis(self.slow_query_milliseconds),
+        )
     }
 
     pub fn with_db(&self) -> PgConnectOptions {
-        self.without_db().database(&self.database_name)
+        let options = self.without_db();
+        match options.get_database() {
+            // Named by the URL.
+            Some(_) => options,
+            None => options.database(&self.database_name),
+        }
     }
-}
 
-pub fn get_configuration() -> Result<Configuration, config::ConfigError> {
-    let base_path = std::env::current_dir().expect("Failed to get current directory.");
-    let config_directory = base_path.join("configuration");
+    pub fn pool_options(&self) -> PgPoolOptions {
+        PgPoolOptions::new()
+            .max_connections(self.max_connections)
+            .min_connections(self.min_connections)
+            .acquire_timeout(Duration::from_secs(self.acquire_timeout_seconds))
+            .idle_timeout(
+                (self.idle_timeout_seconds > 0)
+                    .then(|| Duration::from_secs(self.idle_timeout_seconds)),
+            )
+    }
 
-    let builder = config::ConfigBuilder::<DefaultState>::default()
-        .add_source(config::File::from(config_directory.join("base")).required(true));
+    fn ssl_mode(&self) -> PgSslMode {
+        if self.ssl_verify_full {
+            PgSslMode::VerifyFull
+        } else if self.require_ssl {
+            PgSslMode::Require
+        } else {
+            // Try an encrypted connection, fallback to unencrypted if it fails
+            PgSslMode::Prefer
+        }
+    }
+}
+
+/// `APP_CONFIG_DIR`, or `configuration/` in the working directory.
+pub fn configuration_directory() -> PathBuf {
+    match std::env::var_os("APP_CONFIG_DIR") {
+        Some(directory) => directory.into(),
+        None => std::env::current_dir()
+            .expect("Failed to get current directory.")
+            .join("configuration"),
+    }
+}
 
-    let environment: Environment = std::env::var("APP_ENVIRONMENT")
+/// `APP_ENVIRONMENT`, `local` when unset.
+pub fn get_environment() -> Result<Environment, config::ConfigError> {
+    std::env::var("APP_ENVIRONMENT")
         .unwrap_or_else(|_| "local".into())
         .try_into()
-        .expect("Failed to read APP_ENVIRONMENT");
+        .map_err(|e| config::ConfigError::Message(format!("APP_ENVIRONMENT: {}", e)))
+}
 
-    let config = builder
+/// Layers, later ones win: `base.yaml`, `<environment>.yaml`, the optional and git-ignored
+/// `local.override.yaml`, `APP__*` environment variables, then `*_file` secrets.
+pub fn merge_layers(
+    config_directory: &Path,
+    environment: &Environment,
+) -> Result<config::Config, config::ConfigError> {
+    let config = config::ConfigBuilder::<DefaultState>::default()
+        .add_source(config::File::from(config_directory.join("base")).required(true))
         .add_source(config::File::from(config_directory.join(environment.as_str())).required(true))
+        .add_source(
+            config::File::from(config_directory.join("local.override.yaml")).required(false),
+        )
         .add_source(config::Environment::with_prefix("app").separator("__"))
         .build()?;
+    resolve_secret_files(config)
+}
+
+pub fn get_configuration() -> Result<Configuration, config::ConfigError> {
+    let environment = get_environment()?;
+    let config = merge_layers(&configuration_directory(), &environment)?;
+
+    let configuration = config.try_deserialize::<Configuration>()?;
+    configuration
+        .validate(&environment)
+        .map_err(|e| config::ConfigError::Message(e.to_string()))?;
+    Ok(configuration)
+}
+
+/// Every merged setting, secrets redacted. Not validated, so it also helps with a broken setup.
+pub fn effective_configuration() -> Result<serde_json::Value, config::ConfigError> {
+    let config = merge_layers(&configuration_directory(), &get_environment()?)?;
+    let mut effective = config.try_deserialize::<serde_json::Value>()?;
+    redact_secrets(&mut effective);
+    Ok(effective)
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::SecretString;
+    use sqlx::postgres::PgSslMode;
+
+    use super::{merge_layers, Configuration};
+    use crate::configuration::environment::Environment;
+
+    fn configuration() -> Configuration {
+        config::Config::builder()
+            .add_source(config::File::from_str(
+                include_str!("../../configuration/base.yaml"),
+                config::FileFormat::Yaml,
+            ))
+            .set_override("application.base_url", "https://example.com")
+            .unwrap()
+            .set_override("application.logger_name", "newsletter")
+            .unwrap()
+            .set_override("application.default_env_filter", "info")
+            .unwrap()
+            .set_override("aws.region", "eu-central-1")
+            .unwrap()
+            .set_override("aws.verified_email", "sender@example.com")
+            .unwrap()
+            .build()
+            .unwrap()
+            .try_deserialize()
+            .unwrap()
+    }
+
+    #[test]
+    fn a_database_url_replaces_the_connection_settings() {
+        let mut configuration = configuration().database;
+        configuration.url = Some(SecretString::from(
+            "postgres://app:pw@db.internal:6543/newsletter_prod?sslmode=require",
+        ));
+
+        let options = configuration.with_db();
+
+        assert_eq!(options.get_host(), "db.internal");
+        assert_eq!(options.get_port(), 6543);
+        assert_eq!(options.get_username(), "app");
+        assert_eq!(options.get_database(), Some("newsletter_prod"));
+        // `require_ssl: false` doesn't weaken what the URL asks for.
+        assert!(matches!(options.get_ssl_mode(), PgSslMode::Require));
+    }
+
+    #[test]
+    fn tls_and_statement_timeout_reach_the_connection() {
+        let mut configuration = configuration().database;
+        configuration.ssl_verify_full = true;
+        configuration.statement_timeout_milliseconds = 5000;
 
-    config.try_deserialize::<Configuration>()
+        let options = configuration.with_db();
+
+        assert!(matches!(options.get_ssl_mode(), PgSslMode::VerifyFull));
+        assert_eq!(options.get_database(), Some("newsletter"));
+        assert_eq!(options.get_options(), Some("-c statement_timeout=5000"));
+    }
+
+    #[test]
+    fn later_layers_win() {
+        let directory = std::env::temp_dir().join(format!("config-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let write = |name: &str, yaml: &str| std::fs::write(directory.join(name), yaml).unwrap();
+        write(
+            "base.yaml",
+            "layer:\n  base: base\n  profile: base\n  override: base\n",
+        );
+        write(
+            "staging.yaml",
+            "layer:\n  profile: staging\n  override: staging\n",
+        );
+
+        let staging = Environment::Named("staging".into());
+        let config = merge_layers(&directory, &staging).unwrap();
+        assert_eq!(config.get_string("layer.base").unwrap(), "base");
+        assert_eq!(config.get_string("layer.profile").unwrap(), "staging");
+        assert_eq!(config.get_string("layer.override").unwrap(), "staging");
+
+        write("local.override.yaml", "layer:\n  override: mine\n");
+        let config = merge_layers(&directory, &staging).unwrap();
+        assert_eq!(config.get_string("layer.override").unwrap(), "mine");
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn a_profile_without_a_file_is_an_error() {
+        let directory = std::env::temp_dir().join(format!("config-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("base.yaml"), "layer: base\n").unwrap();
+
+        assert!(merge_layers(&directory, &Environment::Named("ci".into())).is_err());
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
 }
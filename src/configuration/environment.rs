@@ -1,13 +1,17 @@
+#[derive(Debug, Clone, PartialEq)]
 pub enum Environment {
     Local,
     Production,
+    /// Any other profile, e.g. `staging` or `ci`, read from `configuration/<name>.yaml`.
+    Named(String),
 }
 
 impl Environment {
-    pub fn as_str(&self) -> &'static str {
+    pub fn as_str(&self) -> &str {
         match self {
             Environment::Local => "local",
             Environment::Production => "production",
+            Environment::Named(name) => name,
         }
     }
 }
@@ -19,10 +23,49 @@ impl TryFrom<String> for Environment {
         match value.to_lowercase().as_str() {
             "local" => Ok(Self::Local),
             "production" => Ok(Self::Production),
+            "base" | "local.override" => Err(format!(
+                "`{}` is loaded for every environment and can't be one itself.",
+                value
+            )),
+            // The name ends up in a file path.
+            other
+                if !other.is_empty()
+                    && other
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') =>
+            {
+                Ok(Self::Named(other.to_string()))
+            }
             other => Err(format!(
-                "{} is not a supported environment. Use either `local` or `production`.",
+                "`{}` is not a valid environment name. Use letters, digits, `-` and `_`.",
                 other
             )),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Environment;
+
+    #[test]
+    fn any_plain_name_is_a_profile() {
+        for name in ["staging", "ci", "Test", "eu-west_2"] {
+            assert_eq!(
+                Environment::try_from(name.to_string()),
+                Ok(Environment::Named(name.to_lowercase()))
+            );
+        }
+        assert_eq!(
+            Environment::try_from("PRODUCTION".to_string()),
+            Ok(Environment::Production)
+        );
+    }
+
+    #[test]
+    fn names_that_are_not_profiles_are_rejected() {
+        for name in ["", "base", "local.override", "../secrets", "a/b"] {
+            assert!(Environment::try_from(name.to_string()).is_err(), "{}", name);
+        }
+    }
+}
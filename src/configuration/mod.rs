@@ -1,3 +1,5 @@
 pub mod aws_credentials;
 pub mod config;
 pub mod environment;
+pub mod secrets;
+pub mod validation;
@@ -0,0 +1,176 @@
+use config::{Config, ConfigError};
+
+/// Configuration paths holding secrets. Each one can instead be given as `<path>_file`, a file
+/// holding the value, the way Docker and Kubernetes mount secrets.
+pub const SECRET_PATHS: &[&str] = &[
+    "database.url",
+    "database.password",
+    "aws.access_key_id",
+    "aws.secret_access_key",
+    "bot_protection.form_secret",
+];
+
+/// Configuration paths holding a map whose every value is a secret. `<path>_file` is then a
+/// map of the same keys to files, e.g. `signed_links.keys_file.2024-12`.
+pub const SECRET_MAPS: &[&str] = &["signed_links.keys"];
+
+/// Replaces every secret that has a `_file` variant with the file's content.
+/// A `_file` variant wins over a value set directly.
+pub fn resolve_secret_files(config: Config) -> Result<Config, ConfigError> {
+    let mut files = vec![];
+    for path in SECRET_PATHS {
+        let file_path = format!("{}_file", path);
+        match config.get_string(&file_path) {
+            Ok(file) => files.push((path.to_string(), file_path, file)),
+            Err(ConfigError::NotFound(_)) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    for path in SECRET_MAPS {
+        let map_path = format!("{}_file", path);
+        match config.get_table(&map_path) {
+            Ok(map) => {
+                for (key, file) in map {
+                    files.push((
+                        format!("{}.{}", path, key),
+                        format!("{}.{}", map_path, key),
+                        file.into_string()?,
+                    ));
+                }
+            }
+            Err(ConfigError::NotFound(_)) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    if files.is_empty() {
+        return Ok(config);
+    }
+
+    let mut builder = Config::builder().add_source(config);
+    for (path, file_path, file) in files {
+        let secret = std::fs::read_to_string(&file).map_err(|e| {
+            ConfigError::Message(format!(
+                "{}: failed to read secret from `{}`: {}",
+                file_path, file, e
+            ))
+        })?;
+        // Secret files usually end with a newline that isn't part of the value.
+        builder = builder.set_override(&path, secret.trim_end_matches(['\n', '\r']))?;
+    }
+    builder.build()
+}
+
+/// Blanks out every secret that is set, for printing the configuration.
+pub fn redact_secrets(configuration: &mut serde_json::Value) {
+    for path in SECRET_PATHS {
+        let pointer = format!("/{}", path.replace('.', "/"));
+        if let Some(value) = configuration.pointer_mut(&pointer) {
+            *value = "[REDACTED]".into();
+        }
+    }
+    for path in SECRET_MAPS {
+        let pointer = format!("/{}", path.replace('.', "/"));
+        if let Some(serde_json::Value::Object(map)) = configuration.pointer_mut(&pointer) {
+            for value in map.values_mut() {
+                *value = "[REDACTED]".into();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use config::Config;
+
+    use super::{redact_secrets, resolve_secret_files};
+
+    #[test]
+    fn secrets_are_read_from_their_file_variant() {
+        let dir = std::env::temp_dir().join(format!("secrets-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("db_password");
+        std::fs::write(&file, "from-file\n").unwrap();
+
+        let config = Config::builder()
+            .set_override("database.password", "from-yaml")
+            .unwrap()
+            .set_override("database.password_file", file.to_str().unwrap())
+            .unwrap()
+            .set_override("bot_protection.form_secret", "untouched")
+            .unwrap()
+            .build()
+            .unwrap();
+        let config = resolve_secret_files(config).unwrap();
+
+        assert_eq!(config.get_string("database.password").unwrap(), "from-file");
+        assert_eq!(
+            config.get_string("bot_protection.form_secret").unwrap(),
+            "untouched"
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn link_keys_are_read_from_their_file_variant() {
+        let dir = std::env::temp_dir().join(format!("secrets-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("link_key");
+        std::fs::write(&file, "from-file\n").unwrap();
+
+        let config = Config::builder()
+            .set_override("signed_links.keys.2024-06", "from-yaml")
+            .unwrap()
+            .set_override("signed_links.keys_file.2024-12", file.to_str().unwrap())
+            .unwrap()
+            .build()
+            .unwrap();
+        let config = resolve_secret_files(config).unwrap();
+
+        assert_eq!(
+            config.get_string("signed_links.keys.2024-12").unwrap(),
+            "from-file"
+        );
+        assert_eq!(
+            config.get_string("signed_links.keys.2024-06").unwrap(),
+            "from-yaml"
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn a_missing_secret_file_names_the_setting() {
+        let config = Config::builder()
+            .set_override("aws.secret_access_key_file", "/nonexistent/secret")
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let error = resolve_secret_files(config).unwrap_err().to_string();
+
+        assert!(
+            error.starts_with("aws.secret_access_key_file: failed to read secret"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn set_secrets_are_redacted() {
+        let mut configuration = serde_json::json!({
+            "database": { "password": "hunter2", "password_file": "/run/secrets/db" },
+            "aws": { "region": "eu-central-1" },
+            "signed_links": { "keys": { "2024-12": "s3cret" }, "signing_key": "2024-12" },
+        });
+
+        redact_secrets(&mut configuration);
+
+        assert_eq!(
+            configuration,
+            serde_json::json!({
+                "database": { "password": "[REDACTED]", "password_file": "/run/secrets/db" },
+                "aws": { "region": "eu-central-1" },
+                "signed_links": { "keys": { "2024-12": "[REDACTED]" }, "signing_key": "2024-12" },
+            })
+        );
+    }
+}
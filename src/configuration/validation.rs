@@ -0,0 +1,495 @@
+use std::path::Path;
+
+use axum::http::Uri;
+use secrecy::ExposeSecret;
+use sqlx::postgres::PgConnectOptions;
+
+use super::{
+    config::{AwsCredentialsMode, Configuration},
+    environment::Environment,
+};
+use crate::domain::SubscriberEmail;
+
+/// A single problem, tied to the config path it was found at.
+#[derive(Debug, PartialEq)]
+pub struct ConfigIssue {
+    pub path: &'static str,
+    pub message: String,
+}
+
+/// Every problem found in a configuration, reported in one go.
+#[derive(Debug)]
+pub struct ConfigErrors(pub Vec<ConfigIssue>);
+
+impl std::fmt::Display for ConfigErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid configuration:")?;
+        for issue in &self.0 {
+            write!(f, "\n  - {}: {}", issue.path, issue.message)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigErrors {}
+
+impl Configuration {
+    /// Catches what deserialisation can't, before anything gets built from it.
+    pub fn validate(&self, environment: &Environment) -> Result<(), ConfigErrors> {
+        let mut issues = vec![];
+        let mut report = |path, message: String| issues.push(ConfigIssue { path, message });
+
+        if let Some(url) = &self.application.confirmed_redirect_url {
+            if let Err(e) = check_http_url(url) {
+                report("application.confirmed_redirect_url", e);
+            }
+        }
+        if let Err(e) = check_http_url(&self.application.base_url) {
+            report("application.base_url", e);
+        } else if self.application.base_url.ends_with('/') {
+            report(
+                "application.base_url",
+                "must not end with `/`, paths get appended to it".into(),
+            );
+        }
+        if self.application.port == 0 {
+            report("application.port", "must be between 1 and 65535".into());
+        }
+        if self.database.port == 0 {
+            report("database.port", "must be between 1 and 65535".into());
+        }
+        if let Err(e) = SubscriberEmail::parse(self.aws.verified_email.clone()) {
+            report("aws.verified_email", e);
+        }
+        if let Some(endpoint_url) = &self.aws.endpoint_url {
+            if let Err(e) = check_http_url(endpoint_url) {
+                report("aws.endpoint_url", e);
+            }
+        }
+        match self.aws.credentials_mode {
+            AwsCredentialsMode::Static => {
+                if self.aws.access_key_id.is_none() {
+                    report("aws.access_key_id", "is required in `static` mode".into());
+                }
+                if self.aws.secret_access_key.is_none() {
+                    report(
+                        "aws.secret_access_key",
+                        "is required in `static` mode".into(),
+                    );
+                }
+            }
+            AwsCredentialsMode::AssumeRole if self.aws.role_arn.is_none() => {
+                report("aws.role_arn", "is required in `assume_role` mode".into());
+            }
+            _ => {}
+        }
+        if matches!(environment, Environment::Production) {
+            // Anyone who knows the form secret can mint tokens that pass the timing check.
+            let form_secret = self.bot_protection.form_secret.expose_secret();
+            if form_secret == DEFAULT_FORM_SECRET {
+                report(
+                    "bot_protection.form_secret",
+                    "must be changed from the shipped default in production".into(),
+                );
+            } else if form_secret.len() < MIN_FORM_SECRET_LENGTH {
+                report(
+                    "bot_protection.form_secret",
+                    format!(
+                        "must be at least {} characters long in production",
+                        MIN_FORM_SECRET_LENGTH
+                    ),
+                );
+            }
+        }
+        if self.telemetry.otlp_enabled {
+            if let Err(e) = check_http_url(&self.telemetry.otlp_endpoint) {
+                report("telemetry.otlp_endpoint", e);
+            }
+        }
+        if let Some(url) = &self.database.url {
+            if let Err(e) = url.expose_secret().parse::<PgConnectOptions>() {
+                report(
+                    "database.url",
+                    format!("is not a valid Postgres URL: {}", e),
+                );
+            }
+        }
+        if self.database.max_connections == 0 {
+            report("database.max_connections", "must be at least 1".into());
+        } else if self.database.min_connections > self.database.max_connections {
+            report(
+                "database.min_connections",
+                format!(
+                    "must not exceed `max_connections` ({})",
+                    self.database.max_connections
+                ),
+            );
+        }
+        if let Some(ssl_root_cert) = &self.database.ssl_root_cert {
+            if !Path::new(ssl_root_cert).is_file() {
+                report(
+                    "database.ssl_root_cert",
+                    format!("`{}` is not a file", ssl_root_cert),
+                );
+            }
+        }
+        let links = &self.signed_links;
+        let mut key_ids: Vec<_> = links.keys.keys().collect();
+        key_ids.sort();
+        for id in key_ids {
+            if id.is_empty()
+                || !id
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            {
+                report(
+                    "signed_links.keys",
+                    format!(
+                        "`{}` is not a valid key id, use letters, digits, `-` and `_`",
+                        id
+                    ),
+                );
+            } else if links.keys[id].expose_secret().len() < MIN_LINK_KEY_LENGTH {
+                report(
+                    "signed_links.keys",
+                    format!(
+                        "`{}` must be at least {} characters long",
+                        id, MIN_LINK_KEY_LENGTH
+                    ),
+                );
+            }
+        }
+        match &links.signing_key {
+            Some(id) if !links.keys.contains_key(id) => report(
+                "signed_links.signing_key",
+                format!("`{}` is not one of `signed_links.keys`", id),
+            ),
+            None if links.confirmation_links => report(
+                "signed_links.signing_key",
+                "is required when `confirmation_links` is on".into(),
+            ),
+            // Without it issues go out with no way to unsubscribe.
+            None if matches!(environment, Environment::Production) => report(
+                "signed_links.signing_key",
+                "is required in production".into(),
+            ),
+            _ => {}
+        }
+        if links.confirm_ttl_seconds == 0 {
+            report(
+                "signed_links.confirm_ttl_seconds",
+                "must be at least 1".into(),
+            );
+        }
+        if links.unsubscribe_ttl_seconds == 0 {
+            report(
+                "signed_links.unsubscribe_ttl_seconds",
+                "must be at least 1".into(),
+            );
+        }
+        let cleanup = &self.pending_cleanup;
+        if cleanup.interval_seconds == 0 {
+            report(
+                "pending_cleanup.interval_seconds",
+                "must be at least 1".into(),
+            );
+        }
+        if cleanup.purge_after_seconds <= cleanup.remind_after_seconds {
+            report(
+                "pending_cleanup.purge_after_seconds",
+                "must be longer than `remind_after_seconds`, or nobody would be reminded".into(),
+            );
+        }
+        if matches!(environment, Environment::Production)
+            && !(self.database.require_ssl || self.database.ssl_verify_full)
+        {
+            report(
+                "database.require_ssl",
+                "must be `true` in production".into(),
+            );
+        }
+
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigErrors(issues))
+        }
+    }
+}
+
+// As long as the SHA-256 block, shorter keys are easier to guess.
+const MIN_LINK_KEY_LENGTH: usize = 32;
+
+// What `base.yaml` ships with.
+const DEFAULT_FORM_SECRET: &str = "change-me-in-production";
+const MIN_FORM_SECRET_LENGTH: usize = 32;
+
+fn check_http_url(value: &str) -> Result<(), String> {
+    let uri: Uri = value
+        .parse()
+        .map_err(|_| format!("`{}` is not a valid URL", value))?;
+    match (uri.scheme_str(), uri.host()) {
+        (Some("http" | "https"), Some(host)) if !host.is_empty() => Ok(()),
+        _ => Err(format!(
+            "`{}` must be an absolute http(s) URL, e.g. `https://example.com`",
+            value
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use config::{File, FileFormat};
+
+    use super::ConfigIssue;
+    use crate::configuration::{config::Configuration, environment::Environment};
+
+    fn configuration(overrides: &[(&str, &str)]) -> Configuration {
+        let mut builder = config::Config::builder()
+            .add_source(File::from_str(
+                include_str!("../../configuration/base.yaml"),
+                FileFormat::Yaml,
+            ))
+            .set_override("application.base_url", "https://example.com")
+            .unwrap()
+            .set_override("application.logger_name", "newsletter")
+            .unwrap()
+            .set_override("application.default_env_filter", "info")
+            .unwrap()
+            .set_override("aws.region", "eu-central-1")
+            .unwrap()
+            .set_override("aws.verified_email", "sender@example.com")
+            .unwrap()
+            .set_override("aws.access_key_id", "x")
+            .unwrap()
+            .set_override("aws.secret_access_key", "y")
+            .unwrap();
+        for (key, value) in overrides {
+            builder = builder.set_override(*key, *value).unwrap();
+        }
+        builder.build().unwrap().try_deserialize().unwrap()
+    }
+
+    fn paths(issues: Vec<ConfigIssue>) -> Vec<&'static str> {
+        issues.into_iter().map(|issue| issue.path).collect()
+    }
+
+    #[test]
+    fn the_base_configuration_is_valid_locally() {
+        assert!(configuration(&[]).validate(&Environment::Local).is_ok());
+    }
+
+    #[test]
+    fn every_problem_is_reported_at_once() {
+        let errors = configuration(&[
+            ("application.base_url", "example.com"),
+            ("application.port", "0"),
+            ("aws.verified_email", "not-an-email"),
+        ])
+        .validate(&Environment::Local)
+        .unwrap_err();
+
+        assert_eq!(
+            paths(errors.0),
+            vec![
+                "application.base_url",
+                "application.port",
+                "aws.verified_email"
+            ]
+        );
+    }
+
+    #[test]
+    fn base_url_must_be_http_and_without_a_trailing_slash() {
+        for base_url in ["ftp://example.com", "/relative", "https://example.com/"] {
+            let errors = configuration(&[("application.base_url", base_url)])
+                .validate(&Environment::Local)
+                .unwrap_err();
+            assert_eq!(
+                paths(errors.0),
+                vec!["application.base_url"],
+                "{}",
+                base_url
+            );
+        }
+    }
+
+    const LINK_KEY: &str = "a-production-key-that-is-long-enough";
+    const FORM_SECRET: &str = "a-production-form-secret-long-enough";
+
+    fn production(overrides: &[(&str, &str)]) -> Configuration {
+        let mut all = vec![
+            ("bot_protection.form_secret", FORM_SECRET),
+            ("signed_links.keys.2024-12", LINK_KEY),
+            ("signed_links.signing_key", "2024-12"),
+        ];
+        all.extend_from_slice(overrides);
+        configuration(&all)
+    }
+
+    #[test]
+    fn production_requires_ssl_to_the_database() {
+        let errors = production(&[])
+            .validate(&Environment::Production)
+            .unwrap_err();
+        assert_eq!(paths(errors.0), vec!["database.require_ssl"]);
+
+        assert!(production(&[("database.require_ssl", "true")])
+            .validate(&Environment::Production)
+            .is_ok());
+    }
+
+    #[test]
+    fn verify_full_also_satisfies_production() {
+        assert!(production(&[("database.ssl_verify_full", "true")])
+            .validate(&Environment::Production)
+            .is_ok());
+    }
+
+    #[test]
+    fn production_requires_a_link_signing_key() {
+        let errors = configuration(&[
+            ("database.require_ssl", "true"),
+            ("bot_protection.form_secret", FORM_SECRET),
+        ])
+        .validate(&Environment::Production)
+        .unwrap_err();
+        assert_eq!(paths(errors.0), vec!["signed_links.signing_key"]);
+
+        assert!(configuration(&[]).validate(&Environment::Local).is_ok());
+    }
+
+    #[test]
+    fn production_rejects_the_default_or_a_short_form_secret() {
+        for form_secret in ["change-me-in-production", "short"] {
+            let errors = production(&[
+                ("database.require_ssl", "true"),
+                ("bot_protection.form_secret", form_secret),
+            ])
+            .validate(&Environment::Production)
+            .unwrap_err();
+            assert_eq!(
+                paths(errors.0),
+                vec!["bot_protection.form_secret"],
+                "{}",
+                form_secret
+            );
+        }
+
+        assert!(configuration(&[]).validate(&Environment::Local).is_ok());
+    }
+
+    #[test]
+    fn database_pool_and_tls_settings_are_checked() {
+        let errors = configuration(&[
+            ("database.url", "not a url"),
+            ("database.min_connections", "11"),
+            ("database.ssl_root_cert", "/nonexistent/ca.pem"),
+        ])
+        .validate(&Environment::Local)
+        .unwrap_err();
+
+        assert_eq!(
+            paths(errors.0),
+            vec![
+                "database.url",
+                "database.min_connections",
+                "database.ssl_root_cert"
+            ]
+        );
+    }
+
+    #[test]
+    fn the_otlp_endpoint_is_only_checked_when_export_is_on() {
+        let disabled = configuration(&[("telemetry.otlp_endpoint", "nope")]);
+        assert!(disabled.validate(&Environment::Local).is_ok());
+
+        let enabled = configuration(&[
+            ("telemetry.otlp_enabled", "true"),
+            ("telemetry.otlp_endpoint", "nope"),
+        ]);
+        let errors = enabled.validate(&Environment::Local).unwrap_err();
+        assert_eq!(paths(errors.0), vec!["telemetry.otlp_endpoint"]);
+    }
+
+    #[test]
+    fn aws_credentials_must_match_their_mode() {
+        let mut static_keys = configuration(&[]);
+        static_keys.aws.secret_access_key = None;
+        let errors = static_keys.validate(&Environment::Local).unwrap_err();
+        assert_eq!(paths(errors.0), vec!["aws.secret_access_key"]);
+
+        let default_chain = configuration(&[("aws.credentials_mode", "default_chain")]);
+        assert!(default_chain.validate(&Environment::Local).is_ok());
+
+        let assume_role = configuration(&[("aws.credentials_mode", "assume_role")]);
+        let errors = assume_role.validate(&Environment::Local).unwrap_err();
+        assert_eq!(paths(errors.0), vec!["aws.role_arn"]);
+    }
+
+    #[test]
+    fn the_aws_endpoint_override_must_be_a_url() {
+        let errors = configuration(&[("aws.endpoint_url", "localhost:8005")])
+            .validate(&Environment::Local)
+            .unwrap_err();
+        assert_eq!(paths(errors.0), vec!["aws.endpoint_url"]);
+
+        assert!(
+            configuration(&[("aws.endpoint_url", "http://localhost:8005")])
+                .validate(&Environment::Local)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn issues_are_listed_with_their_paths() {
+        let errors = configuration(&[("aws.verified_email", "nope")])
+            .validate(&Environment::Local)
+            .unwrap_err();
+        assert_eq!(
+            errors.to_string(),
+            "Invalid configuration:\n  - aws.verified_email: nope is not a valid subscriber email"
+        );
+    }
+
+    #[test]
+    fn pending_subscribers_are_reminded_before_being_purged() {
+        let errors = configuration(&[
+            ("pending_cleanup.remind_after_seconds", "3600"),
+            ("pending_cleanup.purge_after_seconds", "3600"),
+        ])
+        .validate(&Environment::Local)
+        .unwrap_err();
+        assert_eq!(paths(errors.0), vec!["pending_cleanup.purge_after_seconds"]);
+    }
+
+    #[test]
+    fn signed_link_keys_are_checked() {
+        let secret = "k".repeat(32);
+        let valid = configuration(&[
+            ("signed_links.confirmation_links", "true"),
+            ("signed_links.keys.2024-12", &secret),
+            ("signed_links.signing_key", "2024-12"),
+        ]);
+        assert!(valid.validate(&Environment::Local).is_ok());
+
+        let errors = configuration(&[
+            ("signed_links.confirmation_links", "true"),
+            ("signed_links.keys.2024-12", "short"),
+        ])
+        .validate(&Environment::Local)
+        .unwrap_err();
+        assert_eq!(
+            paths(errors.0),
+            vec!["signed_links.keys", "signed_links.signing_key"]
+        );
+
+        let errors = configuration(&[
+            ("signed_links.keys.2024-12", &secret),
+            ("signed_links.signing_key", "2025-01"),
+        ])
+        .validate(&Environment::Local)
+        .unwrap_err();
+        assert_eq!(paths(errors.0), vec!["signed_links.signing_key"]);
+    }
+}
@@ -1,16 +1,17 @@
-use sqlx::{
-    postgres::{PgConnectOptions, PgPoolOptions},
-    PgPool,
-};
+use sqlx::PgPool;
+
+use crate::configuration::config::DatabaseConfiguration;
 
 pub struct Database {
     pub pool: PgPool,
 }
 
 impl Database {
-    pub async fn new(options: PgConnectOptions) -> Result<Self, anyhow::Error> {
-        let pool = PgPoolOptions::new().connect_lazy_with(options);
-        sqlx::migrate!("./migrations").run(&pool).await?;
-        Ok(Self { pool })
+    /// Connects lazily, migrations are up to the caller, see `database::migrations`.
+    pub fn new(configuration: &DatabaseConfiguration) -> Self {
+        let pool = configuration
+            .pool_options()
+            .connect_lazy_with(configuration.with_db());
+        Self { pool }
     }
 }
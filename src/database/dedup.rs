@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+
+use sqlx::{types::chrono::Utc, PgPool};
+use uuid::Uuid;
+
+use crate::domain::{SubscriberEmail, SubscriptionStatus};
+
+/// One group of addresses that turned out to reach the same mailbox.
+#[derive(Debug)]
+pub struct MergedSubscribers {
+    pub canonical_email: String,
+    pub kept: (Uuid, String),
+    pub removed: Vec<(Uuid, String)>,
+}
+
+#[derive(Debug, Default)]
+pub struct DedupReport {
+    pub merged: Vec<MergedSubscribers>,
+    /// Rows whose stored email no longer parses, left untouched.
+    pub unparseable: Vec<(Uuid, String)>,
+}
+
+impl std::fmt::Display for DedupReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Merged {} group(s) of duplicate subscribers.",
+            self.merged.len()
+        )?;
+        for group in &self.merged {
+            writeln!(
+                f,
+                "{}: kept {} ({})",
+                group.canonical_email, group.kept.1, group.kept.0
+            )?;
+            for (id, email) in &group.removed {
+                writeln!(f, "\tremoved {} ({})", email, id)?;
+            }
+        }
+        for (id, email) in &self.unparseable {
+            writeln!(f, "Skipped {} ({}): not a valid email", email, id)?;
+        }
+        Ok(())
+    }
+}
+
+struct Row {
+    id: Uuid,
+    email: String,
+    email_canonical: Option<String>,
+    status: String,
+    subscribed_at: sqlx::types::chrono::DateTime<Utc>,
+}
+
+/// Backfills `email_canonical` and merges subscribers that share it.
+///
+/// The row kept out of each group is the confirmed one if any, the oldest
+/// otherwise. The others are deleted along with their tokens. Running it
+/// again on a clean table is a no-op.
+#[tracing::instrument(name = "Merge duplicate subscribers", skip(pool))]
+pub async fn merge_duplicate_subscribers(pool: &PgPool) -> Result<DedupReport, anyhow::Error> {
+    let rows = sqlx::query_as!(
+        Row,
+        r#"SELECT id, email, email_canonical, status, subscribed_at FROM subscriptions"#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut report = DedupReport::default();
+    let mut groups: HashMap<String, Vec<Row>> = HashMap::new();
+    for row in rows {
+        match SubscriberEmail::parse(row.email.clone()) {
+            Ok(email) => groups
+                .entry(email.canonical().to_string())
+                .or_default()
+                .push(row),
+            Err(_) => report.unparseable.push((row.id, row.email)),
+        }
+    }
+
+    let mut transaction = pool.begin().await?;
+    for (canonical_email, mut rows) in groups {
+        rows.sort_by_key(|r| {
+            (
+                r.status != SubscriptionStatus::Confirmed.as_str(),
+                r.subscribed_at,
+                r.id,
+            )
+        });
+        let mut rows = rows.into_iter();
+        let kept = rows.next().expect("Groups are never empty");
+        let removed: Vec<Row> = rows.collect();
+
+        // Free the canonical value before handing it to the row we keep.
+        for row in &removed {
+            sqlx::query!(
+                r#"DELETE FROM subscription_tokens WHERE subscriber_id = $1"#,
+                row.id
+            )
+            .execute(&mut *transaction)
+            .await?;
+            sqlx::query!(r#"DELETE FROM subscriptions WHERE id = $1"#, row.id)
+                .execute(&mut *transaction)
+                .await?;
+        }
+        if kept.email_canonical.as_deref() != Some(canonical_email.as_str()) {
+            sqlx::query!(
+                r#"UPDATE subscriptions SET email_canonical = $1 WHERE id = $2"#,
+                canonical_email,
+                kept.id
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+
+        if !removed.is_empty() {
+            report.merged.push(MergedSubscribers {
+                canonical_email,
+                kept: (kept.id, kept.email),
+                removed: removed.into_iter().map(|r| (r.id, r.email)).collect(),
+            });
+        }
+    }
+    transaction.commit().await?;
+
+    Ok(report)
+}
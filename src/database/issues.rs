@@ -0,0 +1,41 @@
+use sqlx::{
+    types::{chrono::Utc, Json},
+    PgPool,
+};
+use uuid::Uuid;
+
+use crate::routes::newsletter::NewsletterPayload;
+
+/// Keeps a published issue around for its view-in-browser links.
+pub async fn store_issue(
+    pool: &PgPool,
+    payload: &NewsletterPayload,
+) -> Result<Uuid, anyhow::Error> {
+    let issue_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+          INSERT INTO newsletter_issues (id, title, content, published_at)
+          VALUES ($1, $2, $3, $4)
+        "#,
+        issue_id,
+        payload.title,
+        Json(payload) as _,
+        Utc::now()
+    )
+    .execute(pool)
+    .await?;
+    Ok(issue_id)
+}
+
+pub async fn issue_by_id(
+    pool: &PgPool,
+    issue_id: Uuid,
+) -> Result<Option<NewsletterPayload>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"SELECT content AS "content: Json<NewsletterPayload>" FROM newsletter_issues WHERE id = $1"#,
+        issue_id
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.map(|row| row.content.0))
+}
@@ -0,0 +1,113 @@
+use std::collections::HashSet;
+
+use sqlx::{
+    migrate::{Migrate, Migrator},
+    PgPool,
+};
+
+/// The migrations this build was compiled with.
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// Applies every pending migration. Runners on other replicas wait on a Postgres
+/// advisory lock, the first one applies the migrations and the rest find nothing to do.
+pub async fn migrate_up(pool: &PgPool) -> Result<(), anyhow::Error> {
+    MIGRATOR.run(pool).await?;
+    Ok(())
+}
+
+/// Reverts every applied migration newer than `target`, under the same lock as `migrate_up`.
+/// `0` reverts everything.
+pub async fn migrate_down(pool: &PgPool, target: i64) -> Result<(), anyhow::Error> {
+    if target != 0 && !MIGRATOR.version_exists(target) {
+        anyhow::bail!("{} is not a known migration version", target);
+    }
+    MIGRATOR.undo(pool, target).await?;
+    Ok(())
+}
+
+pub struct MigrationState {
+    pub version: i64,
+    pub description: String,
+    pub applied: bool,
+}
+
+/// Where the database schema stands compared to this build.
+pub struct SchemaStatus {
+    pub migrations: Vec<MigrationState>,
+    /// A migration that failed half-way and has to be fixed by hand.
+    pub dirty_version: Option<i64>,
+}
+
+impl SchemaStatus {
+    pub fn pending(&self) -> usize {
+        self.migrations.iter().filter(|m| !m.applied).count()
+    }
+
+    /// The newest applied migration this build knows about.
+    pub fn current_version(&self) -> Option<i64> {
+        self.migrations
+            .iter()
+            .filter(|m| m.applied)
+            .map(|m| m.version)
+            .max()
+    }
+
+    pub fn expected_version(&self) -> Option<i64> {
+        self.migrations.iter().map(|m| m.version).max()
+    }
+}
+
+impl std::fmt::Display for SchemaStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for migration in &self.migrations {
+            let state = if Some(migration.version) == self.dirty_version {
+                "failed"
+            } else if migration.applied {
+                "applied"
+            } else {
+                "pending"
+            };
+            writeln!(
+                f,
+                "{} {:<7} {}",
+                migration.version, state, migration.description
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Read-only, so it works for a role without DDL rights and doesn't wait for the migration lock.
+pub async fn schema_status(pool: &PgPool) -> Result<SchemaStatus, anyhow::Error> {
+    let mut connection = pool.acquire().await?;
+    // Nothing has ever been migrated on a blank database.
+    let tracked: bool = sqlx::query_scalar("SELECT to_regclass('_sqlx_migrations') IS NOT NULL")
+        .fetch_one(&mut *connection)
+        .await?;
+    let (dirty_version, applied) = if tracked {
+        let applied: HashSet<i64> = connection
+            .list_applied_migrations()
+            .await?
+            .into_iter()
+            .map(|migration| migration.version)
+            .collect();
+        (connection.dirty_version().await?, applied)
+    } else {
+        (None, HashSet::new())
+    };
+
+    let migrations = MIGRATOR
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .map(|migration| MigrationState {
+            version: migration.version,
+            description: migration.description.to_string(),
+            applied: applied.contains(&migration.version),
+        })
+        .collect();
+
+    Ok(SchemaStatus {
+        migrations,
+        dirty_version,
+    })
+}
@@ -1 +1,5 @@
 pub mod db;
+pub mod dedup;
+pub mod issues;
+pub mod migrations;
+pub mod subscribers;
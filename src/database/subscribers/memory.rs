@@ -0,0 +1,560 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use async_trait::async_trait;
+use sqlx::types::chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use super::{
+    AlreadySubscribed, ConfirmedSubscriber, ConfirmedSubscriberPage, StatusChangeError, Subscriber,
+    SubscriberRepository, SubscriberRow,
+};
+use crate::domain::{
+    subscription_token_digest, IllegalTransition, NewSubscriber, SubscriberEmail,
+    SubscriptionStatus,
+};
+
+struct StoredSubscriber {
+    email: SubscriberEmail,
+    name: String,
+    locale: String,
+    status: SubscriptionStatus,
+    subscribed_at: DateTime<Utc>,
+    reminded: bool,
+}
+
+#[derive(Default)]
+struct Tables {
+    subscribers: HashMap<Uuid, StoredSubscriber>,
+    // Keyed by digest, like the table.
+    tokens: HashMap<String, Uuid>,
+}
+
+/// Keeps subscribers in process memory, for tests that don't need Postgres.
+///
+/// Enforces the same uniqueness as the schema: one subscriber per canonical email
+/// and one subscriber per token.
+#[derive(Default)]
+pub struct InMemorySubscriberRepository {
+    tables: Mutex<Tables>,
+}
+
+impl InMemorySubscriberRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn insert(
+        &self,
+        new_subscriber: &NewSubscriber,
+        status: SubscriptionStatus,
+        subscription_token: Option<&str>,
+    ) -> Result<Uuid, anyhow::Error> {
+        let mut tables = self.tables.lock().expect("Subscriber tables lock poisoned");
+
+        let canonical = new_subscriber.email.canonical();
+        if tables
+            .subscribers
+            .values()
+            .any(|subscriber| subscriber.email.canonical() == canonical)
+        {
+            return Err(AlreadySubscribed.into());
+        }
+        let token_hash = subscription_token.map(subscription_token_digest);
+        if let Some(token_hash) = &token_hash {
+            if tables.tokens.contains_key(token_hash) {
+                anyhow::bail!("The subscription token is taken already");
+            }
+        }
+
+        let subscriber_id = Uuid::new_v4();
+        tables.subscribers.insert(
+            subscriber_id,
+            StoredSubscriber {
+                email: new_subscriber.email.clone(),
+                name: new_subscriber.name.as_ref().to_string(),
+                locale: new_subscriber.locale.clone(),
+                status,
+                subscribed_at: Utc::now(),
+                reminded: false,
+            },
+        );
+        if let Some(token_hash) = token_hash {
+            tables.tokens.insert(token_hash, subscriber_id);
+        }
+        Ok(subscriber_id)
+    }
+
+    /// Deletes the subscribers `remove` picks, with their tokens. Returns how many.
+    fn delete_where(&self, remove: impl Fn(&Uuid, &StoredSubscriber) -> bool) -> u64 {
+        let mut tables = self.tables.lock().expect("Subscriber tables lock poisoned");
+        let before = tables.subscribers.len();
+        let Tables {
+            subscribers,
+            tokens,
+        } = &mut *tables;
+        subscribers.retain(|id, subscriber| !remove(id, subscriber));
+        tokens.retain(|_, id| subscribers.contains_key(id));
+        (before - subscribers.len()) as u64
+    }
+}
+
+#[async_trait]
+impl SubscriberRepository for InMemorySubscriberRepository {
+    async fn insert_pending(
+        &self,
+        new_subscriber: &NewSubscriber,
+        subscription_token: Option<&str>,
+    ) -> Result<Uuid, anyhow::Error> {
+        self.insert(
+            new_subscriber,
+            SubscriptionStatus::PendingConfirmation,
+            subscription_token,
+        )
+    }
+
+    async fn insert_confirmed(
+        &self,
+        new_subscriber: &NewSubscriber,
+    ) -> Result<Uuid, anyhow::Error> {
+        self.insert(new_subscriber, SubscriptionStatus::Confirmed, None)
+    }
+
+    async fn subscriber_id_from_token(
+        &self,
+        subscription_token: &str,
+    ) -> Result<Option<Uuid>, anyhow::Error> {
+        let tables = self.tables.lock().expect("Subscriber tables lock poisoned");
+        Ok(tables
+            .tokens
+            .get(&subscription_token_digest(subscription_token))
+            .copied())
+    }
+
+    async fn subscriber_id_from_email(
+        &self,
+        email: &SubscriberEmail,
+    ) -> Result<Option<Uuid>, anyhow::Error> {
+        let tables = self.tables.lock().expect("Subscriber tables lock poisoned");
+        Ok(tables
+            .subscribers
+            .iter()
+            .find(|(_, subscriber)| subscriber.email.canonical() == email.canonical())
+            .map(|(id, _)| *id))
+    }
+
+    async fn subscriber_by_email(
+        &self,
+        email: &SubscriberEmail,
+    ) -> Result<Option<Subscriber>, anyhow::Error> {
+        let tables = self.tables.lock().expect("Subscriber tables lock poisoned");
+        Ok(tables
+            .subscribers
+            .iter()
+            .find(|(_, subscriber)| subscriber.email.canonical() == email.canonical())
+            .map(|(id, subscriber)| Subscriber {
+                id: *id,
+                email: subscriber.email.clone(),
+                locale: subscriber.locale.clone(),
+                status: subscriber.status,
+            }))
+    }
+
+    async fn replace_token(
+        &self,
+        subscriber_id: Uuid,
+        subscription_token: &str,
+    ) -> Result<(), anyhow::Error> {
+        let mut tables = self.tables.lock().expect("Subscriber tables lock poisoned");
+        if !tables.subscribers.contains_key(&subscriber_id) {
+            anyhow::bail!("There is no subscriber with this id");
+        }
+        let token_hash = subscription_token_digest(subscription_token);
+        if tables.tokens.contains_key(&token_hash) {
+            anyhow::bail!("The subscription token is taken already");
+        }
+        tables.tokens.retain(|_, id| *id != subscriber_id);
+        tables.tokens.insert(token_hash, subscriber_id);
+        Ok(())
+    }
+
+    async fn resubscribe(
+        &self,
+        subscriber_id: Uuid,
+        new_subscriber: &NewSubscriber,
+        subscription_token: Option<&str>,
+    ) -> Result<(), StatusChangeError> {
+        let mut tables = self.tables.lock().expect("Subscriber tables lock poisoned");
+        let token_hash = subscription_token.map(subscription_token_digest);
+        if let Some(token_hash) = &token_hash {
+            if tables.tokens.contains_key(token_hash) {
+                return Err(anyhow::anyhow!("The subscription token is taken already").into());
+            }
+        }
+        let subscriber = tables
+            .subscribers
+            .get_mut(&subscriber_id)
+            .ok_or(StatusChangeError::UnknownSubscriber)?;
+        let to = SubscriptionStatus::PendingConfirmation;
+        if subscriber.status != SubscriptionStatus::Unsubscribed {
+            return Err(IllegalTransition {
+                from: subscriber.status,
+                to,
+            }
+            .into());
+        }
+        subscriber.status = subscriber.status.transition_to(to)?;
+        subscriber.name = new_subscriber.name.as_ref().to_string();
+        subscriber.locale = new_subscriber.locale.clone();
+        subscriber.subscribed_at = Utc::now();
+        subscriber.reminded = false;
+        tables.tokens.retain(|_, id| *id != subscriber_id);
+        if let Some(token_hash) = token_hash {
+            tables.tokens.insert(token_hash, subscriber_id);
+        }
+        Ok(())
+    }
+
+    async fn change_status(
+        &self,
+        subscriber_id: Uuid,
+        to: SubscriptionStatus,
+    ) -> Result<SubscriptionStatus, StatusChangeError> {
+        let mut tables = self.tables.lock().expect("Subscriber tables lock poisoned");
+        let subscriber = tables
+            .subscribers
+            .get_mut(&subscriber_id)
+            .ok_or(StatusChangeError::UnknownSubscriber)?;
+        let from = subscriber.status;
+        subscriber.status = from.transition_to(to)?;
+        Ok(from)
+    }
+
+    async fn pending_to_remind(
+        &self,
+        subscribed_before: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<Vec<Subscriber>, anyhow::Error> {
+        let tables = self.tables.lock().expect("Subscriber tables lock poisoned");
+        let mut pending: Vec<_> = tables
+            .subscribers
+            .iter()
+            .filter(|(_, subscriber)| {
+                subscriber.status == SubscriptionStatus::PendingConfirmation
+                    && !subscriber.reminded
+                    && subscriber.subscribed_at < subscribed_before
+            })
+            .collect();
+        pending.sort_by_key(|(_, subscriber)| subscriber.subscribed_at);
+        Ok(pending
+            .into_iter()
+            .take(limit)
+            .map(|(id, subscriber)| Subscriber {
+                id: *id,
+                email: subscriber.email.clone(),
+                locale: subscriber.locale.clone(),
+                status: subscriber.status,
+            })
+            .collect())
+    }
+
+    async fn claim_reminder(&self, subscriber_id: Uuid) -> Result<bool, anyhow::Error> {
+        let mut tables = self.tables.lock().expect("Subscriber tables lock poisoned");
+        Ok(match tables.subscribers.get_mut(&subscriber_id) {
+            Some(subscriber) if !subscriber.reminded => {
+                subscriber.reminded = true;
+                true
+            }
+            _ => false,
+        })
+    }
+
+    async fn purge_pending(&self, subscribed_before: DateTime<Utc>) -> Result<u64, anyhow::Error> {
+        Ok(self.delete_where(|_, subscriber| {
+            subscriber.status == SubscriptionStatus::PendingConfirmation
+                && subscriber.subscribed_at < subscribed_before
+        }))
+    }
+
+    async fn list(
+        &self,
+        status: Option<SubscriptionStatus>,
+    ) -> Result<Vec<SubscriberRow>, anyhow::Error> {
+        let tables = self.tables.lock().expect("Subscriber tables lock poisoned");
+        let mut rows: Vec<_> = tables
+            .subscribers
+            .values()
+            .filter(|subscriber| status.is_none_or(|status| subscriber.status == status))
+            .map(|subscriber| SubscriberRow {
+                email: subscriber.email.to_string(),
+                name: subscriber.name.clone(),
+                status: subscriber.status.as_str().to_string(),
+                locale: subscriber.locale.clone(),
+                subscribed_at: subscriber.subscribed_at,
+            })
+            .collect();
+        rows.sort_by(|a, b| (a.subscribed_at, &a.email).cmp(&(b.subscribed_at, &b.email)));
+        Ok(rows)
+    }
+
+    async fn remove(&self, email: &SubscriberEmail) -> Result<bool, anyhow::Error> {
+        let canonical = email.canonical();
+        Ok(self.delete_where(|_, subscriber| subscriber.email.canonical() == canonical) > 0)
+    }
+
+    async fn confirmed_subscribers_page(
+        &self,
+        after: Option<Uuid>,
+        limit: usize,
+    ) -> Result<ConfirmedSubscriberPage, anyhow::Error> {
+        let tables = self.tables.lock().expect("Subscriber tables lock poisoned");
+        let mut confirmed: Vec<_> = tables
+            .subscribers
+            .iter()
+            .filter(|(id, subscriber)| {
+                subscriber.status == SubscriptionStatus::Confirmed
+                    && after.is_none_or(|after| **id > after)
+            })
+            .collect();
+        confirmed.sort_by_key(|(id, _)| **id);
+        confirmed.truncate(limit);
+
+        let next = match confirmed.last() {
+            Some((id, _)) if confirmed.len() == limit => Some(**id),
+            _ => None,
+        };
+        let subscribers = confirmed
+            .into_iter()
+            .map(|(id, subscriber)| {
+                Ok(ConfirmedSubscriber {
+                    id: *id,
+                    email: subscriber.email.clone(),
+                    locale: subscriber.locale.clone(),
+                })
+            })
+            .collect();
+        Ok(ConfirmedSubscriberPage { subscribers, next })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claim::{assert_none, assert_ok, assert_some_eq};
+
+    use std::time::Duration;
+
+    use sqlx::types::chrono::Utc;
+
+    use super::InMemorySubscriberRepository;
+    use crate::{
+        database::subscribers::{
+            AlreadySubscribed, ConfirmedSubscribers, StatusChangeError, SubscriberRepository,
+        },
+        domain::{NewSubscriber, SubscriberEmail, SubscriberName, SubscriptionStatus},
+    };
+
+    async fn confirmed(repository: &InMemorySubscriberRepository) -> Vec<String> {
+        let mut emails = vec![];
+        let mut pages = ConfirmedSubscribers::new(repository, 2);
+        while let Some(page) = pages.next_page().await.unwrap() {
+            for subscriber in page {
+                emails.push(subscriber.unwrap().email.to_string());
+            }
+        }
+        emails
+    }
+
+    fn new_subscriber(email: &str) -> NewSubscriber {
+        NewSubscriber {
+            email: SubscriberEmail::parse(email.to_string()).unwrap(),
+            name: SubscriberName::parse("Ursula Le Guin".to_string()).unwrap(),
+            locale: "en".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn a_pending_subscriber_is_found_by_token_and_can_be_confirmed() {
+        let repository = InMemorySubscriberRepository::new();
+        let id = repository
+            .insert_pending(&new_subscriber("ursula@example.com"), Some("token"))
+            .await
+            .unwrap();
+
+        assert_some_eq!(
+            repository.subscriber_id_from_token("token").await.unwrap(),
+            id
+        );
+        assert!(confirmed(&repository).await.is_empty());
+
+        assert_ok!(
+            repository
+                .change_status(id, SubscriptionStatus::Confirmed)
+                .await
+        );
+        assert_eq!(confirmed(&repository).await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn emails_are_unique_on_their_canonical_form() {
+        let repository = InMemorySubscriberRepository::new();
+        assert_ok!(
+            repository
+                .insert_confirmed(&new_subscriber("ursula@example.com"))
+                .await
+        );
+
+        let duplicate = repository
+            .insert_pending(&new_subscriber("Ursula@EXAMPLE.com"), Some("token"))
+            .await
+            .unwrap_err();
+        assert!(duplicate.is::<AlreadySubscribed>());
+        assert_none!(repository.subscriber_id_from_token("token").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn illegal_transitions_leave_the_status_alone() {
+        let repository = InMemorySubscriberRepository::new();
+        let email = SubscriberEmail::parse("ursula@example.com".to_string()).unwrap();
+        let id = repository
+            .insert_pending(&new_subscriber("ursula@example.com"), Some("token"))
+            .await
+            .unwrap();
+        assert_some_eq!(
+            repository.subscriber_id_from_email(&email).await.unwrap(),
+            id
+        );
+        repository
+            .change_status(id, SubscriptionStatus::Bounced)
+            .await
+            .unwrap();
+
+        let result = repository
+            .change_status(id, SubscriptionStatus::Confirmed)
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(StatusChangeError::IllegalTransition(_))
+        ));
+        assert!(confirmed(&repository).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn a_replaced_token_stops_working() {
+        let repository = InMemorySubscriberRepository::new();
+        let id = repository
+            .insert_pending(&new_subscriber("ursula@example.com"), Some("old"))
+            .await
+            .unwrap();
+
+        repository.replace_token(id, "new").await.unwrap();
+
+        assert_none!(repository.subscriber_id_from_token("old").await.unwrap());
+        assert_some_eq!(
+            repository.subscriber_id_from_token("new").await.unwrap(),
+            id
+        );
+    }
+
+    #[tokio::test]
+    async fn only_unsubscribed_subscribers_can_start_over() {
+        let repository = InMemorySubscriberRepository::new();
+        let id = repository
+            .insert_confirmed(&new_subscriber("ursula@example.com"))
+            .await
+            .unwrap();
+        assert!(matches!(
+            repository
+                .resubscribe(id, &new_subscriber("ursula@example.com"), Some("token"))
+                .await,
+            Err(StatusChangeError::IllegalTransition(_))
+        ));
+
+        repository
+            .change_status(id, SubscriptionStatus::Unsubscribed)
+            .await
+            .unwrap();
+        repository
+            .resubscribe(id, &new_subscriber("ursula@example.com"), Some("token"))
+            .await
+            .unwrap();
+
+        assert_some_eq!(
+            repository.subscriber_id_from_token("token").await.unwrap(),
+            id
+        );
+        let pending = repository
+            .list(Some(SubscriptionStatus::PendingConfirmation))
+            .await
+            .unwrap();
+        assert_eq!(pending.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn only_stale_pending_subscribers_are_purged() {
+        const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+        let repository = InMemorySubscriberRepository::new();
+        let pending = repository
+            .insert_pending(&new_subscriber("pending@example.com"), Some("token"))
+            .await
+            .unwrap();
+        repository
+            .insert_confirmed(&new_subscriber("confirmed@example.com"))
+            .await
+            .unwrap();
+
+        assert_eq!(repository.purge_pending(Utc::now() - DAY).await.unwrap(), 0);
+        assert!(repository.claim_reminder(pending).await.unwrap());
+        assert!(!repository.claim_reminder(pending).await.unwrap());
+
+        assert_eq!(repository.purge_pending(Utc::now() + DAY).await.unwrap(), 1);
+        assert_none!(repository.subscriber_id_from_token("token").await.unwrap());
+        assert_eq!(confirmed(&repository).await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn subscribers_are_listed_and_removed_with_their_tokens() {
+        let repository = InMemorySubscriberRepository::new();
+        repository
+            .insert_pending(&new_subscriber("pending@example.com"), Some("token"))
+            .await
+            .unwrap();
+        repository
+            .insert_confirmed(&new_subscriber("confirmed@example.com"))
+            .await
+            .unwrap();
+
+        let confirmed = repository
+            .list(Some(SubscriptionStatus::Confirmed))
+            .await
+            .unwrap();
+        assert_eq!(confirmed.len(), 1);
+        assert_eq!(confirmed[0].email, "confirmed@example.com");
+        assert_eq!(confirmed[0].name, "Ursula Le Guin");
+        assert_eq!(repository.list(None).await.unwrap().len(), 2);
+
+        let email = SubscriberEmail::parse("Pending@Example.com".to_string()).unwrap();
+        assert!(repository.remove(&email).await.unwrap());
+        assert!(!repository.remove(&email).await.unwrap());
+        assert_none!(repository.subscriber_id_from_token("token").await.unwrap());
+        assert_eq!(repository.list(None).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn confirmed_subscribers_come_in_pages() {
+        let repository = InMemorySubscriberRepository::new();
+        for i in 0..5 {
+            repository
+                .insert_confirmed(&new_subscriber(&format!("reader{}@example.com", i)))
+                .await
+                .unwrap();
+        }
+
+        let mut pages = ConfirmedSubscribers::new(&repository, 2);
+        let mut sizes = vec![];
+        while let Some(page) = pages.next_page().await.unwrap() {
+            sizes.push(page.len());
+        }
+
+        assert_eq!(sizes, vec![2, 2, 1]);
+    }
+}
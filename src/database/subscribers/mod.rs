@@ -0,0 +1,180 @@
+pub mod memory;
+pub mod postgres;
+
+use async_trait::async_trait;
+use sqlx::types::chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::domain::{IllegalTransition, NewSubscriber, SubscriberEmail, SubscriptionStatus};
+
+#[derive(thiserror::Error, Debug)]
+pub enum StatusChangeError {
+    #[error("There is no subscriber with this id")]
+    UnknownSubscriber,
+    #[error(transparent)]
+    IllegalTransition(#[from] IllegalTransition),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+/// The email, in its canonical form, belongs to a stored subscriber already.
+#[derive(thiserror::Error, Debug)]
+#[error("The email is subscribed already")]
+pub struct AlreadySubscribed;
+
+/// A stored subscriber, whatever their status.
+pub struct Subscriber {
+    pub id: Uuid,
+    pub email: SubscriberEmail,
+    pub locale: String,
+    pub status: SubscriptionStatus,
+}
+
+/// A subscriber as listed by `subscribers list`. The email is the stored one, it may not
+/// parse anymore.
+pub struct SubscriberRow {
+    pub email: String,
+    pub name: String,
+    pub status: String,
+    pub locale: String,
+    pub subscribed_at: DateTime<Utc>,
+}
+
+pub struct ConfirmedSubscriber {
+    pub id: Uuid,
+    pub email: SubscriberEmail,
+    pub locale: String,
+}
+
+/// Confirmed subscribers ordered by id. The `Err`s are stored rows whose email doesn't
+/// parse anymore.
+pub struct ConfirmedSubscriberPage {
+    pub subscribers: Vec<Result<ConfirmedSubscriber, anyhow::Error>>,
+    /// Where the next page starts, `None` on the last page.
+    pub next: Option<Uuid>,
+}
+
+/// Where subscribers and their confirmation tokens are stored.
+///
+/// Postgres in production, the in-memory repository lets handlers run without a database.
+#[async_trait]
+pub trait SubscriberRepository: Send + Sync {
+    /// Stores a subscriber pending confirmation together with their token, all or nothing.
+    /// Only the token's digest is kept, and there is none with signed confirmation links.
+    /// Fails with `AlreadySubscribed` if the email is taken already.
+    async fn insert_pending(
+        &self,
+        new_subscriber: &NewSubscriber,
+        subscription_token: Option<&str>,
+    ) -> Result<Uuid, anyhow::Error>;
+
+    /// Stores a subscriber that needs no confirmation. Fails with `AlreadySubscribed` if the
+    /// email is taken already.
+    async fn insert_confirmed(&self, new_subscriber: &NewSubscriber)
+        -> Result<Uuid, anyhow::Error>;
+
+    async fn subscriber_id_from_token(
+        &self,
+        subscription_token: &str,
+    ) -> Result<Option<Uuid>, anyhow::Error>;
+
+    async fn subscriber_id_from_email(
+        &self,
+        email: &SubscriberEmail,
+    ) -> Result<Option<Uuid>, anyhow::Error>;
+
+    async fn subscriber_by_email(
+        &self,
+        email: &SubscriberEmail,
+    ) -> Result<Option<Subscriber>, anyhow::Error>;
+
+    /// Swaps every token of the subscriber for `subscription_token`, all or nothing.
+    async fn replace_token(
+        &self,
+        subscriber_id: Uuid,
+        subscription_token: &str,
+    ) -> Result<(), anyhow::Error>;
+
+    /// Starts an unsubscribed subscriber over as if they had just signed up with
+    /// `new_subscriber`: pending again, not reminded yet, and `subscription_token` replacing
+    /// their old tokens. All or nothing.
+    async fn resubscribe(
+        &self,
+        subscriber_id: Uuid,
+        new_subscriber: &NewSubscriber,
+        subscription_token: Option<&str>,
+    ) -> Result<(), StatusChangeError>;
+
+    /// Moves a subscriber to `to` if `SubscriptionStatus::transition_to` allows it.
+    /// Returns the status they were in before.
+    async fn change_status(
+        &self,
+        subscriber_id: Uuid,
+        to: SubscriptionStatus,
+    ) -> Result<SubscriptionStatus, StatusChangeError>;
+
+    /// Up to `limit` subscribers still pending since before `subscribed_before` who haven't
+    /// been reminded yet. Rows whose stored email doesn't parse are skipped.
+    async fn pending_to_remind(
+        &self,
+        subscribed_before: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<Vec<Subscriber>, anyhow::Error>;
+
+    /// Marks the subscriber as reminded. `false` if they were already, e.g. by another
+    /// instance running the same job.
+    async fn claim_reminder(&self, subscriber_id: Uuid) -> Result<bool, anyhow::Error>;
+
+    /// Deletes the subscribers still pending since before `subscribed_before`, with their
+    /// tokens. Returns how many were deleted.
+    async fn purge_pending(&self, subscribed_before: DateTime<Utc>) -> Result<u64, anyhow::Error>;
+
+    /// Every subscriber, oldest first, optionally only those in `status`.
+    async fn list(
+        &self,
+        status: Option<SubscriptionStatus>,
+    ) -> Result<Vec<SubscriberRow>, anyhow::Error>;
+
+    /// Deletes the subscriber with their tokens. `false` if there was nobody to delete.
+    async fn remove(&self, email: &SubscriberEmail) -> Result<bool, anyhow::Error>;
+
+    /// Up to `limit` confirmed subscribers with an id greater than `after`.
+    async fn confirmed_subscribers_page(
+        &self,
+        after: Option<Uuid>,
+        limit: usize,
+    ) -> Result<ConfirmedSubscriberPage, anyhow::Error>;
+}
+
+/// Walks every confirmed subscriber a page at a time, so memory stays flat however long
+/// the list is and the first page is available right away.
+pub struct ConfirmedSubscribers<'a> {
+    repository: &'a dyn SubscriberRepository,
+    page_size: usize,
+    next: Option<Option<Uuid>>,
+}
+
+impl<'a> ConfirmedSubscribers<'a> {
+    pub fn new(repository: &'a dyn SubscriberRepository, page_size: usize) -> Self {
+        Self {
+            repository,
+            page_size,
+            next: Some(None),
+        }
+    }
+
+    /// `None` once every page has been handed out.
+    pub async fn next_page(
+        &mut self,
+    ) -> Result<Option<Vec<Result<ConfirmedSubscriber, anyhow::Error>>>, anyhow::Error> {
+        let Some(after) = self.next else {
+            return Ok(None);
+        };
+        let page = self
+            .repository
+            .confirmed_subscribers_page(after, self.page_size)
+            .await?;
+        self.next = page.next.map(Some);
+        Ok(Some(page.subscribers))
+    }
+}
@@ -0,0 +1,495 @@
+use anyhow::Context;
+use async_trait::async_trait;
+use sqlx::{
+    types::chrono::{DateTime, Utc},
+    PgConnection, PgPool,
+};
+use uuid::Uuid;
+
+use super::{
+    AlreadySubscribed, ConfirmedSubscriber, ConfirmedSubscriberPage, StatusChangeError, Subscriber,
+    SubscriberRepository, SubscriberRow,
+};
+use crate::domain::{
+    subscription_token_digest, IllegalTransition, NewSubscriber, SubscriberEmail,
+    SubscriptionStatus,
+};
+
+pub struct PostgresSubscriberRepository {
+    pool: PgPool,
+}
+
+impl PostgresSubscriberRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl SubscriberRepository for PostgresSubscriberRepository {
+    async fn insert_pending(
+        &self,
+        new_subscriber: &NewSubscriber,
+        subscription_token: Option<&str>,
+    ) -> Result<Uuid, anyhow::Error> {
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .context("Failed to acquire a Postgres connection from the pool.")?;
+        let subscriber_id = insert_subscriber(&mut transaction, new_subscriber).await?;
+        if let Some(subscription_token) = subscription_token {
+            store_token(&mut transaction, subscriber_id, subscription_token)
+                .await
+                .context("Failed to store the confirmation token for a new subscriber.")?;
+        }
+        transaction
+            .commit()
+            .await
+            .context("Failed to commit SQL transaction to store a new subscriber.")?;
+        Ok(subscriber_id)
+    }
+
+    async fn insert_confirmed(
+        &self,
+        new_subscriber: &NewSubscriber,
+    ) -> Result<Uuid, anyhow::Error> {
+        let mut transaction = self.pool.begin().await?;
+        let subscriber_id = insert_subscriber(&mut transaction, new_subscriber).await?;
+        change_status(
+            &mut transaction,
+            subscriber_id,
+            SubscriptionStatus::Confirmed,
+        )
+        .await?;
+        transaction.commit().await?;
+        Ok(subscriber_id)
+    }
+
+    #[tracing::instrument(name = "Get subscriber ID from token", skip(self, subscription_token))]
+    async fn subscriber_id_from_token(
+        &self,
+        subscription_token: &str,
+    ) -> Result<Option<Uuid>, anyhow::Error> {
+        let subscriber_id = sqlx::query_scalar!(
+            "SELECT subscriber_id FROM subscription_tokens WHERE token_hash = $1",
+            subscription_token_digest(subscription_token)
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(subscriber_id)
+    }
+
+    async fn subscriber_id_from_email(
+        &self,
+        email: &SubscriberEmail,
+    ) -> Result<Option<Uuid>, anyhow::Error> {
+        let subscriber_id = sqlx::query_scalar!(
+            "SELECT id FROM subscriptions WHERE email_canonical = $1",
+            email.canonical()
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(subscriber_id)
+    }
+
+    async fn subscriber_by_email(
+        &self,
+        email: &SubscriberEmail,
+    ) -> Result<Option<Subscriber>, anyhow::Error> {
+        let Some(row) = sqlx::query!(
+            "SELECT id, email, locale, status FROM subscriptions WHERE email_canonical = $1",
+            email.canonical()
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        else {
+            return Ok(None);
+        };
+        Ok(Some(Subscriber {
+            id: row.id,
+            email: SubscriberEmail::parse(row.email).map_err(anyhow::Error::msg)?,
+            locale: row.locale,
+            status: row.status.parse().map_err(anyhow::Error::msg)?,
+        }))
+    }
+
+    #[tracing::instrument(name = "Replace subscription token", skip(self, subscription_token))]
+    async fn replace_token(
+        &self,
+        subscriber_id: Uuid,
+        subscription_token: &str,
+    ) -> Result<(), anyhow::Error> {
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .context("Failed to acquire a Postgres connection from the pool.")?;
+        sqlx::query!(
+            "DELETE FROM subscription_tokens WHERE subscriber_id = $1",
+            subscriber_id
+        )
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to delete the old confirmation tokens.")?;
+        store_token(&mut transaction, subscriber_id, subscription_token)
+            .await
+            .context("Failed to store the new confirmation token.")?;
+        transaction
+            .commit()
+            .await
+            .context("Failed to commit SQL transaction to replace a confirmation token.")?;
+        Ok(())
+    }
+
+    #[tracing::instrument(
+        name = "Resubscribe a subscriber",
+        skip(self, new_subscriber, subscription_token)
+    )]
+    async fn resubscribe(
+        &self,
+        subscriber_id: Uuid,
+        new_subscriber: &NewSubscriber,
+        subscription_token: Option<&str>,
+    ) -> Result<(), StatusChangeError> {
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .context("Failed to acquire a Postgres connection from the pool.")?;
+        let from = change_status(
+            &mut transaction,
+            subscriber_id,
+            SubscriptionStatus::PendingConfirmation,
+        )
+        .await?;
+        // Staying put is allowed, but only unsubscribed subscribers start over.
+        if from != SubscriptionStatus::Unsubscribed {
+            return Err(IllegalTransition {
+                from,
+                to: SubscriptionStatus::PendingConfirmation,
+            }
+            .into());
+        }
+        // A fresh `subscribed_at`, or the cleanup job would purge them right away.
+        sqlx::query!(
+            r#"
+              UPDATE subscriptions
+              SET name = $2, locale = $3, subscribed_at = $4, reminded_at = NULL
+              WHERE id = $1
+            "#,
+            subscriber_id,
+            new_subscriber.name.as_ref(),
+            new_subscriber.locale,
+            Utc::now()
+        )
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to restart the subscription.")?;
+        sqlx::query!(
+            "DELETE FROM subscription_tokens WHERE subscriber_id = $1",
+            subscriber_id
+        )
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to delete the old confirmation tokens.")?;
+        if let Some(subscription_token) = subscription_token {
+            store_token(&mut transaction, subscriber_id, subscription_token)
+                .await
+                .context("Failed to store the new confirmation token.")?;
+        }
+        transaction
+            .commit()
+            .await
+            .context("Failed to commit SQL transaction to resubscribe a subscriber.")?;
+        Ok(())
+    }
+
+    async fn change_status(
+        &self,
+        subscriber_id: Uuid,
+        to: SubscriptionStatus,
+    ) -> Result<SubscriptionStatus, StatusChangeError> {
+        let mut connection = self.pool.acquire().await.map_err(anyhow::Error::from)?;
+        change_status(&mut connection, subscriber_id, to).await
+    }
+
+    #[tracing::instrument(name = "Get pending subscribers to remind", skip(self))]
+    async fn pending_to_remind(
+        &self,
+        subscribed_before: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<Vec<Subscriber>, anyhow::Error> {
+        let rows = sqlx::query!(
+            r#"
+              SELECT id, email, locale FROM subscriptions
+              WHERE status = $1 AND reminded_at IS NULL AND subscribed_at < $2
+              ORDER BY subscribed_at
+              LIMIT $3
+            "#,
+            SubscriptionStatus::PendingConfirmation.as_str(),
+            subscribed_before,
+            limit as i64
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|row| match SubscriberEmail::parse(row.email) {
+                Ok(email) => Some(Subscriber {
+                    id: row.id,
+                    email,
+                    locale: row.locale,
+                    status: SubscriptionStatus::PendingConfirmation,
+                }),
+                Err(error) => {
+                    tracing::warn!(subscriber_id = %row.id, "Not reminding, {}", error);
+                    None
+                }
+            })
+            .collect())
+    }
+
+    async fn claim_reminder(&self, subscriber_id: Uuid) -> Result<bool, anyhow::Error> {
+        let claimed = sqlx::query!(
+            r#"
+              UPDATE subscriptions SET reminded_at = $2
+              WHERE id = $1 AND reminded_at IS NULL
+            "#,
+            subscriber_id,
+            Utc::now()
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        Ok(claimed > 0)
+    }
+
+    #[tracing::instrument(name = "Purge stale pending subscribers", skip(self))]
+    async fn purge_pending(&self, subscribed_before: DateTime<Utc>) -> Result<u64, anyhow::Error> {
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .context("Failed to acquire a Postgres connection from the pool.")?;
+        let stale = sqlx::query_scalar!(
+            "SELECT id FROM subscriptions WHERE status = $1 AND subscribed_at < $2 FOR UPDATE",
+            SubscriptionStatus::PendingConfirmation.as_str(),
+            subscribed_before
+        )
+        .fetch_all(&mut *transaction)
+        .await
+        .context("Failed to find stale pending subscribers.")?;
+        let purged = delete_subscribers(&mut transaction, &stale).await?;
+        transaction
+            .commit()
+            .await
+            .context("Failed to commit SQL transaction to purge pending subscribers.")?;
+        Ok(purged)
+    }
+
+    #[tracing::instrument(name = "List subscribers", skip(self))]
+    async fn list(
+        &self,
+        status: Option<SubscriptionStatus>,
+    ) -> Result<Vec<SubscriberRow>, anyhow::Error> {
+        let rows = sqlx::query_as!(
+            SubscriberRow,
+            r#"
+              SELECT email, name, status, locale, subscribed_at FROM subscriptions
+              WHERE $1::TEXT IS NULL OR status = $1
+              ORDER BY subscribed_at, email
+            "#,
+            status.map(|status| status.as_str())
+        )
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to list subscribers.")?;
+        Ok(rows)
+    }
+
+    #[tracing::instrument(name = "Remove a subscriber", skip(self, email))]
+    async fn remove(&self, email: &SubscriberEmail) -> Result<bool, anyhow::Error> {
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .context("Failed to acquire a Postgres connection from the pool.")?;
+        let ids = sqlx::query_scalar!(
+            "SELECT id FROM subscriptions WHERE email_canonical = $1 FOR UPDATE",
+            email.canonical()
+        )
+        .fetch_all(&mut *transaction)
+        .await
+        .context("Failed to look the subscriber up.")?;
+        let removed = delete_subscribers(&mut transaction, &ids).await?;
+        transaction
+            .commit()
+            .await
+            .context("Failed to commit SQL transaction to remove a subscriber.")?;
+        Ok(removed > 0)
+    }
+
+    #[tracing::instrument(name = "Get a page of confirmed subscribers", skip(self))]
+    async fn confirmed_subscribers_page(
+        &self,
+        after: Option<Uuid>,
+        limit: usize,
+    ) -> Result<ConfirmedSubscriberPage, anyhow::Error> {
+        struct Row {
+            id: Uuid,
+            email: String,
+            locale: String,
+        }
+        // Keyset pagination on the primary key: every page is an index range scan, and no
+        // connection is held while the caller works through the previous page.
+        let rows = sqlx::query_as!(
+            Row,
+            r#"
+              SELECT id, email, locale FROM subscriptions
+              WHERE status = $1 AND ($2::UUID IS NULL OR id > $2)
+              ORDER BY id
+              LIMIT $3
+            "#,
+            SubscriptionStatus::Confirmed.as_str(),
+            after,
+            limit as i64
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let next = match rows.last() {
+            Some(last) if rows.len() == limit => Some(last.id),
+            _ => None,
+        };
+        let subscribers = rows
+            .into_iter()
+            .map(|r| match SubscriberEmail::parse(r.email) {
+                Ok(email) => Ok(ConfirmedSubscriber {
+                    id: r.id,
+                    email,
+                    locale: r.locale,
+                }),
+                Err(error) => Err(anyhow::anyhow!(error)),
+            })
+            .collect();
+
+        Ok(ConfirmedSubscriberPage { subscribers, next })
+    }
+}
+
+/// Deletes the subscribers and their tokens, the tokens first since they reference them.
+async fn delete_subscribers(
+    connection: &mut PgConnection,
+    subscriber_ids: &[Uuid],
+) -> Result<u64, anyhow::Error> {
+    sqlx::query!(
+        "DELETE FROM subscription_tokens WHERE subscriber_id = ANY($1)",
+        subscriber_ids
+    )
+    .execute(&mut *connection)
+    .await
+    .context("Failed to delete the subscribers' tokens.")?;
+    let deleted = sqlx::query!(
+        "DELETE FROM subscriptions WHERE id = ANY($1)",
+        subscriber_ids
+    )
+    .execute(connection)
+    .await
+    .context("Failed to delete the subscribers.")?
+    .rows_affected();
+    Ok(deleted)
+}
+
+#[tracing::instrument(
+    name = "Saving new subscriber details in the database",
+    skip(connection, new_subscriber)
+)]
+async fn insert_subscriber(
+    connection: &mut PgConnection,
+    new_subscriber: &NewSubscriber,
+) -> Result<Uuid, anyhow::Error> {
+    let subscriber_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+          INSERT INTO subscriptions (id, email, email_canonical, name, locale, subscribed_at, status)
+          VALUES ($1, $2, $3, $4, $5, $6, $7)
+        "#,
+        subscriber_id,
+        new_subscriber.email.as_ref(),
+        new_subscriber.email.canonical(),
+        new_subscriber.name.as_ref(),
+        new_subscriber.locale,
+        Utc::now(),
+        SubscriptionStatus::PendingConfirmation.as_str()
+    )
+    .execute(connection)
+    .await
+    .map_err(|e| match e {
+        // Both unique columns are the email.
+        sqlx::Error::Database(e) if e.is_unique_violation() => AlreadySubscribed.into(),
+        e => anyhow::Error::new(e).context("Failed to insert new subscriber in the database."),
+    })?;
+
+    Ok(subscriber_id)
+}
+
+#[tracing::instrument(
+    name = "Store subscription token in the database",
+    skip(connection, subscription_token)
+)]
+async fn store_token(
+    connection: &mut PgConnection,
+    subscriber_id: Uuid,
+    subscription_token: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+          INSERT INTO subscription_tokens (token_hash, subscriber_id)
+          VALUES ($1, $2)
+        "#,
+        subscription_token_digest(subscription_token),
+        subscriber_id
+    )
+    .execute(connection)
+    .await?;
+
+    Ok(())
+}
+
+/// Checked and applied in one statement, so a concurrent change can't slip in between.
+#[tracing::instrument(name = "Change subscription status", skip(connection))]
+async fn change_status(
+    connection: &mut PgConnection,
+    subscriber_id: Uuid,
+    to: SubscriptionStatus,
+) -> Result<SubscriptionStatus, StatusChangeError> {
+    let allowed_from: Vec<String> = SubscriptionStatus::ALL
+        .into_iter()
+        .filter(|from| from.transition_to(to).is_ok())
+        .map(|from| from.as_str().to_string())
+        .collect();
+
+    let previous = sqlx::query_scalar!(
+        r#"
+          WITH current AS (SELECT id, status FROM subscriptions WHERE id = $1 FOR UPDATE)
+          UPDATE subscriptions s
+          SET status = CASE WHEN current.status = ANY($3) THEN $2 ELSE s.status END
+          FROM current
+          WHERE s.id = current.id
+          RETURNING current.status AS "previous!"
+        "#,
+        subscriber_id,
+        to.as_str(),
+        &allowed_from[..]
+    )
+    .fetch_optional(&mut *connection)
+    .await
+    .context("Failed to update the subscription status.")?
+    .ok_or(StatusChangeError::UnknownSubscriber)?;
+
+    let from: SubscriptionStatus = previous.parse().map_err(anyhow::Error::msg)?;
+    from.transition_to(to)?;
+    Ok(from)
+}
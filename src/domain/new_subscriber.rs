@@ -3,4 +3,5 @@ use super::{SubscriberEmail, SubscriberName};
 pub struct NewSubscriber {
     pub email: SubscriberEmail,
     pub name: SubscriberName,
+    pub locale: String,
 }
@@ -1,28 +1,62 @@
 use validator::ValidateEmail;
 
+/// An email address as the subscriber typed it, plus the canonical form we
+/// use to tell whether two addresses reach the same mailbox.
 #[derive(Debug, Clone)]
-pub struct SubscriberEmail(String);
+pub struct SubscriberEmail {
+    original: String,
+    canonical: String,
+}
 
 impl AsRef<str> for SubscriberEmail {
     fn as_ref(&self) -> &str {
-        &self.0
+        &self.original
     }
 }
 
 impl SubscriberEmail {
     pub fn parse(s: String) -> Result<SubscriberEmail, String> {
-        if s.validate_email() {
-            Ok(Self(s))
-        } else {
-            Err(format!("{} is not a valid subscriber email", s))
+        let original = s.trim();
+        if !original.validate_email() {
+            return Err(format!("{} is not a valid subscriber email", s));
         }
+        let canonical = canonicalise(original)
+            .ok_or_else(|| format!("{} is not a valid subscriber email", s))?;
+        Ok(Self {
+            original: original.to_string(),
+            canonical,
+        })
+    }
+
+    /// Lowercased, with the domain turned into IDNA punycode.
+    ///
+    /// RFC 5321 lets the local part be case sensitive, but no mainstream
+    /// provider treats it that way and subscribers don't expect it either.
+    pub fn canonical(&self) -> &str {
+        &self.canonical
+    }
+
+    /// The canonical domain, the part after the `@`.
+    pub fn domain(&self) -> &str {
+        self.canonical
+            .rsplit_once('@')
+            .map_or("", |(_, domain)| domain)
+    }
+}
+
+fn canonicalise(email: &str) -> Option<String> {
+    let (local_part, domain) = email.rsplit_once('@')?;
+    let domain = idna::domain_to_ascii(domain.trim_end_matches('.')).ok()?;
+    if domain.is_empty() {
+        return None;
     }
+    Some(format!("{}@{}", local_part.to_lowercase(), domain))
 }
 
 impl std::fmt::Display for SubscriberEmail {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         // Forward to the Display implementation of the wrapped String
-        self.0.fmt(f)
+        self.original.fmt(f)
     }
 }
 
@@ -50,4 +84,25 @@ mod tests {
         dbg!(&valid_email.0);
         SubscriberEmail::parse(valid_email.0).is_ok()
     }
+
+    #[test]
+    fn the_original_form_is_kept_but_trimmed() {
+        let email = SubscriberEmail::parse("  Alice@Example.com ".to_string()).unwrap();
+        assert_eq!(email.as_ref(), "Alice@Example.com");
+    }
+
+    #[test]
+    fn addresses_differing_only_in_case_share_a_canonical_form() {
+        let first = SubscriberEmail::parse("Alice@Example.COM".to_string()).unwrap();
+        let second = SubscriberEmail::parse("alice@example.com".to_string()).unwrap();
+        assert_eq!(first.canonical(), "alice@example.com");
+        assert_eq!(first.canonical(), second.canonical());
+    }
+
+    #[test]
+    fn internationalised_domains_are_punycoded() {
+        let email = SubscriberEmail::parse("leser@Bücher.de".to_string()).unwrap();
+        assert_eq!(email.canonical(), "leser@xn--bcher-kva.de");
+        assert_eq!(email.domain(), "xn--bcher-kva.de");
+    }
 }
@@ -0,0 +1,142 @@
+/// Where a subscriber stands. Stored as text, a CHECK constraint keeps the column
+/// to these values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubscriptionStatus {
+    /// Signed up, the confirmation link hasn't been followed yet.
+    PendingConfirmation,
+    Confirmed,
+    Unsubscribed,
+    /// Mail to the address bounces, nothing gets sent to it anymore.
+    Bounced,
+}
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+#[error("A subscription that is {from} can't become {to}")]
+pub struct IllegalTransition {
+    pub from: SubscriptionStatus,
+    pub to: SubscriptionStatus,
+}
+
+impl SubscriptionStatus {
+    pub const ALL: [SubscriptionStatus; 4] = [
+        Self::PendingConfirmation,
+        Self::Confirmed,
+        Self::Unsubscribed,
+        Self::Bounced,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::PendingConfirmation => "pending_confirmation",
+            Self::Confirmed => "confirmed",
+            Self::Unsubscribed => "unsubscribed",
+            Self::Bounced => "bounced",
+        }
+    }
+
+    /// The only place that decides which status changes are allowed. Staying put is
+    /// always fine, e.g. following a confirmation link twice.
+    pub fn transition_to(
+        self,
+        to: SubscriptionStatus,
+    ) -> Result<SubscriptionStatus, IllegalTransition> {
+        use SubscriptionStatus::*;
+
+        let allowed = self == to
+            || matches!(
+                (self, to),
+                (PendingConfirmation, Confirmed)
+                    | (PendingConfirmation | Confirmed, Unsubscribed)
+                    // Signing up again starts over with a new confirmation.
+                    | (Unsubscribed, PendingConfirmation)
+                    | (_, Bounced)
+            );
+        if allowed {
+            Ok(to)
+        } else {
+            Err(IllegalTransition { from: self, to })
+        }
+    }
+}
+
+impl std::str::FromStr for SubscriptionStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|status| status.as_str() == s)
+            .ok_or_else(|| {
+                let known: Vec<_> = Self::ALL.iter().map(|status| status.as_str()).collect();
+                format!(
+                    "`{}` is not a subscription status, use one of: {}",
+                    s,
+                    known.join(", ")
+                )
+            })
+    }
+}
+
+impl std::fmt::Display for SubscriptionStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claim::{assert_err, assert_ok};
+
+    use super::SubscriptionStatus::{self, *};
+
+    #[test]
+    fn the_happy_path_is_allowed() {
+        assert_ok!(PendingConfirmation.transition_to(Confirmed));
+        assert_ok!(Confirmed.transition_to(Unsubscribed));
+        assert_ok!(Unsubscribed.transition_to(PendingConfirmation));
+    }
+
+    #[test]
+    fn any_status_can_bounce() {
+        for status in SubscriptionStatus::ALL {
+            assert_eq!(status.transition_to(Bounced), Ok(Bounced));
+        }
+    }
+
+    #[test]
+    fn staying_put_is_allowed() {
+        for status in SubscriptionStatus::ALL {
+            assert_eq!(status.transition_to(status), Ok(status));
+        }
+    }
+
+    #[test]
+    fn confirmation_needs_a_pending_subscription() {
+        assert_err!(Unsubscribed.transition_to(Confirmed));
+        assert_err!(Bounced.transition_to(Confirmed));
+    }
+
+    #[test]
+    fn bounced_addresses_stay_bounced() {
+        for status in [PendingConfirmation, Confirmed, Unsubscribed] {
+            assert_err!(Bounced.transition_to(status));
+        }
+    }
+
+    #[test]
+    fn illegal_transitions_say_what_was_attempted() {
+        let error = Bounced.transition_to(Confirmed).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "A subscription that is bounced can't become confirmed"
+        );
+    }
+
+    #[test]
+    fn statuses_round_trip_through_their_stored_form() {
+        for status in SubscriptionStatus::ALL {
+            assert_eq!(status.as_str().parse::<SubscriptionStatus>(), Ok(status));
+        }
+        assert!("active".parse::<SubscriptionStatus>().is_err());
+    }
+}
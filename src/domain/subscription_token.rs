@@ -0,0 +1,49 @@
+use rand::{distributions::Alphanumeric, rngs::OsRng, Rng};
+use sha2::{Digest, Sha256};
+
+/// Versions the token format, so it can change without breaking links already sent.
+const TOKEN_PREFIX: &str = "st1_";
+const TOKEN_RANDOM_CHARS: usize = 32;
+
+/// A fresh confirmation token: the version prefix followed by 32 alphanumerics, about 190 bits
+/// straight from the operating system's CSPRNG.
+pub fn generate_subscription_token() -> String {
+    let random: String = OsRng
+        .sample_iter(&Alphanumeric)
+        .take(TOKEN_RANDOM_CHARS)
+        .map(char::from)
+        .collect();
+    format!("{}{}", TOKEN_PREFIX, random)
+}
+
+/// What gets stored instead of the token, hex encoded SHA-256. Tokens carry enough entropy
+/// that there's nothing to gain from a salt.
+///
+/// Tokens are looked up by their digest. Whatever the lookup's timing gives away is about
+/// the digest, and an attacker can't pick a token that hashes to a prefix of their choosing.
+pub fn subscription_token_digest(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate_subscription_token, subscription_token_digest};
+
+    #[test]
+    fn tokens_are_prefixed_and_unique() {
+        let token = generate_subscription_token();
+
+        assert!(token.starts_with("st1_"));
+        assert_eq!(token.len(), 36);
+        assert!(token[4..].chars().all(|c| c.is_ascii_alphanumeric()));
+        assert_ne!(token, generate_subscription_token());
+    }
+
+    #[test]
+    fn the_digest_is_hex_encoded_sha256() {
+        assert_eq!(
+            subscription_token_digest("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
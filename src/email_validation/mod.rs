@@ -0,0 +1,295 @@
+pub mod mx;
+
+use std::{collections::HashSet, sync::Arc};
+
+use mx::MxResolver;
+use once_cell::sync::Lazy;
+
+use crate::{configuration::config::EmailValidationConfiguration, domain::SubscriberEmail};
+
+static DISPOSABLE_DOMAINS: Lazy<HashSet<&'static str>> = Lazy::new(|| {
+    include_str!("disposable_domains.txt")
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect()
+});
+
+// Providers people mistype the most. A domain with the same suffix and a name a few
+// edits away from one of these is most likely a typo.
+const POPULAR_DOMAINS: &[&str] = &[
+    "aol.com",
+    "gmail.com",
+    "gmx.com",
+    "gmx.de",
+    "gmx.net",
+    "googlemail.com",
+    "hotmail.com",
+    "i.ua",
+    "icloud.com",
+    "live.com",
+    "mail.com",
+    "mail.ru",
+    "me.com",
+    "msn.com",
+    "outlook.com",
+    "proton.me",
+    "protonmail.com",
+    "ukr.net",
+    "web.de",
+    "yahoo.com",
+    "yandex.ru",
+    "ymail.com",
+];
+
+// Real providers that look like a typo of a popular one, e.g. `email.com` next to `gmail.com`.
+const KNOWN_DOMAINS: &[&str] = &[
+    "bk.ru",
+    "bol.com",
+    "email.com",
+    "gmx.at",
+    "gmx.ch",
+    "inbox.ru",
+    "list.ru",
+    "mac.com",
+    "mail.de",
+    "mail.ua",
+    "meta.ua",
+    "pm.me",
+    "usa.com",
+    "web.ru",
+    "yandex.com",
+    "yandex.ua",
+];
+
+// Misspelt top-level domains that aren't real ones themselves.
+const TLD_TYPOS: &[(&str, &str)] = &[
+    ("cmo", "com"),
+    ("comm", "com"),
+    ("con", "com"),
+    ("cpm", "com"),
+    ("ocm", "com"),
+    ("vom", "com"),
+    ("xom", "com"),
+    ("nte", "net"),
+];
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum EmailRejection {
+    #[error("Addresses at {0} are disposable, please use a permanent one.")]
+    Disposable(String),
+    #[error("Did you mean {0}?")]
+    LikelyTypo(String),
+    #[error("{0} does not accept email.")]
+    Undeliverable(String),
+}
+
+/// Checks that go beyond syntax, run on every new subscription.
+pub struct EmailValidator {
+    reject_disposable: bool,
+    suggest_typos: bool,
+    mx_resolver: Option<Arc<dyn MxResolver>>,
+}
+
+impl EmailValidator {
+    /// The MX lookup only runs when an `mx_resolver` is plugged in.
+    pub fn new(
+        configuration: &EmailValidationConfiguration,
+        mx_resolver: Option<Arc<dyn MxResolver>>,
+    ) -> Self {
+        Self {
+            reject_disposable: configuration.reject_disposable,
+            suggest_typos: configuration.suggest_typos,
+            mx_resolver,
+        }
+    }
+
+    /// `accept_typo` lets the subscriber insist on a domain we flagged as a typo.
+    #[tracing::instrument(name = "Validate subscriber email", skip(self))]
+    pub async fn validate(
+        &self,
+        email: &SubscriberEmail,
+        accept_typo: bool,
+    ) -> Result<(), EmailRejection> {
+        let domain = email.domain().to_string();
+
+        if self.reject_disposable && is_disposable(&domain) {
+            return Err(EmailRejection::Disposable(domain));
+        }
+
+        if self.suggest_typos && !accept_typo {
+            if let Some(suggestion) = suggest_domain(&domain) {
+                let local_part = email.as_ref().rsplit_once('@').map_or("", |(l, _)| l);
+                return Err(EmailRejection::LikelyTypo(format!(
+                    "{}@{}",
+                    local_part, suggestion
+                )));
+            }
+        }
+
+        if let Some(resolver) = &self.mx_resolver {
+            match resolver.accepts_mail(&domain).await {
+                Ok(true) => {}
+                Ok(false) => return Err(EmailRejection::Undeliverable(domain)),
+                Err(error) => {
+                    // DNS hiccups should not cost us a subscriber.
+                    tracing::warn!(
+                        error.cause_chain = ?error,
+                        "MX lookup failed, accepting the address anyway"
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn is_disposable(domain: &str) -> bool {
+    // Walk up the labels so `foo.mailinator.com` matches `mailinator.com`.
+    let mut candidate = domain;
+    loop {
+        if DISPOSABLE_DOMAINS.contains(candidate) {
+            return true;
+        }
+        match candidate.split_once('.') {
+            Some((_, parent)) if parent.contains('.') => candidate = parent,
+            _ => return false,
+        }
+    }
+}
+
+fn suggest_domain(domain: &str) -> Option<&'static str> {
+    if POPULAR_DOMAINS.contains(&domain) || KNOWN_DOMAINS.contains(&domain) {
+        return None;
+    }
+    let (name, suffix) = domain.split_once('.')?;
+    POPULAR_DOMAINS
+        .iter()
+        .filter_map(|popular| {
+            let (popular_name, popular_suffix) = popular.split_once('.')?;
+            if suffix == popular_suffix {
+                let distance = strsim::damerau_levenshtein(name, popular_name);
+                (distance <= max_typo_distance(popular_name)).then_some((distance, *popular))
+            } else if name == popular_name && TLD_TYPOS.contains(&(suffix, popular_suffix)) {
+                Some((1, *popular))
+            } else {
+                None
+            }
+        })
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, popular)| popular)
+}
+
+/// Short names are a few edits away from plenty of real ones (`aol` and `bol`), so they
+/// only get their top-level domain corrected.
+fn max_typo_distance(name: &str) -> usize {
+    match name.chars().count() {
+        0..=3 => 0,
+        4..=5 => 1,
+        _ => 2,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use claim::assert_ok;
+
+    use super::{mx::StubMxResolver, EmailRejection, EmailValidator};
+    use crate::{configuration::config::EmailValidationConfiguration, domain::SubscriberEmail};
+
+    fn validator(mx_domains: Option<&[&str]>) -> EmailValidator {
+        let configuration = EmailValidationConfiguration {
+            reject_disposable: true,
+            suggest_typos: true,
+            check_mx: mx_domains.is_some(),
+        };
+        let resolver = mx_domains
+            .map(|domains| Arc::new(StubMxResolver::new(domains.iter().copied())) as Arc<_>);
+        EmailValidator::new(&configuration, resolver)
+    }
+
+    fn email(s: &str) -> SubscriberEmail {
+        SubscriberEmail::parse(s.to_string()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn a_regular_address_is_accepted() {
+        assert_ok!(
+            validator(None)
+                .validate(&email("ursula@gmail.com"), false)
+                .await
+        );
+    }
+
+    #[tokio::test]
+    async fn disposable_domains_are_rejected() {
+        for address in ["someone@mailinator.com", "someone@eu.Mailinator.com"] {
+            assert!(matches!(
+                validator(None).validate(&email(address), false).await,
+                Err(EmailRejection::Disposable(_))
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn common_typos_come_with_a_suggestion() {
+        for (address, suggestion) in [
+            ("ursula@gmial.com", "ursula@gmail.com"),
+            ("ursula@hotmial.com", "ursula@hotmail.com"),
+            ("ursula@yahoo.con", "ursula@yahoo.com"),
+            ("ursula@outlok.com", "ursula@outlook.com"),
+            ("ursula@gmx.nte", "ursula@gmx.net"),
+        ] {
+            assert_eq!(
+                validator(None).validate(&email(address), false).await,
+                Err(EmailRejection::LikelyTypo(suggestion.to_string()))
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn real_providers_next_to_popular_ones_are_not_typos() {
+        for address in [
+            "ursula@mac.com",
+            "ursula@email.com",
+            "ursula@gmx.at",
+            "ursula@gmx.ch",
+            "ursula@mail.de",
+            "ursula@bol.com",
+            "ursula@yandex.ua",
+            "ursula@web.ru",
+            "ursula@usa.com",
+        ] {
+            assert_eq!(
+                validator(None).validate(&email(address), false).await,
+                Ok(()),
+                "{}",
+                address
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn a_flagged_typo_can_be_insisted_on() {
+        assert_ok!(
+            validator(None)
+                .validate(&email("ursula@gmial.com"), true)
+                .await
+        );
+    }
+
+    #[tokio::test]
+    async fn domains_without_a_mail_server_are_rejected() {
+        let validator = validator(Some(&["gmail.com"]));
+        assert_ok!(validator.validate(&email("ursula@gmail.com"), false).await);
+        assert_eq!(
+            validator
+                .validate(&email("ursula@example.org"), false)
+                .await,
+            Err(EmailRejection::Undeliverable("example.org".to_string()))
+        );
+    }
+}
@@ -0,0 +1,70 @@
+use std::collections::HashSet;
+
+use async_trait::async_trait;
+use hickory_resolver::{error::ResolveErrorKind, TokioAsyncResolver};
+
+/// Tells whether a domain has a mail server willing to accept our messages.
+#[async_trait]
+pub trait MxResolver: Send + Sync {
+    async fn accepts_mail(&self, domain: &str) -> Result<bool, anyhow::Error>;
+}
+
+/// Looks the domain up using the system DNS configuration.
+pub struct DnsMxResolver {
+    resolver: TokioAsyncResolver,
+}
+
+impl DnsMxResolver {
+    pub fn from_system_conf() -> Result<Self, anyhow::Error> {
+        Ok(Self {
+            resolver: TokioAsyncResolver::tokio_from_system_conf()?,
+        })
+    }
+}
+
+#[async_trait]
+impl MxResolver for DnsMxResolver {
+    async fn accepts_mail(&self, domain: &str) -> Result<bool, anyhow::Error> {
+        // The trailing dot stops the resolver from trying search domains.
+        let fqdn = format!("{}.", domain);
+        match self.resolver.mx_lookup(fqdn.as_str()).await {
+            // RFC 7505: a single `MX 0 .` record means the domain accepts no mail.
+            Ok(mx) => Ok(mx.iter().any(|record| !record.exchange().is_root())),
+            Err(e) if matches!(e.kind(), ResolveErrorKind::NoRecordsFound { .. }) => {
+                // RFC 5321: without MX records, mail goes to the A/AAAA host.
+                match self.resolver.lookup_ip(fqdn.as_str()).await {
+                    Ok(ips) => Ok(ips.iter().next().is_some()),
+                    Err(e) if matches!(e.kind(), ResolveErrorKind::NoRecordsFound { .. }) => {
+                        Ok(false)
+                    }
+                    Err(e) => Err(e.into()),
+                }
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Knows a fixed set of mail domains. Meant for local runs and tests.
+pub struct StubMxResolver {
+    domains: HashSet<String>,
+}
+
+impl StubMxResolver {
+    pub fn new<I, D>(domains: I) -> Self
+    where
+        I: IntoIterator<Item = D>,
+        D: Into<String>,
+    {
+        Self {
+            domains: domains.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+#[async_trait]
+impl MxResolver for StubMxResolver {
+    async fn accepts_mail(&self, domain: &str) -> Result<bool, anyhow::Error> {
+        Ok(self.domains.contains(domain))
+    }
+}
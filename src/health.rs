@@ -0,0 +1,162 @@
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use sqlx::PgPool;
+
+use crate::{
+    configuration::config::HealthConfiguration,
+    database::{db::Database, migrations::schema_status},
+    ses_workflow::SESWorkflow,
+};
+
+#[derive(serde::Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Ok,
+    /// Only non-critical dependencies are failing.
+    Degraded,
+    Unavailable,
+}
+
+#[derive(serde::Serialize, Debug)]
+pub struct CheckResult {
+    pub status: Status,
+    pub critical: bool,
+    pub latency_ms: u128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(serde::Serialize, Debug)]
+pub struct ReadinessReport {
+    pub status: Status,
+    pub checks: BTreeMap<&'static str, CheckResult>,
+}
+
+/// Checks everything the service needs before it can take traffic.
+pub struct ReadinessProbe {
+    db: Arc<Database>,
+    ses: Arc<SESWorkflow>,
+    timeout: Duration,
+    check_ses: bool,
+}
+
+impl ReadinessProbe {
+    pub fn new(
+        configuration: &HealthConfiguration,
+        db: Arc<Database>,
+        ses: Arc<SESWorkflow>,
+    ) -> Self {
+        Self {
+            db,
+            ses,
+            timeout: Duration::from_millis(configuration.check_timeout_milliseconds),
+            check_ses: configuration.check_ses,
+        }
+    }
+
+    pub async fn run(&self) -> ReadinessReport {
+        let mut checks = BTreeMap::new();
+        checks.insert(
+            "database",
+            self.check("database", true, ping_database(&self.db.pool))
+                .await,
+        );
+        checks.insert(
+            "migrations",
+            self.check("migrations", true, check_migrations(&self.db.pool))
+                .await,
+        );
+        if self.check_ses {
+            // Subscriptions are still stored while SES is unreachable, so don't pull the instance.
+            checks.insert(
+                "ses",
+                self.check("ses", false, self.ses.check_reachable()).await,
+            );
+        }
+
+        let status = if checks
+            .values()
+            .any(|check| check.critical && check.status != Status::Ok)
+        {
+            Status::Unavailable
+        } else if checks.values().any(|check| check.status != Status::Ok) {
+            Status::Degraded
+        } else {
+            Status::Ok
+        };
+
+        ReadinessReport { status, checks }
+    }
+
+    /// The report is public, so it only carries the outermost error message. The rest of
+    /// the chain is logged.
+    async fn check(
+        &self,
+        name: &'static str,
+        critical: bool,
+        check: impl Future<Output = Result<(), anyhow::Error>>,
+    ) -> CheckResult {
+        let started = Instant::now();
+        let outcome = match tokio::time::timeout(self.timeout, check).await {
+            Ok(outcome) => outcome,
+            Err(_) => Err(anyhow::anyhow!(
+                "Timed out after {}ms",
+                self.timeout.as_millis()
+            )),
+        };
+        if let Err(error) = &outcome {
+            tracing::warn!(check = name, error.cause_chain = ?error, "Readiness check failed");
+        }
+        let failed_status = if critical {
+            Status::Unavailable
+        } else {
+            Status::Degraded
+        };
+
+        CheckResult {
+            status: if outcome.is_ok() {
+                Status::Ok
+            } else {
+                failed_status
+            },
+            critical,
+            latency_ms: started.elapsed().as_millis(),
+            error: outcome.err().map(|e| e.to_string()),
+        }
+    }
+}
+
+async fn ping_database(pool: &PgPool) -> Result<(), anyhow::Error> {
+    sqlx::query("SELECT 1")
+        .execute(pool)
+        .await
+        .context("The database is unreachable")?;
+    Ok(())
+}
+
+async fn check_migrations(pool: &PgPool) -> Result<(), anyhow::Error> {
+    let status = schema_status(pool)
+        .await
+        .context("Failed to read the applied migrations")?;
+    if let Some(version) = status.dirty_version {
+        anyhow::bail!("Migration {} failed half-way", version);
+    }
+    let pending = status.pending();
+    if pending > 0 {
+        anyhow::bail!(
+            "Schema is at version {}, this build expects {} ({} migration(s) not applied)",
+            status
+                .current_version()
+                .map_or_else(|| "none".to_string(), |version| version.to_string()),
+            status.expected_version().unwrap_or_default(),
+            pending
+        );
+    }
+    Ok(())
+}
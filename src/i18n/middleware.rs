@@ -0,0 +1,58 @@
+use std::{cell::RefCell, sync::Arc};
+
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+
+use super::Localizer;
+
+tokio::task_local! {
+    static CURRENT: RequestLocale;
+}
+
+/// The language to answer the request being handled in.
+struct RequestLocale {
+    localizer: Arc<Localizer>,
+    locale: RefCell<String>,
+}
+
+/// Message `id` in the current request's language. Outside a request, e.g. in unit tests,
+/// there is nothing to negotiate with and `fallback` is used.
+pub fn localize(id: &str, args: &[(&str, &str)], fallback: impl FnOnce() -> String) -> String {
+    CURRENT
+        .try_with(|current| {
+            current
+                .localizer
+                .message(&current.locale.borrow(), id, args)
+        })
+        .unwrap_or_else(|_| fallback())
+}
+
+/// Answers the rest of the current request in `locale`, for handlers that are told the
+/// language in the request itself, e.g. a form field, rather than `Accept-Language`.
+pub fn set_locale(locale: &str) {
+    let _ = CURRENT.try_with(|current| current.locale.replace(locale.to_string()));
+}
+
+/// Negotiates the response language from `Accept-Language`, for error messages built
+/// where the headers are out of reach.
+pub async fn negotiate_locale(
+    State(localizer): State<Arc<Localizer>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let locale = localizer
+        .negotiate_request(None, request.headers())
+        .to_string();
+    CURRENT
+        .scope(
+            RequestLocale {
+                localizer,
+                locale: RefCell::new(locale),
+            },
+            next.run(request),
+        )
+        .await
+}
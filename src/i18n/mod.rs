@@ -0,0 +1,177 @@
+pub mod middleware;
+
+use axum::http::{header::ACCEPT_LANGUAGE, HeaderMap};
+use fluent_bundle::{concurrent::FluentBundle, FluentArgs, FluentResource};
+use unic_langid::LanguageIdentifier;
+
+// The first catalogue is the fallback for anything we can't match.
+const CATALOGUES: &[(&str, &str)] = &[
+    ("en", include_str!("../../locales/en.ftl")),
+    ("de", include_str!("../../locales/de.ftl")),
+    ("uk", include_str!("../../locales/uk.ftl")),
+];
+
+/// Fluent catalogues for every language we speak to subscribers in.
+pub struct Localizer {
+    bundles: Vec<(String, FluentBundle<FluentResource>)>,
+}
+
+impl Default for Localizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Localizer {
+    /// Panics if a bundled catalogue is broken, the unit tests make sure it isn't.
+    pub fn new() -> Self {
+        let bundles = CATALOGUES
+            .iter()
+            .map(|(tag, source)| {
+                let langid: LanguageIdentifier = tag.parse().expect("Invalid bundled locale tag");
+                let resource = FluentResource::try_new(source.to_string())
+                    .unwrap_or_else(|_| panic!("Failed to parse the {} catalogue", tag));
+                let mut bundle = FluentBundle::new_concurrent(vec![langid]);
+                // Unicode isolation marks would end up inside links.
+                bundle.set_use_isolating(false);
+                bundle
+                    .add_resource(resource)
+                    .unwrap_or_else(|_| panic!("Duplicate messages in the {} catalogue", tag));
+                (tag.to_string(), bundle)
+            })
+            .collect();
+        Self { bundles }
+    }
+
+    pub fn default_locale(&self) -> &str {
+        &self.bundles[0].0
+    }
+
+    /// Picks the first supported locale out of `preferences`, matching on
+    /// the primary language when there is no exact match (`de-AT` -> `de`).
+    pub fn negotiate<'a>(&self, preferences: impl IntoIterator<Item = &'a str>) -> &str {
+        for preference in preferences {
+            let Ok(requested) = preference.trim().parse::<LanguageIdentifier>() else {
+                continue;
+            };
+            if let Some((tag, _)) = self.bundles.iter().find(|(tag, _)| {
+                tag.parse::<LanguageIdentifier>()
+                    .is_ok_and(|supported| supported.language == requested.language)
+            }) {
+                return tag;
+            }
+        }
+        self.default_locale()
+    }
+
+    /// An explicit choice wins over whatever the browser advertises.
+    pub fn negotiate_request(&self, explicit: Option<&str>, headers: &HeaderMap) -> &str {
+        let accept_language = headers
+            .get(ACCEPT_LANGUAGE)
+            .and_then(|value| value.to_str().ok())
+            .map(parse_accept_language)
+            .unwrap_or_default();
+        self.negotiate(
+            explicit
+                .into_iter()
+                .chain(accept_language.iter().map(String::as_str)),
+        )
+    }
+
+    /// Falls back to the default locale, then to the message id itself.
+    pub fn message(&self, locale: &str, id: &str, args: &[(&str, &str)]) -> String {
+        let mut fluent_args = FluentArgs::new();
+        for (name, value) in args {
+            fluent_args.set(*name, *value);
+        }
+
+        let candidates = self
+            .bundles
+            .iter()
+            .filter(|(tag, _)| tag == locale)
+            .chain(self.bundles.iter().take(1));
+        for (_, bundle) in candidates {
+            if let Some(pattern) = bundle.get_message(id).and_then(|m| m.value()) {
+                let mut errors = vec![];
+                let value = bundle.format_pattern(pattern, Some(&fluent_args), &mut errors);
+                if !errors.is_empty() {
+                    tracing::warn!(?errors, id, locale, "Failed to format a localised message");
+                }
+                return value.into_owned();
+            }
+        }
+        tracing::warn!(id, locale, "Missing localised message");
+        id.to_string()
+    }
+}
+
+/// Language tags from an `Accept-Language` header, most preferred first.
+pub fn parse_accept_language(header: &str) -> Vec<String> {
+    let mut tags: Vec<(String, f32)> = header
+        .split(',')
+        .filter_map(|entry| {
+            let mut parts = entry.split(';').map(str::trim);
+            let tag = parts.next().filter(|tag| !tag.is_empty() && *tag != "*")?;
+            let quality = parts
+                .find_map(|param| param.strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.parse::<f32>().ok())?;
+            (quality > 0.0).then(|| (tag.to_string(), quality))
+        })
+        .collect();
+    // `sort_by` is stable, so equal weights keep the client's order.
+    tags.sort_by(|a, b| b.1.total_cmp(&a.1));
+    tags.into_iter().map(|(tag, _)| tag).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::{parse_accept_language, Localizer, CATALOGUES};
+
+    #[test]
+    fn every_catalogue_defines_the_same_messages() {
+        // Message definitions are the only lines starting at column 0.
+        let ids = |source: &str| -> HashSet<String> {
+            source
+                .lines()
+                .filter(|line| line.starts_with(|c: char| c.is_ascii_alphabetic()))
+                .filter_map(|line| line.split_once('=').map(|(id, _)| id.trim().to_string()))
+                .collect()
+        };
+        let reference = ids(CATALOGUES[0].1);
+        for (tag, source) in CATALOGUES {
+            assert_eq!(ids(source), reference, "{} is out of sync", tag);
+        }
+    }
+
+    #[test]
+    fn accept_language_is_ordered_by_quality() {
+        assert_eq!(
+            parse_accept_language("en;q=0.5, uk, de-AT;q=0.8, fr;q=0"),
+            vec!["uk", "de-AT", "en"]
+        );
+    }
+
+    #[test]
+    fn negotiation_matches_on_the_primary_language() {
+        let localizer = Localizer::new();
+        assert_eq!(localizer.negotiate(["fr", "de-AT", "uk"]), "de");
+    }
+
+    #[test]
+    fn unknown_languages_fall_back_to_english() {
+        let localizer = Localizer::new();
+        assert_eq!(localizer.negotiate(["fr", "not a tag"]), "en");
+    }
+
+    #[test]
+    fn messages_are_interpolated_without_isolation_marks() {
+        let localizer = Localizer::new();
+        let text = localizer.message("de", "confirmation-text", &[("link", "https://x.y/z")]);
+        assert_eq!(
+            text,
+            "Willkommen bei unserem Newsletter!\nBesuche https://x.y/z, um dein Abonnement zu bestätigen."
+        );
+    }
+}
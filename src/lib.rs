@@ -1,11 +1,37 @@
+pub mod admin;
+
+pub mod bot_protection;
+
+pub mod cli;
+
 pub mod configuration;
 
 pub mod database;
 
 pub mod domain;
 
+pub mod email_validation;
+
+pub mod health;
+
+pub mod i18n;
+
+pub mod metrics;
+
+pub mod pending_cleanup;
+
+pub mod reload;
+
 pub mod ses_workflow;
 
+pub mod shutdown;
+
+pub mod signed_links;
+
+pub mod rate_limit;
+
+pub mod request_id;
+
 pub mod routes;
 
 pub mod telemetry;
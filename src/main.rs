@@ -1,35 +1,179 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
+use clap::Parser;
 use newsletter::{
-    configuration::config::get_configuration,
-    database::db::Database,
-    routes::router::router,
+    admin,
+    bot_protection::BotProtection,
+    cli::{Cli, Command},
+    configuration::{
+        aws_credentials::StaticCredentials,
+        config::{effective_configuration, get_configuration, Configuration, RateLimitBackend},
+    },
+    database::{
+        db::Database, migrations::migrate_up, subscribers::postgres::PostgresSubscriberRepository,
+    },
+    email_validation::{
+        mx::{DnsMxResolver, MxResolver},
+        EmailValidator,
+    },
+    health::ReadinessProbe,
+    i18n::Localizer,
+    metrics::Metrics,
+    pending_cleanup::PendingCleanup,
+    rate_limit::{
+        memory::InMemoryRateLimiter, middleware::RateLimits, postgres::PostgresRateLimiter,
+        RateLimitStore,
+    },
+    reload::CredentialReloader,
+    routes::{confirmation_page::ConfirmationPages, router::router},
     ses_workflow::SESWorkflow,
+    shutdown::Shutdown,
+    signed_links::LinkSigner,
     startup::{configure_sdk_config, create_aws_client, init_logging, start_server},
     state::AppState,
 };
 
+// The requests and workers that held connections are gone by then, this only covers a
+// Postgres that stopped answering.
+const POOL_CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
 #[tokio::main]
 async fn main() -> Result<(), anyhow::Error> {
-    let configuration = get_configuration().expect("Failed to read configuration.");
+    let cli = Cli::parse();
+    if cli.print_config {
+        let effective = effective_configuration()?;
+        println!("{}", serde_json::to_string_pretty(&effective)?);
+        return Ok(());
+    }
+    let configuration = match get_configuration() {
+        Ok(configuration) => configuration,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    if cli.check_config {
+        println!("Configuration is valid.");
+        return Ok(());
+    }
+
+    let (tracer_provider, outcome) = match cli.command.unwrap_or(Command::Serve) {
+        Command::Serve => {
+            let tracer_provider = init_logging(&configuration, std::io::stdout)?;
+            (tracer_provider, serve(configuration).await)
+        }
+        command => {
+            // Keep stdout for the command's own output.
+            let tracer_provider = init_logging(&configuration, std::io::stderr)?;
+            (tracer_provider, admin::run(&configuration, command).await)
+        }
+    };
+
+    if let Some(provider) = tracer_provider {
+        provider.shutdown()?;
+    }
+    outcome
+}
 
-    init_logging(&configuration)?;
+async fn serve(configuration: Configuration) -> Result<(), anyhow::Error> {
+    let db = Arc::new(Database::new(&configuration.database));
+    if configuration.database.auto_migrate {
+        migrate_up(&db.pool).await?;
+    }
 
-    let sdk_config = configure_sdk_config(&configuration)?;
+    let static_credentials = StaticCredentials::from_configuration(&configuration.aws);
+    let sdk_config = configure_sdk_config(&configuration, static_credentials.clone()).await?;
     let aws_client = create_aws_client(&sdk_config)?;
 
     let ses = Arc::new(SESWorkflow::new(
         aws_client,
         configuration.aws.verified_email.clone(),
     ));
-    let db = Arc::new(Database::new(configuration.database.with_db()).await?);
     let base_url = Arc::new(configuration.application.base_url.clone());
 
-    let state = AppState::new(db, ses);
+    let rate_limit_store: Arc<dyn RateLimitStore> = match configuration.rate_limit.backend {
+        RateLimitBackend::Memory => Arc::new(InMemoryRateLimiter::new()),
+        RateLimitBackend::Postgres => Arc::new(PostgresRateLimiter::new(db.pool.clone())),
+    };
+    let bot_protection = Arc::new(BotProtection::new(
+        &configuration.bot_protection,
+        rate_limit_store.clone(),
+        None,
+    ));
+    let rate_limits = RateLimits::new(&configuration.rate_limit, rate_limit_store);
 
-    let app = router(state, base_url);
+    let mx_resolver: Option<Arc<dyn MxResolver>> = if configuration.email_validation.check_mx {
+        Some(Arc::new(DnsMxResolver::from_system_conf()?))
+    } else {
+        None
+    };
+    let email_validator = Arc::new(EmailValidator::new(
+        &configuration.email_validation,
+        mx_resolver,
+    ));
 
-    start_server(&configuration, app).await?;
+    let localizer = Arc::new(Localizer::new());
+    let metrics = Arc::new(Metrics::new());
+    let shutdown = Shutdown::new();
+    if configuration.application.reload_on_sighup {
+        let reloader = CredentialReloader::new(db.pool.clone(), static_credentials);
+        shutdown.spawn_worker("credential-reload", |shutdown| reloader.run(shutdown));
+    }
+    let readiness = Arc::new(ReadinessProbe::new(
+        &configuration.health,
+        db.clone(),
+        ses.clone(),
+    ));
+
+    let subscribers = Arc::new(PostgresSubscriberRepository::new(db.pool.clone()));
+    let links = Arc::new(LinkSigner::new(&configuration.signed_links));
+    if configuration.pending_cleanup.enabled {
+        let cleanup = PendingCleanup::new(
+            &configuration.pending_cleanup,
+            subscribers.clone(),
+            links.clone(),
+            ses.clone(),
+            localizer.clone(),
+            metrics.clone(),
+            configuration.application.base_url.clone(),
+        );
+        shutdown.spawn_worker("pending-cleanup", |shutdown| cleanup.run(shutdown));
+    }
+
+    let pages = Arc::new(ConfirmationPages::new(
+        localizer.clone(),
+        configuration.application.confirmed_redirect_url.clone(),
+    ));
+
+    let state = AppState::new(
+        db.clone(),
+        subscribers,
+        ses,
+        bot_protection,
+        rate_limits,
+        email_validator,
+        localizer,
+        metrics.clone(),
+        readiness,
+        links,
+        pages,
+    );
+
+    let app = router(state, base_url);
 
+    let summary = start_server(&configuration, app, shutdown, metrics).await?;
+    if tokio::time::timeout(POOL_CLOSE_TIMEOUT, db.pool.close())
+        .await
+        .is_err()
+    {
+        tracing::warn!("Gave up closing the database connections");
+    }
+    tracing::info!(
+        requests_cut_off = summary.requests_cut_off,
+        deliveries_not_sent = summary.deliveries_not_sent,
+        workers_aborted = ?summary.workers_aborted,
+        "Shutdown complete: {}",
+        summary
+    );
     Ok(())
 }
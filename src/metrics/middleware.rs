@@ -0,0 +1,37 @@
+use std::{sync::Arc, time::Instant};
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    middleware::Next,
+    response::Response,
+};
+
+use super::Metrics;
+
+/// Counts and times every request under its route template, e.g.
+/// `/subscriptions/confirm` rather than the full URI with its query.
+pub async fn track_http_metrics(
+    State(metrics): State<Arc<Metrics>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    // Unknown paths share one label, otherwise scanners would blow up cardinality.
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or_else(|| "unmatched".to_string(), |path| path.as_str().to_string());
+    let method = request.method().to_string();
+
+    let started = Instant::now();
+    let in_flight = metrics.request_started();
+    let response = next.run(request).await;
+    drop(in_flight);
+
+    metrics.record_http(
+        &method,
+        &route,
+        response.status().as_u16(),
+        started.elapsed(),
+    );
+    response
+}
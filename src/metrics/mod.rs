@@ -0,0 +1,356 @@
+pub mod middleware;
+
+use std::time::Duration;
+
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts,
+    Registry, TextEncoder,
+};
+use sqlx::PgPool;
+
+use crate::ses_workflow::Throttled;
+
+#[derive(Debug, Clone, Copy)]
+pub enum EmailKind {
+    Confirmation,
+    Reminder,
+    Newsletter,
+}
+
+impl EmailKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Confirmation => "confirmation",
+            Self::Reminder => "reminder",
+            Self::Newsletter => "newsletter",
+        }
+    }
+}
+
+/// Everything exposed on `/metrics`.
+///
+/// Each instance owns its registry, so test apps don't share counters.
+pub struct Metrics {
+    registry: Registry,
+    http_requests: IntCounterVec,
+    http_request_duration: HistogramVec,
+    http_requests_in_flight: IntGauge,
+    emails: IntCounterVec,
+    pending_deliveries: IntGauge,
+    invalid_subscribers: IntCounter,
+    link_clicks: IntCounter,
+    pending_reminded: IntCounter,
+    pending_purged: IntCounter,
+    db_pool_connections: IntGaugeVec,
+    db_pool_max_connections: IntGauge,
+    subscribers: IntGaugeVec,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metrics {
+    /// Panics on duplicate registrations, which would be a bug in this constructor.
+    pub fn new() -> Self {
+        let registry = Registry::new();
+
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests handled"),
+            &["method", "route", "status"],
+        )
+        .unwrap();
+        let http_request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "Time spent handling HTTP requests",
+            ),
+            &["method", "route"],
+        )
+        .unwrap();
+        let http_requests_in_flight = IntGauge::new(
+            "http_requests_in_flight",
+            "HTTP requests being handled right now",
+        )
+        .unwrap();
+        let emails = IntCounterVec::new(
+            Opts::new("emails_total", "Emails handed over to SES"),
+            &["kind", "outcome"],
+        )
+        .unwrap();
+        let pending_deliveries = IntGauge::new(
+            "email_deliveries_pending",
+            "Newsletter emails queued but not sent yet",
+        )
+        .unwrap();
+        let invalid_subscribers = IntCounter::new(
+            "newsletter_invalid_subscribers_total",
+            "Confirmed subscribers skipped because their stored email is invalid",
+        )
+        .unwrap();
+        let link_clicks = IntCounter::new(
+            "newsletter_link_clicks_total",
+            "Tracked links in newsletter issues followed by subscribers",
+        )
+        .unwrap();
+        let pending_reminded = IntCounter::new(
+            "pending_subscribers_reminded_total",
+            "Pending subscribers reminded to confirm by the cleanup job",
+        )
+        .unwrap();
+        let pending_purged = IntCounter::new(
+            "pending_subscribers_purged_total",
+            "Pending subscribers deleted by the cleanup job",
+        )
+        .unwrap();
+        let db_pool_connections = IntGaugeVec::new(
+            Opts::new("db_pool_connections", "Open Postgres connections"),
+            &["state"],
+        )
+        .unwrap();
+        let db_pool_max_connections = IntGauge::new(
+            "db_pool_max_connections",
+            "Upper bound of the Postgres pool",
+        )
+        .unwrap();
+        let subscribers = IntGaugeVec::new(
+            Opts::new("subscribers", "Subscribers by status"),
+            &["status"],
+        )
+        .unwrap();
+
+        registry.register(Box::new(http_requests.clone())).unwrap();
+        registry
+            .register(Box::new(http_request_duration.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(http_requests_in_flight.clone()))
+            .unwrap();
+        registry.register(Box::new(emails.clone())).unwrap();
+        registry
+            .register(Box::new(pending_deliveries.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(invalid_subscribers.clone()))
+            .unwrap();
+        registry.register(Box::new(link_clicks.clone())).unwrap();
+        registry
+            .register(Box::new(pending_reminded.clone()))
+            .unwrap();
+        registry.register(Box::new(pending_purged.clone())).unwrap();
+        registry
+            .register(Box::new(db_pool_connections.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(db_pool_max_connections.clone()))
+            .unwrap();
+        registry.register(Box::new(subscribers.clone())).unwrap();
+
+        Self {
+            registry,
+            http_requests,
+            http_request_duration,
+            http_requests_in_flight,
+            emails,
+            pending_deliveries,
+            invalid_subscribers,
+            link_clicks,
+            pending_reminded,
+            pending_purged,
+            db_pool_connections,
+            db_pool_max_connections,
+            subscribers,
+        }
+    }
+
+    pub fn record_http(&self, method: &str, route: &str, status: u16, elapsed: Duration) {
+        self.http_requests
+            .with_label_values(&[method, route, &status.to_string()])
+            .inc();
+        self.http_request_duration
+            .with_label_values(&[method, route])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    /// Counts a request as in flight until the returned guard drops.
+    pub fn request_started(&self) -> InFlightRequest<'_> {
+        self.http_requests_in_flight.inc();
+        InFlightRequest {
+            gauge: &self.http_requests_in_flight,
+        }
+    }
+
+    pub fn requests_in_flight(&self) -> i64 {
+        self.http_requests_in_flight.get()
+    }
+
+    pub fn deliveries_pending(&self) -> i64 {
+        self.pending_deliveries.get()
+    }
+
+    pub fn record_email<T>(&self, kind: EmailKind, result: &Result<T, anyhow::Error>) {
+        let outcome = match result {
+            Ok(_) => "sent",
+            Err(e) if e.downcast_ref::<Throttled>().is_some() => "throttled",
+            Err(_) => "failed",
+        };
+        self.emails
+            .with_label_values(&[kind.as_str(), outcome])
+            .inc();
+    }
+
+    pub fn record_invalid_subscriber(&self) {
+        self.invalid_subscribers.inc();
+    }
+
+    pub fn record_link_click(&self) {
+        self.link_clicks.inc();
+    }
+
+    pub fn record_pending_cleanup(&self, reminded: u64, purged: u64) {
+        self.pending_reminded.inc_by(reminded);
+        self.pending_purged.inc_by(purged);
+    }
+
+    /// Tracks a batch of deliveries, whatever is left over when the
+    /// returned guard drops is taken off the gauge.
+    pub fn enqueue_deliveries(&self, count: usize) -> PendingDeliveries<'_> {
+        let remaining = count as i64;
+        self.pending_deliveries.add(remaining);
+        PendingDeliveries {
+            gauge: &self.pending_deliveries,
+            remaining,
+        }
+    }
+
+    /// Refreshes the gauges that are sampled on scrape and encodes everything.
+    pub async fn render(&self, pool: &PgPool) -> Result<String, anyhow::Error> {
+        let open = pool.size() as i64;
+        let idle = pool.num_idle() as i64;
+        self.db_pool_connections
+            .with_label_values(&["idle"])
+            .set(idle);
+        self.db_pool_connections
+            .with_label_values(&["in_use"])
+            .set(open - idle);
+        self.db_pool_max_connections
+            .set(pool.options().get_max_connections() as i64);
+
+        let counts = sqlx::query!(
+            r#"SELECT status, COUNT(*) AS "count!" FROM subscriptions GROUP BY status"#
+        )
+        .fetch_all(pool)
+        .await;
+        // Statuses nobody is in any more should read 0, not their last value. Without
+        // counts the gauge is left out rather than failing the whole scrape.
+        self.subscribers.reset();
+        match counts {
+            Ok(counts) => {
+                for row in counts {
+                    self.subscribers
+                        .with_label_values(&[&row.status])
+                        .set(row.count);
+                }
+            }
+            Err(e) => tracing::error!("Failed to count subscribers: {:?}", e),
+        }
+
+        let mut buffer = vec![];
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        Ok(String::from_utf8(buffer)?)
+    }
+}
+
+pub struct InFlightRequest<'a> {
+    gauge: &'a IntGauge,
+}
+
+impl Drop for InFlightRequest<'_> {
+    fn drop(&mut self) {
+        self.gauge.dec();
+    }
+}
+
+pub struct PendingDeliveries<'a> {
+    gauge: &'a IntGauge,
+    remaining: i64,
+}
+
+impl PendingDeliveries<'_> {
+    pub fn done(&mut self) {
+        if self.remaining > 0 {
+            self.remaining -= 1;
+            self.gauge.dec();
+        }
+    }
+}
+
+impl Drop for PendingDeliveries<'_> {
+    fn drop(&mut self) {
+        self.gauge.sub(self.remaining);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{EmailKind, Metrics};
+    use crate::ses_workflow::Throttled;
+
+    fn encode(metrics: &Metrics) -> String {
+        use prometheus::Encoder;
+
+        let mut buffer = vec![];
+        prometheus::TextEncoder::new()
+            .encode(&metrics.registry.gather(), &mut buffer)
+            .unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+
+    #[test]
+    fn throttled_sends_are_counted_apart_from_failures() {
+        let metrics = Metrics::new();
+
+        metrics.record_email(EmailKind::Newsletter, &Ok(()));
+        metrics.record_email::<()>(
+            EmailKind::Newsletter,
+            &Err(anyhow::anyhow!("quota").context(Throttled)),
+        );
+        metrics.record_email::<()>(EmailKind::Confirmation, &Err(anyhow::anyhow!("boom")));
+
+        let output = encode(&metrics);
+        assert!(output.contains(r#"emails_total{kind="newsletter",outcome="sent"} 1"#));
+        assert!(output.contains(r#"emails_total{kind="newsletter",outcome="throttled"} 1"#));
+        assert!(output.contains(r#"emails_total{kind="confirmation",outcome="failed"} 1"#));
+    }
+
+    #[test]
+    fn dropping_a_batch_clears_what_was_not_delivered() {
+        let metrics = Metrics::new();
+
+        {
+            let mut batch = metrics.enqueue_deliveries(3);
+            batch.done();
+            assert_eq!(metrics.pending_deliveries.get(), 2);
+        }
+
+        assert_eq!(metrics.pending_deliveries.get(), 0);
+    }
+
+    #[test]
+    fn requests_are_labelled_by_route() {
+        let metrics = Metrics::new();
+
+        metrics.record_http("GET", "/health_check", 200, Duration::from_millis(3));
+
+        let output = encode(&metrics);
+        assert!(output
+            .contains(r#"http_requests_total{method="GET",route="/health_check",status="200"} 1"#));
+        assert!(output.contains(
+            r#"http_request_duration_seconds_count{method="GET",route="/health_check"} 1"#
+        ));
+    }
+}
@@ -0,0 +1,165 @@
+use std::{sync::Arc, time::Duration};
+
+use sqlx::types::chrono::{DateTime, Utc};
+
+use crate::{
+    configuration::config::PendingCleanupConfiguration,
+    database::subscribers::SubscriberRepository,
+    i18n::Localizer,
+    metrics::{EmailKind, Metrics},
+    routes::subscriptions::{reissue_confirmation, send_link_email},
+    ses_workflow::SESWorkflow,
+    shutdown::Shutdown,
+    signed_links::LinkSigner,
+};
+
+// Whoever is left waits for the next run.
+const REMINDER_BATCH_SIZE: usize = 500;
+
+/// What one run of the job did.
+#[derive(Debug, Default, PartialEq)]
+pub struct CleanupReport {
+    pub reminded: u64,
+    pub purged: u64,
+    pub failed: u64,
+}
+
+impl std::fmt::Display for CleanupReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "reminded {} pending subscriber(s), purged {}",
+            self.reminded, self.purged
+        )?;
+        if self.failed > 0 {
+            write!(f, ", {} reminder(s) failed", self.failed)?;
+        }
+        Ok(())
+    }
+}
+
+/// Sends subscribers who never confirmed one reminder, then deletes them once the
+/// retention period is over.
+pub struct PendingCleanup {
+    subscribers: Arc<dyn SubscriberRepository>,
+    links: Arc<LinkSigner>,
+    ses: Arc<SESWorkflow>,
+    localizer: Arc<Localizer>,
+    metrics: Arc<Metrics>,
+    base_url: String,
+    interval: Duration,
+    remind_after: Duration,
+    purge_after: Duration,
+}
+
+impl PendingCleanup {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        configuration: &PendingCleanupConfiguration,
+        subscribers: Arc<dyn SubscriberRepository>,
+        links: Arc<LinkSigner>,
+        ses: Arc<SESWorkflow>,
+        localizer: Arc<Localizer>,
+        metrics: Arc<Metrics>,
+        base_url: String,
+    ) -> Self {
+        Self {
+            subscribers,
+            links,
+            ses,
+            localizer,
+            metrics,
+            base_url,
+            interval: Duration::from_secs(configuration.interval_seconds),
+            remind_after: Duration::from_secs(configuration.remind_after_seconds),
+            purge_after: Duration::from_secs(configuration.purge_after_seconds),
+        }
+    }
+
+    pub async fn run_once(&self) -> Result<CleanupReport, anyhow::Error> {
+        self.run_at(Utc::now()).await
+    }
+
+    /// Purges first, so nobody is reminded right before being deleted.
+    #[tracing::instrument(name = "Clean up pending subscribers", skip(self))]
+    pub async fn run_at(&self, now: DateTime<Utc>) -> Result<CleanupReport, anyhow::Error> {
+        let mut report = CleanupReport {
+            purged: self
+                .subscribers
+                .purge_pending(now - self.purge_after)
+                .await?,
+            ..Default::default()
+        };
+
+        let to_remind = self
+            .subscribers
+            .pending_to_remind(now - self.remind_after, REMINDER_BATCH_SIZE)
+            .await?;
+        for subscriber in to_remind {
+            if !self.subscribers.claim_reminder(subscriber.id).await? {
+                continue;
+            }
+            let sent = async {
+                let link = reissue_confirmation(
+                    self.subscribers.as_ref(),
+                    &self.links,
+                    &self.base_url,
+                    subscriber.id,
+                )
+                .await?;
+                send_link_email(
+                    &self.ses,
+                    &self.localizer,
+                    &subscriber.locale,
+                    subscriber.email,
+                    "reminder",
+                    &link,
+                )
+                .await
+            }
+            .await;
+            self.metrics.record_email(EmailKind::Reminder, &sent);
+            match sent {
+                Ok(()) => report.reminded += 1,
+                Err(error) => {
+                    // Claimed already, so they won't be retried: one missed reminder is
+                    // better than reminding twice.
+                    tracing::error!(
+                        error.cause_chain = ?error,
+                        subscriber_id = %subscriber.id,
+                        "Failed to send a reminder"
+                    );
+                    report.failed += 1;
+                }
+            }
+        }
+
+        self.metrics
+            .record_pending_cleanup(report.reminded, report.purged);
+        Ok(report)
+    }
+
+    /// Runs every `interval_seconds` until shutdown.
+    pub async fn run(self, shutdown: Shutdown) {
+        let mut interval = tokio::time::interval(self.interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = shutdown.triggered() => return,
+            }
+            match self.run_once().await {
+                Ok(report) => tracing::info!(
+                    reminded = report.reminded,
+                    purged = report.purged,
+                    failed = report.failed,
+                    "Cleaned up pending subscribers"
+                ),
+                Err(error) => tracing::error!(
+                    error.cause_chain = ?error,
+                    "Failed to clean up pending subscribers"
+                ),
+            }
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+
+use super::{Quota, RateLimitStore};
+
+// Stop the map from growing without bound when many distinct keys hit us.
+const PRUNE_THRESHOLD: usize = 10_000;
+
+struct Window {
+    started_at: Instant,
+    window: Duration,
+    hits: u32,
+}
+
+/// Fixed-window counters kept in process memory.
+///
+/// Every key gets `quota.limit` hits per `quota.window`; the counter resets
+/// once the window has elapsed.
+#[derive(Default)]
+pub struct InMemoryRateLimiter {
+    windows: Mutex<HashMap<String, Window>>,
+}
+
+impl InMemoryRateLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a hit for `key`.
+    /// Returns how long the caller has to wait if the quota is exhausted.
+    pub fn check(&self, key: &str, quota: Quota) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut windows = self.windows.lock().expect("Rate limiter lock poisoned");
+
+        if windows.len() > PRUNE_THRESHOLD {
+            windows.retain(|_, w| now.duration_since(w.started_at) < w.window);
+        }
+
+        let window = windows.entry(key.to_string()).or_insert(Window {
+            started_at: now,
+            window: quota.window,
+            hits: 0,
+        });
+        if now.duration_since(window.started_at) >= quota.window {
+            window.started_at = now;
+            window.hits = 0;
+        }
+        window.window = quota.window;
+
+        if window.hits >= quota.limit {
+            return Err(quota.window - now.duration_since(window.started_at));
+        }
+        window.hits += 1;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for InMemoryRateLimiter {
+    async fn hit(&self, key: &str, quota: Quota) -> Result<Result<(), Duration>, anyhow::Error> {
+        Ok(self.check(key, quota))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use claim::{assert_err, assert_ok};
+
+    use super::InMemoryRateLimiter;
+    use crate::rate_limit::Quota;
+
+    fn quota(limit: u32) -> Quota {
+        Quota::new(limit, Duration::from_secs(60))
+    }
+
+    #[test]
+    fn hits_within_the_limit_are_allowed() {
+        let limiter = InMemoryRateLimiter::new();
+        assert_ok!(limiter.check("a", quota(2)));
+        assert_ok!(limiter.check("a", quota(2)));
+    }
+
+    #[test]
+    fn hits_over_the_limit_are_rejected_with_a_retry_delay() {
+        let limiter = InMemoryRateLimiter::new();
+        assert_ok!(limiter.check("a", quota(1)));
+        let retry_after = limiter.check("a", quota(1)).unwrap_err();
+        assert!(retry_after <= Duration::from_secs(60));
+    }
+
+    #[test]
+    fn keys_are_counted_separately() {
+        let limiter = InMemoryRateLimiter::new();
+        assert_ok!(limiter.check("a", quota(1)));
+        assert_ok!(limiter.check("b", quota(1)));
+        assert_err!(limiter.check("a", quota(1)));
+    }
+
+    #[test]
+    fn the_counter_resets_after_the_window() {
+        let limiter = InMemoryRateLimiter::new();
+        let quota = Quota::new(1, Duration::from_millis(10));
+        assert_ok!(limiter.check("a", quota));
+        std::thread::sleep(Duration::from_millis(20));
+        assert_ok!(limiter.check("a", quota));
+    }
+}
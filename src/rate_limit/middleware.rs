@@ -0,0 +1,143 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::{
+    extract::{Request, State},
+    http::{header::RETRY_AFTER, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+
+use super::{Cooldown, Quota, RateLimitStore};
+use crate::{
+    configuration::config::{RateLimitConfiguration, RouteRateLimitConfiguration},
+    i18n::middleware::localize,
+    request_id::RequestId,
+    routes::client_ip::{ClientIp, TrustedProxies},
+};
+
+/// Quota enforced on a single route, per client IP.
+#[derive(Clone)]
+pub struct RouteRateLimit {
+    scope: &'static str,
+    quota: Quota,
+    store: Arc<dyn RateLimitStore>,
+}
+
+impl RouteRateLimit {
+    pub fn new(
+        scope: &'static str,
+        configuration: &RouteRateLimitConfiguration,
+        store: Arc<dyn RateLimitStore>,
+    ) -> Self {
+        Self {
+            scope,
+            quota: Quota::new(
+                configuration.limit,
+                Duration::from_secs(configuration.window_seconds),
+            ),
+            store,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimits {
+    pub trusted_proxies: Arc<TrustedProxies>,
+    pub subscriptions: RouteRateLimit,
+    pub subscriptions_confirm: RouteRateLimit,
+    pub resend_confirmation: RouteRateLimit,
+    /// Per address, on top of the per-client `resend_confirmation` limit.
+    pub resend_cooldown: Cooldown,
+    pub newsletters: RouteRateLimit,
+}
+
+impl RateLimits {
+    pub fn new(configuration: &RateLimitConfiguration, store: Arc<dyn RateLimitStore>) -> Self {
+        Self {
+            trusted_proxies: Arc::new(TrustedProxies::new(configuration.trusted_proxies.clone())),
+            subscriptions: RouteRateLimit::new(
+                "subscriptions",
+                &configuration.subscriptions,
+                store.clone(),
+            ),
+            subscriptions_confirm: RouteRateLimit::new(
+                "subscriptions_confirm",
+                &configuration.subscriptions_confirm,
+                store.clone(),
+            ),
+            resend_confirmation: RouteRateLimit::new(
+                "resend_confirmation",
+                &configuration.resend_confirmation,
+                store.clone(),
+            ),
+            resend_cooldown: Cooldown::new(
+                "resend_cooldown",
+                Duration::from_secs(configuration.resend_cooldown_seconds),
+                store.clone(),
+            ),
+            newsletters: RouteRateLimit::new("newsletters", &configuration.newsletters, store),
+        }
+    }
+}
+
+/// Meant to be mounted with `axum::middleware::from_fn_with_state` on a single route.
+pub async fn rate_limit(
+    State(limit): State<RouteRateLimit>,
+    ClientIp(client_ip): ClientIp,
+    request: Request,
+    next: Next,
+) -> Response {
+    // Requests whose origin is unknown are not counted.
+    let Some(key) = client_ip.map(|ip| format!("ip:{}", ip)) else {
+        return next.run(request).await;
+    };
+
+    match limit
+        .store
+        .hit(&format!("{}:{}", limit.scope, key), limit.quota)
+        .await
+    {
+        Ok(Ok(())) => next.run(request).await,
+        Ok(Err(retry_after)) => {
+            tracing::warn!(scope = limit.scope, %key, "Request rate limited");
+            too_many_requests(retry_after)
+        }
+        Err(error) => {
+            // Fail open, a broken counter store should not take the whole API down.
+            tracing::error!(
+                error.cause_chain = ?error,
+                "Failed to record a rate limited hit, letting the request through"
+            );
+            next.run(request).await
+        }
+    }
+}
+
+fn too_many_requests(retry_after: Duration) -> Response {
+    #[derive(serde::Serialize)]
+    struct Error {
+        message: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        request_id: Option<RequestId>,
+    }
+
+    // Round up so clients never retry a moment too early.
+    let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+    let mut response = (
+        StatusCode::TOO_MANY_REQUESTS,
+        Json(Error {
+            message: localize(
+                "error-rate-limited",
+                &[("seconds", &seconds.to_string())],
+                || format!("Too many requests, retry in {} seconds.", seconds),
+            ),
+            request_id: RequestId::current(),
+        }),
+    )
+        .into_response();
+    response
+        .headers_mut()
+        .insert(RETRY_AFTER, HeaderValue::from(seconds.max(1)));
+    response
+}
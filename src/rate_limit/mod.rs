@@ -0,0 +1,63 @@
+pub mod memory;
+pub mod middleware;
+pub mod postgres;
+
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use sha2::{Digest, Sha256};
+
+/// How many hits a key is allowed within a window.
+#[derive(Debug, Clone, Copy)]
+pub struct Quota {
+    pub limit: u32,
+    pub window: Duration,
+}
+
+impl Quota {
+    pub fn new(limit: u32, window: Duration) -> Self {
+        Self { limit, window }
+    }
+}
+
+/// Where the hit counters live.
+///
+/// The in-memory store is enough for a single instance, deployments running
+/// several replicas should share counters through Postgres.
+#[async_trait]
+pub trait RateLimitStore: Send + Sync {
+    /// Records a hit for `key`.
+    /// The inner `Err` holds how long the caller has to wait if the quota is exhausted.
+    async fn hit(&self, key: &str, quota: Quota) -> Result<Result<(), Duration>, anyhow::Error>;
+}
+
+/// One hit per key per `period`. Unlike route limits it never answers with a `429`, callers
+/// quietly skip the work instead, e.g. not to reveal whether an address is subscribed.
+#[derive(Clone)]
+pub struct Cooldown {
+    scope: &'static str,
+    quota: Quota,
+    store: Arc<dyn RateLimitStore>,
+}
+
+impl Cooldown {
+    pub fn new(scope: &'static str, period: Duration, store: Arc<dyn RateLimitStore>) -> Self {
+        Self {
+            scope,
+            quota: Quota::new(1, period),
+            store,
+        }
+    }
+
+    /// `false` while `key` is cooling down, otherwise starts a new cooldown.
+    ///
+    /// Keys are typically email addresses, the store only ever sees their SHA-256.
+    pub async fn start(&self, key: &str) -> Result<bool, anyhow::Error> {
+        let key = hex::encode(Sha256::digest(key.as_bytes()));
+        let hit = self
+            .store
+            .hit(&format!("{}:{}", self.scope, key), self.quota)
+            .await?;
+        Ok(hit.is_ok())
+    }
+}
@@ -0,0 +1,91 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use sqlx::PgPool;
+
+use super::{Quota, RateLimitStore};
+
+// Every instance deletes the expired windows on its first hit and then once per this many.
+const PRUNE_EVERY: u64 = 1_000;
+
+/// Fixed-window counters shared by every instance through the `rate_limits` table.
+pub struct PostgresRateLimiter {
+    pool: PgPool,
+    hits: AtomicU64,
+}
+
+impl PostgresRateLimiter {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            hits: AtomicU64::new(0),
+        }
+    }
+
+    /// Deletes the rows whose window is over, they would start afresh on their next hit anyway.
+    #[tracing::instrument(name = "Prune expired rate limits", skip(self))]
+    pub async fn prune_expired(&self) -> Result<u64, anyhow::Error> {
+        let deleted = sqlx::query!(r#"DELETE FROM rate_limits WHERE expires_at <= now()"#)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+        Ok(deleted)
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for PostgresRateLimiter {
+    #[tracing::instrument(name = "Record a rate limited hit", skip(self))]
+    async fn hit(&self, key: &str, quota: Quota) -> Result<Result<(), Duration>, anyhow::Error> {
+        if self
+            .hits
+            .fetch_add(1, Ordering::Relaxed)
+            .is_multiple_of(PRUNE_EVERY)
+        {
+            // The hit itself doesn't depend on it.
+            if let Err(error) = self.prune_expired().await {
+                tracing::error!(error.cause_chain = ?error, "Failed to prune expired rate limits");
+            }
+        }
+
+        // A single upsert keeps concurrent instances from racing each other.
+        let row = sqlx::query!(
+            r#"
+              INSERT INTO rate_limits (key, window_started_at, hits, expires_at)
+              VALUES ($1, now(), 1, now() + $2::float8 * INTERVAL '1 second')
+              ON CONFLICT (key) DO UPDATE SET
+                hits = CASE
+                  WHEN rate_limits.window_started_at + $2::float8 * INTERVAL '1 second' <= now() THEN 1
+                  ELSE rate_limits.hits + 1
+                END,
+                window_started_at = CASE
+                  WHEN rate_limits.window_started_at + $2::float8 * INTERVAL '1 second' <= now() THEN now()
+                  ELSE rate_limits.window_started_at
+                END,
+                expires_at = CASE
+                  WHEN rate_limits.window_started_at + $2::float8 * INTERVAL '1 second' <= now()
+                    THEN now() + $2::float8 * INTERVAL '1 second'
+                  ELSE rate_limits.window_started_at + $2::float8 * INTERVAL '1 second'
+                END
+              RETURNING
+                hits,
+                EXTRACT(EPOCH FROM
+                  window_started_at + $2::float8 * INTERVAL '1 second' - now()
+                )::float8 AS "seconds_left!"
+            "#,
+            key,
+            quota.window.as_secs_f64(),
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        if row.hits as u32 > quota.limit {
+            Ok(Err(Duration::from_secs_f64(row.seconds_left.max(0.0))))
+        } else {
+            Ok(Ok(()))
+        }
+    }
+}
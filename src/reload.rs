@@ -0,0 +1,65 @@
+use sqlx::PgPool;
+
+use crate::{
+    configuration::{
+        aws_credentials::StaticCredentials,
+        config::{get_configuration, Configuration},
+    },
+    shutdown::Shutdown,
+};
+
+/// Swaps in new database and SES credentials without a restart.
+pub struct CredentialReloader {
+    pool: PgPool,
+    ses_credentials: Option<StaticCredentials>,
+}
+
+impl CredentialReloader {
+    pub fn new(pool: PgPool, ses_credentials: Option<StaticCredentials>) -> Self {
+        Self {
+            pool,
+            ses_credentials,
+        }
+    }
+
+    /// Open connections keep working, new ones log in with the new password.
+    /// SES keys are only rotated in `static` mode, the other modes refresh on their own.
+    pub fn apply(&self, configuration: &Configuration) {
+        self.pool
+            .set_connect_options(configuration.database.with_db());
+        if let (Some(credentials), Some(access_key_id), Some(secret_access_key)) = (
+            &self.ses_credentials,
+            &configuration.aws.access_key_id,
+            &configuration.aws.secret_access_key,
+        ) {
+            credentials.rotate(access_key_id, secret_access_key);
+        }
+    }
+
+    /// Re-reads the configuration on every SIGHUP until shutdown.
+    pub async fn run(self, shutdown: Shutdown) {
+        #[cfg(unix)]
+        {
+            let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+                .expect("Failed to install the SIGHUP handler");
+            loop {
+                tokio::select! {
+                    _ = hangup.recv() => {}
+                    _ = shutdown.triggered() => return,
+                }
+                match get_configuration() {
+                    Ok(configuration) => {
+                        self.apply(&configuration);
+                        tracing::info!("Reloaded database and SES credentials");
+                    }
+                    Err(e) => tracing::error!(
+                        "Keeping the current credentials, the configuration failed to load: {}",
+                        e
+                    ),
+                }
+            }
+        }
+        #[cfg(not(unix))]
+        shutdown.triggered().await;
+    }
+}
@@ -0,0 +1,116 @@
+use axum::{
+    extract::Request,
+    http::{HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+// Long enough for any sane upstream ID, short enough to stay within SES tag limits.
+const MAX_LENGTH: usize = 128;
+
+tokio::task_local! {
+    static CURRENT: RequestId;
+}
+
+/// Correlates a request across logs, error bodies and outbound SES calls.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(transparent)]
+pub struct RequestId(String);
+
+impl RequestId {
+    pub fn generate() -> Self {
+        Self(uuid::Uuid::new_v4().to_string())
+    }
+
+    /// Only IDs made of ASCII letters, digits, `-` and `_` are kept, which is
+    /// also what SES accepts as a message tag value.
+    pub fn parse(value: &str) -> Option<Self> {
+        let valid = !value.is_empty()
+            && value.len() <= MAX_LENGTH
+            && value
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_');
+        valid.then(|| Self(value.to_string()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// ID of the request being handled by the current task, if any.
+    pub fn current() -> Option<Self> {
+        CURRENT.try_with(Clone::clone).ok()
+    }
+}
+
+/// Honours a well-formed incoming `X-Request-Id`, generates one otherwise,
+/// and echoes it back on the response.
+///
+/// Has to be the outermost layer so that everything below, including the
+/// trace span, sees the same ID.
+pub async fn propagate_request_id(mut request: Request, next: Next) -> Response {
+    let incoming = request
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok());
+    let request_id = match incoming.map(|value| (value, RequestId::parse(value))) {
+        Some((_, Some(request_id))) => request_id,
+        Some((value, None)) => {
+            tracing::warn!(
+                incoming = %value.escape_debug(),
+                "Ignoring a malformed X-Request-Id"
+            );
+            RequestId::generate()
+        }
+        None => RequestId::generate(),
+    };
+
+    request.extensions_mut().insert(request_id.clone());
+    let mut response = CURRENT.scope(request_id.clone(), next.run(request)).await;
+
+    if let Ok(value) = HeaderValue::from_str(request_id.as_str()) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use claim::{assert_none, assert_some_eq};
+
+    use super::RequestId;
+
+    #[test]
+    fn well_formed_ids_are_kept() {
+        assert_some_eq!(
+            RequestId::parse("req_01H-abc").map(|id| id.as_str().to_string()),
+            "req_01H-abc".to_string()
+        );
+    }
+
+    #[test]
+    fn ids_with_other_characters_are_rejected() {
+        assert_none!(RequestId::parse(""));
+        assert_none!(RequestId::parse("with space"));
+        assert_none!(RequestId::parse("line\nbreak"));
+        assert_none!(RequestId::parse("dotted.id"));
+    }
+
+    #[test]
+    fn overly_long_ids_are_rejected() {
+        assert_none!(RequestId::parse(&"a".repeat(129)));
+    }
+
+    #[test]
+    fn generated_ids_are_well_formed() {
+        let generated = RequestId::generate();
+        assert_some_eq!(RequestId::parse(generated.as_str()), generated);
+    }
+
+    #[tokio::test]
+    async fn there_is_no_current_id_outside_a_request() {
+        assert_none!(RequestId::current());
+    }
+}
@@ -0,0 +1,137 @@
+use std::{
+    convert::Infallible,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts},
+    http::{request::Parts, HeaderMap},
+};
+use ipnet::IpNet;
+
+/// Reverse proxies and load balancers allowed to set `X-Forwarded-For`.
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies(Vec<IpNet>);
+
+impl TrustedProxies {
+    pub fn new(networks: Vec<IpNet>) -> Self {
+        Self(networks)
+    }
+
+    fn contains(&self, ip: &IpAddr) -> bool {
+        self.0.iter().any(|network| network.contains(ip))
+    }
+}
+
+/// IP address of the client, when the server was started with connect info.
+///
+/// `X-Forwarded-For` is only honoured when the peer is one of the
+/// [`TrustedProxies`] found in the request extensions.
+pub struct ClientIp(pub Option<IpAddr>);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ClientIp
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+        let ip = match parts.extensions.get::<Arc<TrustedProxies>>() {
+            Some(trusted_proxies) => resolve_client_ip(peer, &parts.headers, trusted_proxies),
+            None => peer,
+        };
+        Ok(Self(ip))
+    }
+}
+
+pub fn resolve_client_ip(
+    peer: Option<IpAddr>,
+    headers: &HeaderMap,
+    trusted_proxies: &TrustedProxies,
+) -> Option<IpAddr> {
+    let peer = peer?;
+    if !trusted_proxies.contains(&peer) {
+        return Some(peer);
+    }
+
+    let hops: Vec<&str> = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .collect();
+
+    // Each proxy appends the address it got the request from, so we walk the
+    // chain right to left and stop at the first hop we don't trust.
+    let mut client = peer;
+    for hop in hops.iter().rev() {
+        match hop.parse::<IpAddr>() {
+            Ok(ip) => {
+                client = ip;
+                if !trusted_proxies.contains(&ip) {
+                    break;
+                }
+            }
+            Err(_) => break,
+        }
+    }
+    Some(client)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+
+    use axum::http::{HeaderMap, HeaderValue};
+
+    use super::{resolve_client_ip, TrustedProxies};
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    fn forwarded_for(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    fn trusted() -> TrustedProxies {
+        TrustedProxies::new(vec!["10.0.0.0/8".parse().unwrap()])
+    }
+
+    #[test]
+    fn the_header_is_ignored_when_the_peer_is_not_trusted() {
+        let headers = forwarded_for("1.2.3.4");
+        let client = resolve_client_ip(Some(ip("5.6.7.8")), &headers, &trusted());
+        assert_eq!(client, Some(ip("5.6.7.8")));
+    }
+
+    #[test]
+    fn the_rightmost_untrusted_hop_is_the_client() {
+        let headers = forwarded_for("9.9.9.9, 1.2.3.4, 10.0.0.2");
+        let client = resolve_client_ip(Some(ip("10.0.0.1")), &headers, &trusted());
+        assert_eq!(client, Some(ip("1.2.3.4")));
+    }
+
+    #[test]
+    fn the_peer_is_used_when_a_trusted_proxy_sends_no_header() {
+        let client = resolve_client_ip(Some(ip("10.0.0.1")), &HeaderMap::new(), &trusted());
+        assert_eq!(client, Some(ip("10.0.0.1")));
+    }
+
+    #[test]
+    fn a_garbage_hop_stops_the_walk() {
+        let headers = forwarded_for("1.2.3.4, not-an-ip, 10.0.0.2");
+        let client = resolve_client_ip(Some(ip("10.0.0.1")), &headers, &trusted());
+        assert_eq!(client, Some(ip("10.0.0.2")));
+    }
+}
@@ -0,0 +1,179 @@
+use std::sync::Arc;
+
+use axum::{
+    http::{header::ACCEPT, HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Redirect, Response},
+};
+
+use crate::i18n::Localizer;
+
+/// What a subscriber sees after following a confirmation or unsubscribe link in a browser.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfirmationPage {
+    Confirmed,
+    AlreadyConfirmed,
+    Expired,
+    Invalid,
+    /// Our fault, the link may well work later.
+    Failed,
+    InvalidUnsubscribeLink,
+    /// Unknown, or e.g. bounced: not getting the newsletter either way.
+    NotSubscribed,
+    UnsubscribeFailed,
+}
+
+impl ConfirmationPage {
+    fn message_prefix(self) -> &'static str {
+        match self {
+            Self::Confirmed => "page-confirmed",
+            Self::AlreadyConfirmed => "page-already-confirmed",
+            Self::Expired => "page-expired",
+            Self::Invalid => "page-invalid",
+            Self::Failed => "page-failed",
+            Self::InvalidUnsubscribeLink => "page-unsubscribe-invalid",
+            Self::NotSubscribed => "page-not-subscribed",
+            Self::UnsubscribeFailed => "page-unsubscribe-failed",
+        }
+    }
+}
+
+/// Renders the confirmation pages, or sends browsers to `redirect_url` after a success.
+pub struct ConfirmationPages {
+    localizer: Arc<Localizer>,
+    redirect_url: Option<String>,
+}
+
+impl ConfirmationPages {
+    pub fn new(localizer: Arc<Localizer>, redirect_url: Option<String>) -> Self {
+        Self {
+            localizer,
+            redirect_url,
+        }
+    }
+
+    pub fn respond(
+        &self,
+        page: ConfirmationPage,
+        status: StatusCode,
+        headers: &HeaderMap,
+    ) -> Response {
+        if let (ConfirmationPage::Confirmed | ConfirmationPage::AlreadyConfirmed, Some(url)) =
+            (page, &self.redirect_url)
+        {
+            return Redirect::to(url).into_response();
+        }
+
+        let locale = self.localizer.negotiate_request(None, headers);
+        (status, self.render(locale, page.message_prefix(), "")).into_response()
+    }
+
+    /// Asks before unsubscribing, so mail scanners prefetching the link don't unsubscribe
+    /// anybody: only the form's POST does. `link` must have been verified, which leaves
+    /// nothing in it that needs escaping.
+    pub fn unsubscribe_form(&self, link: &str, headers: &HeaderMap) -> Response {
+        let locale = self.localizer.negotiate_request(None, headers);
+        let form = format!(
+            r#"<form method="post" action="/subscriptions/unsubscribe?link={}"><button type="submit">{}</button></form>"#,
+            link,
+            self.localizer
+                .message(locale, "page-unsubscribe-button", &[])
+        );
+        self.render(locale, "page-unsubscribe", &form)
+            .into_response()
+    }
+
+    pub fn unsubscribed(&self, headers: &HeaderMap) -> Response {
+        let locale = self.localizer.negotiate_request(None, headers);
+        self.render(locale, "page-unsubscribed", "").into_response()
+    }
+
+    fn render(&self, locale: &str, prefix: &str, extra_html: &str) -> Html<String> {
+        let message = |suffix: &str| {
+            self.localizer
+                .message(locale, &format!("{}-{}", prefix, suffix), &[])
+        };
+        let title = message("title");
+        Html(format!(
+            r#"<!DOCTYPE html>
+<html lang="{}">
+    <head>
+        <meta charset="utf-8">
+        <meta name="viewport" content="width=device-width, initial-scale=1">
+        <title>{}</title>
+        <style>
+            body {{ margin: 0; padding: 4rem 1rem; background: #f4f1ec; color: #1f2933; font-family: system-ui, sans-serif; }}
+            main {{ max-width: 32rem; margin: 0 auto; padding: 2rem 2.5rem; background: #fff; border-radius: 8px; box-shadow: 0 1px 4px rgba(0, 0, 0, 0.1); }}
+            h1 {{ font-size: 1.5rem; }}
+        </style>
+    </head>
+    <body>
+        <main>
+            <h1>{}</h1>
+            <p>{}</p>
+            {}
+        </main>
+    </body>
+</html>
+"#,
+            locale,
+            title,
+            title,
+            message("body"),
+            extra_html
+        ))
+    }
+}
+
+/// `true` when the client lists `text/html` at least as high as `application/json`.
+///
+/// `*/*` doesn't count, so curl and most HTTP libraries keep getting JSON.
+pub fn prefers_html(headers: &HeaderMap) -> bool {
+    let Some(accept) = headers.get(ACCEPT).and_then(|value| value.to_str().ok()) else {
+        return false;
+    };
+    let mut html = 0.0;
+    let mut json = 0.0;
+    for range in accept.split(',') {
+        let mut parts = range.split(';').map(str::trim);
+        let media_type = parts.next().unwrap_or_default().to_ascii_lowercase();
+        let quality = parts
+            .find_map(|parameter| parameter.strip_prefix("q="))
+            .and_then(|q| q.parse::<f32>().ok())
+            .unwrap_or(1.0);
+        match media_type.as_str() {
+            "text/html" => html = f32::max(html, quality),
+            "application/json" => json = f32::max(json, quality),
+            _ => {}
+        }
+    }
+    html > 0.0 && html >= json
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{header::ACCEPT, HeaderMap, HeaderValue};
+
+    use super::prefers_html;
+
+    fn accept(value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT, HeaderValue::from_static(value));
+        headers
+    }
+
+    #[test]
+    fn browsers_get_html() {
+        assert!(prefers_html(&accept(
+            "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8"
+        )));
+    }
+
+    #[test]
+    fn api_clients_get_json() {
+        assert!(!prefers_html(&HeaderMap::new()));
+        assert!(!prefers_html(&accept("*/*")));
+        assert!(!prefers_html(&accept("application/json")));
+        assert!(!prefers_html(&accept("text/html;q=0.5, application/json")));
+        assert!(!prefers_html(&accept("text/html;q=0")));
+    }
+}
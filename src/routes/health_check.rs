@@ -1,5 +1,32 @@
-use axum::{http::StatusCode, response::IntoResponse};
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+
+use crate::health::{ReadinessProbe, Status};
 
 pub async fn health_check() -> impl IntoResponse {
     (StatusCode::OK, "OK")
 }
+
+/// The process is up and serving requests, dependencies are not looked at.
+pub async fn liveness() -> impl IntoResponse {
+    Json(serde_json::json!({ "status": "ok" }))
+}
+
+/// Whether the instance should receive traffic, with a breakdown per dependency.
+pub async fn readiness(State(probe): State<Arc<ReadinessProbe>>) -> Response {
+    let report = probe.run().await;
+    let status = match report.status {
+        Status::Ok | Status::Degraded => StatusCode::OK,
+        Status::Unavailable => {
+            tracing::warn!(?report, "Readiness check failed");
+            StatusCode::SERVICE_UNAVAILABLE
+        }
+    };
+    (status, Json(report)).into_response()
+}
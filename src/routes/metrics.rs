@@ -0,0 +1,28 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    http::{header::CONTENT_TYPE, StatusCode},
+    response::{IntoResponse, Response},
+};
+
+use crate::{database::db::Database, metrics::Metrics};
+
+/// Prometheus scrape endpoint.
+pub async fn metrics(
+    State(metrics): State<Arc<Metrics>>,
+    State(db): State<Arc<Database>>,
+) -> Response {
+    match metrics.render(&db.pool).await {
+        Ok(body) => (
+            StatusCode::OK,
+            [(CONTENT_TYPE, prometheus::TEXT_FORMAT)],
+            body,
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("Failed to render metrics: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
@@ -1,8 +1,14 @@
+pub mod client_ip;
+pub mod confirmation_page;
 pub mod health_check;
+pub mod metrics;
 pub mod newsletter;
+pub mod newsletter_issue;
 pub mod router;
 pub mod subscriptions;
 pub mod subscriptions_confirm;
+pub mod subscriptions_resend;
+pub mod subscriptions_unsubscribe;
 
 pub use health_check::*;
 pub use subscriptions::*;
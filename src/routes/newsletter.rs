@@ -1,68 +1,276 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
 use anyhow::Context;
 use axum::{
     extract::State,
-    http::StatusCode,
+    http::{HeaderMap, StatusCode},
     response::{IntoResponse, Response},
-    Json,
+    Extension, Json,
 };
-use sqlx::PgPool;
+
+use uuid::Uuid;
 
 use super::error_chain_fmt;
-use crate::{database::db::Database, domain::SubscriberEmail, ses_workflow::SESWorkflow};
+use crate::{
+    database::{
+        db::Database,
+        issues::store_issue,
+        subscribers::{ConfirmedSubscriber, ConfirmedSubscribers, SubscriberRepository},
+    },
+    i18n::{middleware::localize, Localizer},
+    metrics::{EmailKind, Metrics},
+    request_id::RequestId,
+    ses_workflow::SESWorkflow,
+    signed_links::LinkSigner,
+};
 
-#[derive(serde::Deserialize)]
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
 pub struct NewsletterPayload {
-    title: String,
-    content: Content,
+    pub title: String,
+    pub content: Content,
+    // Keyed by locale, subscribers without a matching variant get the default issue.
+    #[serde(default)]
+    pub translations: HashMap<String, Translation>,
+}
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub struct Translation {
+    pub title: String,
+    pub content: Content,
+}
+
+impl NewsletterPayload {
+    pub fn for_locale(&self, locale: &str) -> (&str, &Content) {
+        match self.translations.get(locale) {
+            Some(translation) => (&translation.title, &translation.content),
+            None => (&self.title, &self.content),
+        }
+    }
 }
 
-#[derive(serde::Deserialize)]
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
 pub struct Content {
-    text: String,
-    html: String,
+    pub text: String,
+    pub html: String,
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn publish_newsletter(
     State(ses_client): State<Arc<SESWorkflow>>,
+    State(subscribers): State<Arc<dyn SubscriberRepository>>,
+    State(localizer): State<Arc<Localizer>>,
+    State(metrics): State<Arc<Metrics>>,
+    State(links): State<Arc<LinkSigner>>,
     State(db): State<Arc<Database>>,
+    Extension(base_url): Extension<Arc<String>>,
+    headers: HeaderMap,
     Json(payload): Json<NewsletterPayload>,
 ) -> Result<Response, PublishError> {
-    let subscribers = get_confirmed_subscribers(&db.pool).await?;
-    for subscriber in subscribers {
-        match subscriber {
-            Ok(sub) => {
-                ses_client
-                    .send_email(
-                        &sub.email,
-                        &payload.title,
-                        &payload.content.text,
-                        &payload.content.html,
-                    )
-                    .await
-                    // Lazy load context, avoid paying for the error path when fallible operations succeeds.
-                    .with_context(|| {
-                        format!("Failed to send newsletter issue to {}", &sub.email)
-                    })?;
-            }
-            Err(error) => {
-                tracing::warn!(
-                // We record the error chain as a structured field
-                // on the log record.
-                error.cause_chain = ?error,
-                // Using `\` to split a long string literal over
-                // two lines, without creating a `\n` character.
-                "Skipping a confirmed subscriber. \
-                Their stored contact details are invalid",
-                );
-            }
+    let issue_id = store_issue(&db.pool, &payload)
+        .await
+        .context("Failed to store the newsletter issue.")?;
+    let report = deliver_issue(
+        &ses_client,
+        subscribers.as_ref(),
+        &metrics,
+        &IssueLinks {
+            links: &links,
+            localizer: &localizer,
+            base_url: &base_url,
+            issue_id: Some(issue_id),
+        },
+        &payload,
+    )
+    .await?;
+    tracing::info!(
+        sent = report.sent,
+        invalid = report.invalid,
+        "Newsletter issue delivered"
+    );
+
+    let locale = localizer.negotiate_request(None, &headers);
+    let response_body = Json(serde_json::json!({
+        "message": localizer.message(locale, "newsletter-published", &[])
+    }));
+    Ok((StatusCode::OK, response_body).into_response())
+}
+
+// Subscribers fetched per query while an issue goes out.
+const DELIVERY_PAGE_SIZE: usize = 500;
+
+/// What happened to an issue.
+#[derive(Debug, Default, PartialEq)]
+pub struct DeliveryReport {
+    pub sent: usize,
+    /// Confirmed subscribers skipped because their stored email doesn't parse.
+    pub invalid: usize,
+}
+
+impl std::fmt::Display for DeliveryReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "sent to {} subscriber(s), skipped {} with an invalid email",
+            self.sent, self.invalid
+        )
+    }
+}
+
+/// What an issue needs to give every subscriber their own links.
+pub struct IssueLinks<'a> {
+    pub links: &'a LinkSigner,
+    pub localizer: &'a Localizer,
+    pub base_url: &'a str,
+    /// Where the issue was stored, there is no view-in-browser link without it.
+    pub issue_id: Option<Uuid>,
+}
+
+impl IssueLinks<'_> {
+    /// The issue in the subscriber's locale with an unsubscribe and view-in-browser footer
+    /// and its links tracked if enabled, and the unsubscribe link itself for the
+    /// `List-Unsubscribe` header. Sent as is without a signing key.
+    fn personalise(
+        &self,
+        subscriber: &ConfirmedSubscriber,
+        content: &Content,
+    ) -> (String, String, Option<String>) {
+        let Some(unsubscribe) = self.links.unsubscribe_url(self.base_url, subscriber.id) else {
+            return (content.text.clone(), content.html.clone(), None);
+        };
+        let footer = |id, link: &str| {
+            self.localizer
+                .message(&subscriber.locale, id, &[("link", link)])
+        };
+
+        let mut text = format!(
+            "{}\n\n{}",
+            content.text,
+            footer("newsletter-unsubscribe-text", &unsubscribe)
+        );
+        let mut html = if self.links.tracks_clicks() {
+            track_clicks(&content.html, |target| {
+                self.links
+                    .tracking_url(self.base_url, subscriber.id, target)
+            })
+        } else {
+            content.html.clone()
+        };
+        html.push_str(&format!(
+            "\n<hr>\n<p>{}</p>",
+            footer("newsletter-unsubscribe-html", &unsubscribe)
+        ));
+        let view_url = self.issue_id.and_then(|issue_id| {
+            self.links.view_in_browser_url(
+                self.base_url,
+                subscriber.id,
+                issue_id,
+                &subscriber.locale,
+            )
+        });
+        if let Some(view_url) = view_url {
+            text.push_str(&format!(
+                "\n{}",
+                footer("newsletter-view-in-browser-text", &view_url)
+            ));
+            html.push_str(&format!(
+                "\n<p>{}</p>",
+                footer(
+                    "newsletter-view-in-browser-html",
+                    &view_url.replace('&', "&amp;")
+                )
+            ));
         }
+        (text, html, Some(unsubscribe))
     }
+}
 
-    let response_body =
-        Json(serde_json::json!({ "message": "Newsletter was published successfully." }));
-    Ok((StatusCode::OK, response_body).into_response())
+/// Points every absolute `href="…"` in `html` at the URL `tracking_url` makes for it.
+/// Relative and `mailto:` links are left alone.
+fn track_clicks(html: &str, tracking_url: impl Fn(&str) -> Option<String>) -> String {
+    const HREF: &str = "href=\"";
+
+    let mut tracked = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find(HREF) {
+        let (before, after) = rest.split_at(start + HREF.len());
+        tracked.push_str(before);
+        let Some(end) = after.find('"') else {
+            rest = after;
+            break;
+        };
+        let href = &after[..end];
+        let target = href.replace("&amp;", "&");
+        let tracking = (target.starts_with("https://") || target.starts_with("http://"))
+            .then(|| tracking_url(&target))
+            .flatten();
+        match tracking {
+            Some(url) => tracked.push_str(&url.replace('&', "&amp;")),
+            None => tracked.push_str(href),
+        }
+        rest = &after[end..];
+    }
+    tracked.push_str(rest);
+    tracked
+}
+
+/// Sends `payload` to every confirmed subscriber, a page at a time, stops at the first
+/// failed send.
+pub async fn deliver_issue(
+    ses_client: &SESWorkflow,
+    subscribers: &dyn SubscriberRepository,
+    metrics: &Metrics,
+    links: &IssueLinks<'_>,
+    payload: &NewsletterPayload,
+) -> Result<DeliveryReport, anyhow::Error> {
+    if !links.links.signs_links() {
+        tracing::warn!(
+            "No `signed_links.signing_key`, the issue goes out without unsubscribe links"
+        );
+    }
+    let mut report = DeliveryReport::default();
+    let mut pages = ConfirmedSubscribers::new(subscribers, DELIVERY_PAGE_SIZE);
+    while let Some(page) = pages.next_page().await? {
+        let mut pending = metrics.enqueue_deliveries(page.len());
+        for subscriber in page {
+            match subscriber {
+                Ok(sub) => {
+                    let (title, content) = payload.for_locale(&sub.locale);
+                    let (text, html, unsubscribe_url) = links.personalise(&sub, content);
+                    let sent = match unsubscribe_url {
+                        Some(url) => {
+                            ses_client
+                                .send_list_email(&sub.email, title, &text, &html, &url)
+                                .await
+                        }
+                        None => ses_client.send_email(&sub.email, title, &text, &html).await,
+                    };
+                    metrics.record_email(EmailKind::Newsletter, &sent);
+                    pending.done();
+                    sent
+                        // Lazy load context, avoid paying for the error path when fallible operations succeeds.
+                        .with_context(|| {
+                            format!("Failed to send newsletter issue to {}", &sub.email)
+                        })?;
+                    report.sent += 1;
+                }
+                Err(error) => {
+                    tracing::warn!(
+                    // We record the error chain as a structured field
+                    // on the log record.
+                    error.cause_chain = ?error,
+                    // Using `\` to split a long string literal over
+                    // two lines, without creating a `\n` character.
+                    "Skipping a confirmed subscriber. \
+                    Their stored contact details are invalid",
+                    );
+                    metrics.record_invalid_subscriber();
+                    report.invalid += 1;
+                    pending.done();
+                }
+            }
+        }
+    }
+    Ok(report)
 }
 
 #[derive(thiserror::Error)]
@@ -83,45 +291,52 @@ impl IntoResponse for PublishError {
         #[derive(serde::Serialize)]
         struct Error {
             message: String,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            request_id: Option<RequestId>,
         }
 
+        let english = self.to_string();
         let (status, message) = match self {
             Self::UnexpectedError(e) => {
                 tracing::error!("Got an unexpected one: {:?}", e);
-                (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    localize("error-unexpected", &[], || english),
+                )
             }
         };
 
-        (status, Json(Error { message })).into_response()
+        (
+            status,
+            Json(Error {
+                message,
+                request_id: RequestId::current(),
+            }),
+        )
+            .into_response()
     }
 }
 
-struct ConfirmedSubscriber {
-    email: SubscriberEmail,
-}
+#[cfg(test)]
+mod tests {
+    use super::track_clicks;
 
-#[tracing::instrument(name = "Get confirmed subscribers")]
-async fn get_confirmed_subscribers(
-    pool: &PgPool,
-) -> Result<Vec<Result<ConfirmedSubscriber, anyhow::Error>>, anyhow::Error> {
-    struct Row {
-        email: String,
+    #[test]
+    fn absolute_links_are_tracked() {
+        let html = r#"<p><a href="https://example.com/a?x=1&amp;y=2">A</a> <a href="/relative">B</a> <a href="mailto:me@example.com">C</a></p>"#;
+
+        let tracked = track_clicks(html, |target| Some(format!("/click?url={}&link=l", target)));
+
+        assert_eq!(
+            tracked,
+            r#"<p><a href="/click?url=https://example.com/a?x=1&amp;y=2&amp;link=l">A</a> <a href="/relative">B</a> <a href="mailto:me@example.com">C</a></p>"#
+        );
     }
-    // Map to the Row type and minimise amount of data to be fetched
-    let rows = sqlx::query_as!(
-        Row,
-        r#"SELECT email FROM subscriptions WHERE status='confirmed'"#,
-    )
-    .fetch_all(pool)
-    .await?;
 
-    let confirmed_subscribers = rows
-        .into_iter()
-        .map(|r| match SubscriberEmail::parse(r.email) {
-            Ok(email) => Ok(ConfirmedSubscriber { email }),
-            Err(error) => Err(anyhow::anyhow!(error)),
-        })
-        .collect();
+    #[test]
+    fn html_without_links_is_left_alone() {
+        let html = "<p>No links, an unterminated href=\"here</p>";
 
-    Ok(confirmed_subscribers)
+        assert_eq!(track_clicks(html, |_| Some("tracked".into())), html);
+    }
 }
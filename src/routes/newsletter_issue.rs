@@ -0,0 +1,147 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Redirect, Response},
+    Json,
+};
+use uuid::Uuid;
+
+use super::subscriptions_confirm::{link_error_message, link_error_status};
+use crate::{
+    database::{db::Database, issues::issue_by_id},
+    i18n::{
+        middleware::{localize, set_locale},
+        Localizer,
+    },
+    metrics::Metrics,
+    request_id::RequestId,
+    signed_links::{LinkAction, LinkError, LinkSigner},
+};
+
+#[derive(serde::Deserialize)]
+pub struct ViewParameters {
+    link: String,
+    #[serde(default)]
+    locale: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct ClickParameters {
+    url: String,
+    link: String,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum IssueLinkError {
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+    #[error("There is no issue associated with the provided link.")]
+    UnknownIssue,
+    #[error(transparent)]
+    InvalidLink(#[from] LinkError),
+}
+
+impl IntoResponse for IssueLinkError {
+    fn into_response(self) -> Response {
+        #[derive(serde::Serialize)]
+        struct Error {
+            message: String,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            request_id: Option<RequestId>,
+        }
+
+        let english = self.to_string();
+        let (status, message) = match self {
+            IssueLinkError::UnexpectedError(error) => {
+                tracing::error!("Got an unexpected one: {:?}", error);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    localize("error-unexpected", &[], || english),
+                )
+            }
+            IssueLinkError::UnknownIssue => (
+                StatusCode::NOT_FOUND,
+                localize("error-unknown-issue", &[], || english),
+            ),
+            IssueLinkError::InvalidLink(e) => {
+                tracing::warn!("Refused a newsletter issue link: {}", e);
+                (link_error_status(&e), link_error_message(&e))
+            }
+        };
+
+        (
+            status,
+            Json(Error {
+                message,
+                request_id: RequestId::current(),
+            }),
+        )
+            .into_response()
+    }
+}
+
+/// The "view in browser" link at the bottom of an issue.
+#[tracing::instrument(
+    name = "Show a newsletter issue",
+    skip(db, links, localizer, parameters, headers)
+)]
+pub async fn view_issue(
+    State(db): State<Arc<Database>>,
+    State(links): State<Arc<LinkSigner>>,
+    State(localizer): State<Arc<Localizer>>,
+    Path(issue_id): Path<String>,
+    headers: HeaderMap,
+    Query(parameters): Query<ViewParameters>,
+) -> Result<Response, IssueLinkError> {
+    let locale = localizer.negotiate_request(parameters.locale.as_deref(), &headers);
+    set_locale(locale);
+    links.verify_bound(LinkAction::ViewInBrowser, &parameters.link, &issue_id)?;
+    // Only ids we signed get this far.
+    let issue_id = Uuid::try_parse(&issue_id).map_err(|_| LinkError::Malformed)?;
+    let issue = issue_by_id(&db.pool, issue_id)
+        .await?
+        .ok_or(IssueLinkError::UnknownIssue)?;
+
+    let (title, content) = issue.for_locale(locale);
+    Ok(Html(format!(
+        r#"<!DOCTYPE html>
+<html lang="{}">
+    <head>
+        <meta charset="utf-8">
+        <meta name="viewport" content="width=device-width, initial-scale=1">
+        <title>{}</title>
+    </head>
+    <body>
+{}
+    </body>
+</html>
+"#,
+        locale,
+        escape_html(title),
+        content.html
+    ))
+    .into_response())
+}
+
+/// Where tracked links in an issue point, counts the click and sends the subscriber on.
+/// Only signed targets are followed, so this can't be used as an open redirect.
+#[tracing::instrument(name = "Follow a tracked link", skip(links, metrics, parameters))]
+pub async fn track_click(
+    State(links): State<Arc<LinkSigner>>,
+    State(metrics): State<Arc<Metrics>>,
+    Query(parameters): Query<ClickParameters>,
+) -> Result<Response, IssueLinkError> {
+    let subscriber_id = links.verify_bound(LinkAction::Track, &parameters.link, &parameters.url)?;
+    metrics.record_link_click();
+    tracing::info!(%subscriber_id, url = %parameters.url, "Tracked link followed");
+    Ok(Redirect::to(&parameters.url).into_response())
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -1,38 +1,116 @@
 use axum::{
     http::Request,
+    middleware::{from_fn, from_fn_with_state},
     routing::{get, post},
     Extension, Router,
 };
 use tower_http::trace::TraceLayer;
 use tracing::Level;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
 
 use std::sync::Arc;
 
 use super::newsletter::publish_newsletter;
 use crate::{
-    routes::{health_check, subscribe, subscriptions_confirm::confirm},
+    i18n::middleware::negotiate_locale,
+    metrics::middleware::track_http_metrics,
+    rate_limit::middleware::rate_limit,
+    request_id::{propagate_request_id, RequestId},
+    routes::{
+        form_token, health_check, liveness,
+        metrics::metrics,
+        newsletter_issue::{track_click, view_issue},
+        readiness, subscribe,
+        subscriptions_confirm::confirm,
+        subscriptions_resend::resend_confirmation,
+        subscriptions_unsubscribe::{unsubscribe, unsubscribe_page},
+    },
     state::AppState,
+    telemetry::extract_trace_context,
 };
 
 pub fn router(state: AppState, base_url: Arc<String>) -> Router {
+    let rate_limits = state.rate_limits.clone();
+    let http_metrics = state.metrics.clone();
+    let localizer = state.localizer.clone();
+
     Router::new()
         .route("/health_check", get(health_check))
-        .route("/subscriptions", post(subscribe))
-        .route("/subscriptions/confirm", get(confirm))
-        .route("/newsletters", post(publish_newsletter))
+        .route("/health/live", get(liveness))
+        .route("/health/ready", get(readiness))
+        .route("/metrics", get(metrics))
+        .route(
+            "/subscriptions",
+            post(subscribe).route_layer(from_fn_with_state(rate_limits.subscriptions, rate_limit)),
+        )
+        .route("/subscriptions/form-token", get(form_token))
+        .route(
+            "/subscriptions/confirm",
+            get(confirm).route_layer(from_fn_with_state(
+                rate_limits.subscriptions_confirm.clone(),
+                rate_limit,
+            )),
+        )
+        .route(
+            "/subscriptions/resend-confirmation",
+            post(resend_confirmation).route_layer(from_fn_with_state(
+                rate_limits.resend_confirmation,
+                rate_limit,
+            )),
+        )
+        .route(
+            "/subscriptions/unsubscribe",
+            get(unsubscribe_page)
+                .post(unsubscribe)
+                .route_layer(from_fn_with_state(
+                    rate_limits.subscriptions_confirm.clone(),
+                    rate_limit,
+                )),
+        )
+        .route(
+            "/newsletters/issues/:issue_id",
+            get(view_issue).route_layer(from_fn_with_state(
+                rate_limits.subscriptions_confirm.clone(),
+                rate_limit,
+            )),
+        )
+        .route(
+            "/newsletters/click",
+            get(track_click).route_layer(from_fn_with_state(
+                rate_limits.subscriptions_confirm,
+                rate_limit,
+            )),
+        )
+        .route(
+            "/newsletters",
+            post(publish_newsletter)
+                .route_layer(from_fn_with_state(rate_limits.newsletters, rate_limit)),
+        )
         .with_state(state)
+        .layer(from_fn_with_state(localizer, negotiate_locale))
+        .layer(from_fn_with_state(http_metrics, track_http_metrics))
         .layer(Extension(base_url))
+        .layer(Extension(rate_limits.trusted_proxies))
         .layer(
             TraceLayer::new_for_http().make_span_with(|request: &Request<_>| {
-                let request_id = uuid::Uuid::new_v4();
-                tracing::span!(
-                    Level::DEBUG,
+                let request_id = request
+                    .extensions()
+                    .get::<RequestId>()
+                    .map(|id| id.as_str().to_string())
+                    .unwrap_or_default();
+                // Has to be enabled at the default filter, a disabled span can't carry
+                // the caller's trace context down to the handler spans.
+                let span = tracing::span!(
+                    Level::INFO,
                     "request",
                     %request_id,
                     method = ?request.method(),
                     uri = %request.uri(),
                     version = ?request.version(),
-                )
+                );
+                span.set_parent(extract_trace_context(request.headers()));
+                span
             }),
         )
+        .layer(from_fn(propagate_request_id))
 }
@@ -3,40 +3,77 @@ use std::sync::Arc;
 use anyhow::{Context, Result};
 use axum::{
     extract::State,
-    http::StatusCode,
+    http::{header::RETRY_AFTER, HeaderMap, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
     Extension, Form, Json,
 };
-use rand::{distributions::Alphanumeric, thread_rng, Rng};
-use sqlx::types::chrono::Utc;
 use uuid::Uuid;
 
+use super::client_ip::ClientIp;
 use crate::{
-    database::db::Database,
-    domain::{NewSubscriber, SubscriberEmail, SubscriberName},
+    bot_protection::{BotCheckError, BotProtection},
+    database::subscribers::{AlreadySubscribed, SubscriberRepository},
+    domain::{
+        generate_subscription_token, NewSubscriber, SubscriberEmail, SubscriberName,
+        SubscriptionStatus,
+    },
+    email_validation::{EmailRejection, EmailValidator},
+    i18n::{
+        middleware::{localize, set_locale},
+        Localizer,
+    },
+    metrics::{EmailKind, Metrics},
+    request_id::RequestId,
     ses_workflow::SESWorkflow,
+    signed_links::LinkSigner,
 };
 
 #[derive(serde::Deserialize)]
 pub struct FormData {
     email: String,
     name: String,
+    // Honeypot: hidden from humans with CSS, bots tend to fill every input.
+    #[serde(default)]
+    website: String,
+    #[serde(default)]
+    form_token: Option<String>,
+    #[serde(default)]
+    captcha_token: Option<String>,
+    // Set when the subscriber insists on a domain we flagged as a typo.
+    #[serde(default)]
+    accept_typo: bool,
+    // Language picked on the form, takes precedence over `Accept-Language`.
+    #[serde(default)]
+    locale: Option<String>,
 }
 
-impl TryFrom<FormData> for NewSubscriber {
-    type Error = String;
-
-    fn try_from(value: FormData) -> Result<Self, Self::Error> {
-        let name = SubscriberName::parse(value.name)?;
-        let email = SubscriberEmail::parse(value.email)?;
-        Ok(Self { email, name })
+impl FormData {
+    fn into_new_subscriber(self, locale: String) -> Result<NewSubscriber, SubscribeError> {
+        let name = SubscriberName::parse(self.name.clone())
+            .map_err(|_| SubscribeError::InvalidName(self.name))?;
+        Ok(NewSubscriber {
+            email: parse_email(self.email)?,
+            name,
+            locale,
+        })
     }
 }
 
+/// Keeps the address as typed, to show it back in the error.
+pub fn parse_email(email: String) -> Result<SubscriberEmail, SubscribeError> {
+    SubscriberEmail::parse(email.clone()).map_err(|_| SubscribeError::InvalidEmail(email))
+}
+
 #[derive(thiserror::Error)]
 pub enum SubscribeError {
-    #[error("{0}")]
-    ValidationError(String),
+    #[error("{0} is not a valid subscriber name.")]
+    InvalidName(String),
+    #[error("{0} is not a valid subscriber email.")]
+    InvalidEmail(String),
+    #[error(transparent)]
+    RejectedEmail(#[from] EmailRejection),
+    #[error(transparent)]
+    BotCheckFailed(#[from] BotCheckError),
     #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
 }
@@ -52,188 +89,309 @@ impl IntoResponse for SubscribeError {
         #[derive(serde::Serialize)]
         struct Error {
             message: String,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            request_id: Option<RequestId>,
         }
 
+        let english = self.to_string();
+        let mut retry_after = None;
         let (status, message) = match self {
-            Self::ValidationError(e) => {
-                tracing::error!("Validation error occured: {:?}", e);
-                (StatusCode::BAD_REQUEST, e.to_string())
+            Self::InvalidName(name) => {
+                tracing::warn!("Validation error occured: {}", english);
+                (
+                    StatusCode::BAD_REQUEST,
+                    localize("error-invalid-name", &[("name", &name)], || english),
+                )
             }
-            Self::UnexpectedError(e) => {
+            Self::InvalidEmail(email) => {
+                tracing::warn!("Validation error occured: {}", english);
+                (
+                    StatusCode::BAD_REQUEST,
+                    localize("error-invalid-email", &[("email", &email)], || english),
+                )
+            }
+            Self::RejectedEmail(rejection) => {
+                tracing::warn!("Email rejected: {}", english);
+                let (id, arg) = match &rejection {
+                    EmailRejection::Disposable(domain) => {
+                        ("error-disposable-email", ("domain", domain))
+                    }
+                    EmailRejection::LikelyTypo(suggestion) => {
+                        ("error-likely-typo", ("suggestion", suggestion))
+                    }
+                    EmailRejection::Undeliverable(domain) => {
+                        ("error-undeliverable-email", ("domain", domain))
+                    }
+                };
+                (
+                    StatusCode::BAD_REQUEST,
+                    localize(id, &[(arg.0, arg.1.as_str())], || english),
+                )
+            }
+            Self::BotCheckFailed(BotCheckError::RateLimited(wait)) => {
+                tracing::warn!("Subscription attempt rate limited");
+                let seconds = wait.as_secs().max(1);
+                retry_after = Some(seconds);
+                (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    localize(
+                        "error-rate-limited",
+                        &[("seconds", &seconds.to_string())],
+                        || english,
+                    ),
+                )
+            }
+            Self::BotCheckFailed(BotCheckError::UnexpectedError(e)) | Self::UnexpectedError(e) => {
                 tracing::error!("Got an unexpected one: {:?}", e);
-                (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    localize("error-unexpected", &[], || english),
+                )
+            }
+            Self::BotCheckFailed(e) => {
+                tracing::warn!("Suspicious subscription attempt rejected: {}", e);
+                let id = match e {
+                    BotCheckError::CaptchaFailed => "error-captcha-failed",
+                    _ => "error-form-token",
+                };
+                (StatusCode::BAD_REQUEST, localize(id, &[], || english))
             }
         };
 
-        (status, Json(Error { message })).into_response()
+        let mut response = (
+            status,
+            Json(Error {
+                message,
+                request_id: RequestId::current(),
+            }),
+        )
+            .into_response();
+        if let Some(seconds) = retry_after {
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(seconds));
+        }
+        response
     }
 }
 
-fn generate_subscription_token() -> String {
-    let mut rng = thread_rng();
-    std::iter::repeat_with(|| rng.sample(Alphanumeric))
-        .map(char::from)
-        .take(25)
-        .collect()
+/// Hands out a signed render timestamp to embed in the subscription form.
+pub async fn form_token(State(bot_protection): State<Arc<BotProtection>>) -> impl IntoResponse {
+    Json(serde_json::json!({ "form_token": bot_protection.form_tokens.issue() }))
 }
 
+#[allow(clippy::too_many_arguments)]
 #[tracing::instrument(
     name = "Adding a new subscriber",
-    skip(db, ses_client, form, base_url),
+    skip(
+        subscribers,
+        links,
+        ses_client,
+        bot_protection,
+        email_validator,
+        localizer,
+        metrics,
+        client_ip,
+        headers,
+        form,
+        base_url
+    ),
     fields(
       subscriber_email = %form.email,
       subscriber_name= %form.name
     )
 )]
 pub async fn subscribe(
-    State(db): State<Arc<Database>>,
+    State(subscribers): State<Arc<dyn SubscriberRepository>>,
+    State(links): State<Arc<LinkSigner>>,
     State(ses_client): State<Arc<SESWorkflow>>,
+    State(bot_protection): State<Arc<BotProtection>>,
+    State(email_validator): State<Arc<EmailValidator>>,
+    State(localizer): State<Arc<Localizer>>,
+    State(metrics): State<Arc<Metrics>>,
     Extension(base_url): Extension<Arc<String>>,
+    ClientIp(client_ip): ClientIp,
+    headers: HeaderMap,
     Form(form): Form<FormData>,
 ) -> Result<Response, SubscribeError> {
-    let new_subscriber = form.try_into().map_err(SubscribeError::ValidationError)?;
+    let locale = localizer
+        .negotiate_request(form.locale.as_deref(), &headers)
+        .to_string();
+    set_locale(&locale);
+    let response_body = Json(serde_json::json!({
+        "message": localizer.message(&locale, "subscribe-success", &[])
+    }));
 
-    let mut transaction = db
-        .pool
-        .begin()
-        .await
-        .context("Failed to acquire a Postgres connection from the pool.")?;
+    if !form.website.is_empty() {
+        // Pretend everything went fine, there is no point in telling bots what gave them away.
+        tracing::warn!("Honeypot field filled in, dropping the subscription");
+        return Ok((StatusCode::OK, response_body).into_response());
+    }
 
-    let subscriber_id = insert_subscriber(&mut transaction, &new_subscriber)
-        .await
-        .context("Failed to insert new subscriber in the database.")?;
+    bot_protection.check_ip(client_ip).await?;
+    bot_protection.check_form_token(form.form_token.as_deref())?;
+    bot_protection
+        .check_captcha(form.captcha_token.as_deref(), client_ip)
+        .await?;
 
-    let subscription_token = generate_subscription_token();
-    store_token(&mut transaction, subscriber_id, &subscription_token)
-        .await
-        .context("Failed to store the confirmation token for a new subscriber.")?;
+    let accept_typo = form.accept_typo;
+    let new_subscriber = form.into_new_subscriber(locale)?;
+
+    email_validator
+        .validate(&new_subscriber.email, accept_typo)
+        .await?;
+
+    bot_protection
+        .check_domain(new_subscriber.email.domain())
+        .await?;
 
-    transaction
-        .commit()
-        .await
-        .context("Failed to commit SQL transaction to store a new subscriber.")?;
+    let Some(confirmation_link) =
+        register_pending(subscribers.as_ref(), &links, &base_url, &new_subscriber).await?
+    else {
+        // Same answer as for a new address, so the form can't tell who is subscribed.
+        tracing::info!("The email is subscribed already, not sending a confirmation");
+        return Ok((StatusCode::OK, response_body).into_response());
+    };
 
-    send_confirmation_email(
+    let sent = send_confirmation_email(
         ses_client,
+        &localizer,
+        &new_subscriber.locale,
         new_subscriber.email,
-        &base_url,
-        &subscription_token,
+        &confirmation_link,
     )
-    .await
-    .context("Failed to send a confirmation email.")?;
+    .await;
+    metrics.record_email(EmailKind::Confirmation, &sent);
+    sent.context("Failed to send a confirmation email.")?;
 
-    let response_body = Json(serde_json::json!({ "message": "Check your e-mail box, please" }));
     Ok((StatusCode::OK, response_body).into_response())
 }
 
-#[tracing::instrument(
-    name = "Saving new subscriber details in the database",
-    skip(transaction, new_subscriber)
-)]
-pub async fn insert_subscriber(
-    transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+/// Stores a pending subscriber, returns the link to put in their confirmation email.
+/// Someone who unsubscribed and signs up again starts over as pending. `None` when the
+/// email is subscribed already, there is nothing to confirm.
+///
+/// The link is signed when `links` signs confirmation links, otherwise it carries a token
+/// stored along with the subscriber.
+pub async fn register_pending(
+    subscribers: &dyn SubscriberRepository,
+    links: &LinkSigner,
+    base_url: &str,
     new_subscriber: &NewSubscriber,
-) -> Result<Uuid, sqlx::Error> {
-    let subscriber_id = Uuid::new_v4();
-    sqlx::query!(
-        r#"
-          INSERT INTO subscriptions (id, email, name, subscribed_at, status)
-          VALUES ($1, $2, $3, $4, 'pending_confirmation')
-        "#,
-        subscriber_id,
-        new_subscriber.email.as_ref(),
-        new_subscriber.name.as_ref(),
-        Utc::now()
-    )
-    .execute(&mut **transaction)
-    .await?;
+) -> Result<Option<String>> {
+    let subscription_token = (!links.signs_confirmation_links()).then(generate_subscription_token);
+    let subscriber_id = match subscribers
+        .subscriber_by_email(&new_subscriber.email)
+        .await?
+    {
+        Some(existing) if existing.status == SubscriptionStatus::Unsubscribed => {
+            subscribers
+                .resubscribe(existing.id, new_subscriber, subscription_token.as_deref())
+                .await?;
+            existing.id
+        }
+        Some(_) => return Ok(None),
+        None => match subscribers
+            .insert_pending(new_subscriber, subscription_token.as_deref())
+            .await
+        {
+            Ok(subscriber_id) => subscriber_id,
+            // Signed up by a concurrent request.
+            Err(e) if e.is::<AlreadySubscribed>() => return Ok(None),
+            Err(e) => return Err(e),
+        },
+    };
+    let link = match subscription_token {
+        Some(subscription_token) => token_link(base_url, &subscription_token),
+        None => links
+            .confirmation_url(base_url, subscriber_id)
+            .context("Failed to sign a confirmation link.")?,
+    };
+    Ok(Some(link))
+}
 
-    Ok(subscriber_id)
+/// A new confirmation link for a pending subscriber. A new token replaces the old ones, but
+/// signed links already sent stay valid until they expire.
+pub async fn reissue_confirmation(
+    subscribers: &dyn SubscriberRepository,
+    links: &LinkSigner,
+    base_url: &str,
+    subscriber_id: Uuid,
+) -> Result<String> {
+    if links.signs_confirmation_links() {
+        return links
+            .confirmation_url(base_url, subscriber_id)
+            .context("Failed to sign a confirmation link.");
+    }
+    let subscription_token = generate_subscription_token();
+    subscribers
+        .replace_token(subscriber_id, &subscription_token)
+        .await?;
+    Ok(token_link(base_url, &subscription_token))
+}
+
+fn token_link(base_url: &str, subscription_token: &str) -> String {
+    format!(
+        "{}/subscriptions/confirm?subscription_token={}",
+        base_url, subscription_token
+    )
 }
 
 pub async fn send_confirmation_email(
     ses_client: Arc<SESWorkflow>,
+    localizer: &Localizer,
+    locale: &str,
     recipient_email: SubscriberEmail,
-    base_url: &str,
-    subscription_token: &str,
+    confirmation_link: &str,
 ) -> Result<()> {
-    let confirmation_link = format!(
-        "{}/subscriptions/confirm?subscription_token={}",
-        base_url, subscription_token
-    );
+    send_link_email(
+        &ses_client,
+        localizer,
+        locale,
+        recipient_email,
+        "confirmation",
+        confirmation_link,
+    )
+    .await
+}
 
-    let subject = "Welcome to our newsletter!";
-    let text_content = format!(
-        "Welcome to our newsletter!\nVisit {} to confirm your subscription.",
-        confirmation_link
-    );
+/// Sends the `<messages>-subject`, `-text`, `-heading` and `-html` messages with `link`
+/// filled in.
+pub async fn send_link_email(
+    ses_client: &SESWorkflow,
+    localizer: &Localizer,
+    locale: &str,
+    recipient_email: SubscriberEmail,
+    messages: &str,
+    link: &str,
+) -> Result<()> {
+    let message = |id: &str, args: &[(&str, &str)]| {
+        localizer.message(locale, &format!("{}-{}", messages, id), args)
+    };
+    let link = [("link", link)];
+    let subject = message("subject", &[]);
+    let text_content = message("text", &link);
     let html_content = format!(
         r#"
-        <html>
+        <html lang="{}">
             <body>
-                <h1>Welcome to our newsletter!</h1>
-                <p>Visit <a href="{}">{}</a> to confirm your subscription.</p>
+                <h1>{}</h1>
+                <p>{}</p>
             </body>
         </html>
         "#,
-        confirmation_link, confirmation_link
+        locale,
+        message("heading", &[]),
+        message("html", &link)
     );
 
     ses_client
-        .send_email(&recipient_email, subject, &text_content, &html_content)
+        .send_email(&recipient_email, &subject, &text_content, &html_content)
         .await?;
 
     Ok(())
 }
 
-#[tracing::instrument(
-    name = "Store subscription token in the database",
-    skip(transaction, subscription_token)
-)]
-pub async fn store_token(
-    transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
-    subscriber_id: Uuid,
-    subscription_token: &str,
-) -> Result<(), StoreTokenError> {
-    sqlx::query!(
-        r#"
-          INSERT INTO subscription_tokens (subscription_token, subscriber_id)
-          VALUES ($1, $2)
-        "#,
-        subscription_token,
-        subscriber_id
-    )
-    .execute(&mut **transaction)
-    .await
-    .map_err(StoreTokenError)?;
-
-    Ok(())
-}
-
-pub struct StoreTokenError(sqlx::Error);
-
-impl std::fmt::Display for StoreTokenError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "A database error was encountered while \
-            trying to store a subscription token."
-        )
-    }
-}
-
-impl std::error::Error for StoreTokenError {
-    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-        Some(&self.0)
-    }
-}
-
-impl std::fmt::Debug for StoreTokenError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        error_chain_fmt(self, f)
-    }
-}
-
 pub fn error_chain_fmt(
     e: &impl std::error::Error,
     f: &mut std::fmt::Formatter<'_>,
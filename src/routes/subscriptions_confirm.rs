@@ -3,18 +3,25 @@ use std::sync::Arc;
 use anyhow::Context;
 use axum::{
     extract::{Query, State},
-    http::StatusCode,
+    http::{header::VARY, HeaderMap, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
-use sqlx::PgPool;
-use uuid::Uuid;
 
-use crate::database::db::Database;
+use super::confirmation_page::{prefers_html, ConfirmationPage, ConfirmationPages};
+use crate::{
+    database::subscribers::{StatusChangeError, SubscriberRepository},
+    domain::{IllegalTransition, SubscriptionStatus},
+    i18n::middleware::localize,
+    request_id::RequestId,
+    signed_links::{LinkAction, LinkError, LinkSigner},
+};
 
+/// Either a stored token or a signed link, depending on what the confirmation email carried.
 #[derive(serde::Deserialize)]
 pub struct Parameters {
-    subscription_token: String,
+    subscription_token: Option<String>,
+    link: Option<String>,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -23,6 +30,42 @@ pub enum ConfirmationError {
     UnexpectedError(#[from] anyhow::Error),
     #[error("There is no subscriber associated with the provided token.")]
     UnknownToken,
+    #[error("Either a subscription token or a link is required.")]
+    MissingToken,
+    #[error(transparent)]
+    InvalidLink(#[from] LinkError),
+    #[error(transparent)]
+    IllegalTransition(#[from] IllegalTransition),
+}
+
+impl ConfirmationError {
+    fn status(&self) -> StatusCode {
+        match self {
+            Self::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::UnknownToken => StatusCode::NOT_FOUND,
+            Self::MissingToken => StatusCode::BAD_REQUEST,
+            Self::InvalidLink(e) => link_error_status(e),
+            // e.g. an old link followed after unsubscribing.
+            Self::IllegalTransition(_) => StatusCode::CONFLICT,
+        }
+    }
+
+    fn page(&self) -> ConfirmationPage {
+        match self {
+            Self::UnexpectedError(_) => ConfirmationPage::Failed,
+            Self::InvalidLink(LinkError::Expired) => ConfirmationPage::Expired,
+            _ => ConfirmationPage::Invalid,
+        }
+    }
+
+    fn log(&self) {
+        match self {
+            Self::UnexpectedError(error) => tracing::error!("Got an unexpected one: {:?}", error),
+            Self::InvalidLink(e) => tracing::warn!("Refused a confirmation link: {}", e),
+            Self::IllegalTransition(e) => tracing::warn!("Refused to confirm: {}", e),
+            Self::UnknownToken | Self::MissingToken => {}
+        }
+    }
 }
 
 impl IntoResponse for ConfirmationError {
@@ -30,67 +73,116 @@ impl IntoResponse for ConfirmationError {
         #[derive(serde::Serialize)]
         struct Error {
             message: String,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            request_id: Option<RequestId>,
         }
 
-        let (status, message) = match self {
-            ConfirmationError::UnexpectedError(error) => {
-                tracing::error!("Got an unexpected one: {}", error);
-                (StatusCode::INTERNAL_SERVER_ERROR, error.to_string())
-            }
-            ConfirmationError::UnknownToken => (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "Achtung, unknown token error".to_string(),
-            ),
+        self.log();
+        let english = self.to_string();
+        let message = match &self {
+            Self::UnexpectedError(_) => localize("error-unexpected", &[], || english),
+            Self::UnknownToken => localize("error-unknown-token", &[], || english),
+            Self::MissingToken => localize("error-missing-token", &[], || english),
+            Self::InvalidLink(e) => link_error_message(e),
+            Self::IllegalTransition(_) => localize("error-status-conflict", &[], || english),
         };
-
-        (status, Json(Error { message })).into_response()
+        (
+            self.status(),
+            Json(Error {
+                message,
+                request_id: RequestId::current(),
+            }),
+        )
+            .into_response()
     }
 }
 
-#[tracing::instrument(name = "Confirm a pending subscriber", skip(parameters, db))]
-pub async fn confirm(
-    State(db): State<Arc<Database>>,
-    Query(parameters): Query<Parameters>,
-) -> Result<Response, ConfirmationError> {
-    let subscriber_id = get_subscriber_id_from_token(&db.pool, &parameters.subscription_token)
-        .await
-        .context("Failed to get subscriber id.")?
-        .ok_or(ConfirmationError::UnknownToken)?;
-
-    confirm_subscriber(&db.pool, subscriber_id)
-        .await
-        .context("Failed to confirm subscriber.")?;
-
-    Ok(StatusCode::OK.into_response())
+/// Expired links are gone for good, anything else is a bad request.
+pub fn link_error_status(error: &LinkError) -> StatusCode {
+    match error {
+        LinkError::Expired => StatusCode::GONE,
+        _ => StatusCode::BAD_REQUEST,
+    }
 }
 
-#[tracing::instrument(name = "Get subscriber ID from token", skip(pool, subscription_token))]
-pub async fn get_subscriber_id_from_token(
-    pool: &PgPool,
-    subscription_token: &str,
-) -> Result<Option<Uuid>, sqlx::Error> {
-    let subscriber_id = sqlx::query!(
-        r#"
-          SELECT subscriber_id FROM subscription_tokens WHERE subscription_token = $1
-        "#,
-        &subscription_token
-    )
-    .fetch_optional(pool)
-    .await?;
+/// Doesn't tell a forged link from a mistyped one, that's for the logs.
+pub fn link_error_message(error: &LinkError) -> String {
+    let id = match error {
+        LinkError::Expired => "error-expired-link",
+        _ => "error-invalid-link",
+    };
+    localize(id, &[], || error.to_string())
+}
 
-    Ok(subscriber_id.map(|id| id.subscriber_id))
+/// Browsers get an HTML page, or a redirect after a success if one is configured. Everyone
+/// else gets JSON.
+#[tracing::instrument(
+    name = "Confirm a pending subscriber",
+    skip(parameters, subscribers, links, pages, headers)
+)]
+pub async fn confirm(
+    State(subscribers): State<Arc<dyn SubscriberRepository>>,
+    State(links): State<Arc<LinkSigner>>,
+    State(pages): State<Arc<ConfirmationPages>>,
+    headers: HeaderMap,
+    Query(parameters): Query<Parameters>,
+) -> Response {
+    let result = confirm_subscriber(subscribers.as_ref(), &links, parameters).await;
+    let mut response = if !prefers_html(&headers) {
+        match result {
+            Ok(page) => {
+                let status = match page {
+                    ConfirmationPage::AlreadyConfirmed => "already_confirmed",
+                    _ => "confirmed",
+                };
+                Json(serde_json::json!({ "status": status })).into_response()
+            }
+            Err(error) => error.into_response(),
+        }
+    } else {
+        match result {
+            Ok(page) => pages.respond(page, StatusCode::OK, &headers),
+            Err(error) => {
+                error.log();
+                pages.respond(error.page(), error.status(), &headers)
+            }
+        }
+    };
+    // So caches don't hand a browser's page to an API client, or the other way round.
+    response
+        .headers_mut()
+        .insert(VARY, HeaderValue::from_static("accept"));
+    response
 }
 
-#[tracing::instrument(name = "Mark subscriber as confirmed", skip(pool))]
-pub async fn confirm_subscriber(pool: &PgPool, subscriber_id: Uuid) -> Result<(), sqlx::Error> {
-    let _ = sqlx::query!(
-        r#"
-          UPDATE subscriptions SET status = 'confirmed' WHERE id = $1
-        "#,
-        subscriber_id
-    )
-    .execute(pool)
-    .await?;
+/// `Confirmed` or `AlreadyConfirmed`.
+async fn confirm_subscriber(
+    subscribers: &dyn SubscriberRepository,
+    links: &LinkSigner,
+    parameters: Parameters,
+) -> Result<ConfirmationPage, ConfirmationError> {
+    // Signed links carry the subscriber id, no need to look anything up.
+    let subscriber_id = match (parameters.link, parameters.subscription_token) {
+        (Some(link), _) => links.verify(LinkAction::Confirm, &link)?,
+        (None, Some(token)) => subscribers
+            .subscriber_id_from_token(&token)
+            .await
+            .context("Failed to get subscriber id.")?
+            .ok_or(ConfirmationError::UnknownToken)?,
+        (None, None) => return Err(ConfirmationError::MissingToken),
+    };
 
-    Ok(())
+    match subscribers
+        .change_status(subscriber_id, SubscriptionStatus::Confirmed)
+        .await
+    {
+        Ok(SubscriptionStatus::Confirmed) => Ok(ConfirmationPage::AlreadyConfirmed),
+        Ok(_) => Ok(ConfirmationPage::Confirmed),
+        Err(StatusChangeError::IllegalTransition(e)) => Err(e.into()),
+        // The subscriber was removed after the link was sent.
+        Err(StatusChangeError::UnknownSubscriber) => Err(ConfirmationError::UnknownToken),
+        Err(e) => Err(anyhow::Error::from(e)
+            .context("Failed to confirm subscriber.")
+            .into()),
+    }
 }
@@ -0,0 +1,126 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Form, Json,
+};
+use tracing::Instrument;
+
+use super::subscriptions::{
+    parse_email, reissue_confirmation, send_confirmation_email, SubscribeError,
+};
+use crate::{
+    database::subscribers::SubscriberRepository,
+    domain::SubscriptionStatus,
+    i18n::{middleware::set_locale, Localizer},
+    metrics::{EmailKind, Metrics},
+    rate_limit::middleware::RateLimits,
+    ses_workflow::SESWorkflow,
+    signed_links::LinkSigner,
+};
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    email: String,
+    #[serde(default)]
+    locale: Option<String>,
+}
+
+/// Sends a pending subscriber a new confirmation link.
+///
+/// Answers the same whether the address is pending, confirmed, cooling down or unknown, so
+/// the endpoint can't be used to find out who is subscribed.
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(
+    name = "Resending a confirmation email",
+    skip(
+        subscribers,
+        links,
+        ses_client,
+        localizer,
+        metrics,
+        rate_limits,
+        base_url,
+        headers,
+        form
+    )
+)]
+pub async fn resend_confirmation(
+    State(subscribers): State<Arc<dyn SubscriberRepository>>,
+    State(links): State<Arc<LinkSigner>>,
+    State(ses_client): State<Arc<SESWorkflow>>,
+    State(localizer): State<Arc<Localizer>>,
+    State(metrics): State<Arc<Metrics>>,
+    State(rate_limits): State<RateLimits>,
+    Extension(base_url): Extension<Arc<String>>,
+    headers: HeaderMap,
+    Form(form): Form<FormData>,
+) -> Result<Response, SubscribeError> {
+    let locale = localizer.negotiate_request(form.locale.as_deref(), &headers);
+    set_locale(locale);
+    let response = (
+        StatusCode::OK,
+        Json(serde_json::json!({
+            "message": localizer.message(locale, "resend-confirmation-success", &[])
+        })),
+    )
+        .into_response();
+
+    let email = parse_email(form.email)?;
+    let subscriber = subscribers
+        .subscriber_by_email(&email)
+        .await
+        .context("Failed to look the subscriber up.")?;
+    let Some(subscriber) =
+        subscriber.filter(|s| s.status == SubscriptionStatus::PendingConfirmation)
+    else {
+        return Ok(response);
+    };
+
+    // Answer before the cooldown is recorded and the new link goes out: waiting for either
+    // would make pending addresses measurably slower than the rest.
+    let cooldown = rate_limits.resend_cooldown.clone();
+    tokio::spawn(
+        async move {
+            match cooldown.start(email.canonical()).await {
+                Ok(true) => {}
+                Ok(false) => {
+                    tracing::info!("The address is cooling down, not resending");
+                    return;
+                }
+                Err(error) => {
+                    // Fail open, like the route limits.
+                    tracing::error!(
+                        error.cause_chain = ?error,
+                        "Failed to record a resend, sending anyway"
+                    );
+                }
+            }
+
+            let sent = async {
+                let confirmation_link =
+                    reissue_confirmation(subscribers.as_ref(), &links, &base_url, subscriber.id)
+                        .await?;
+                send_confirmation_email(
+                    ses_client,
+                    &localizer,
+                    &subscriber.locale,
+                    subscriber.email,
+                    &confirmation_link,
+                )
+                .await
+            }
+            .await;
+            metrics.record_email(EmailKind::Confirmation, &sent);
+            if let Err(error) = sent {
+                tracing::error!(error.cause_chain = ?error, "Failed to resend a confirmation email");
+            }
+        }
+        .in_current_span(),
+    );
+
+    Ok(response)
+}
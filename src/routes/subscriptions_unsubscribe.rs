@@ -0,0 +1,170 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    http::{header::VARY, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+
+use super::{
+    confirmation_page::{prefers_html, ConfirmationPage, ConfirmationPages},
+    subscriptions_confirm::{link_error_message, link_error_status},
+};
+use crate::{
+    database::subscribers::{StatusChangeError, SubscriberRepository},
+    domain::{IllegalTransition, SubscriptionStatus},
+    i18n::middleware::localize,
+    request_id::RequestId,
+    signed_links::{LinkAction, LinkError, LinkSigner},
+};
+
+#[derive(serde::Deserialize)]
+pub struct Parameters {
+    link: String,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum UnsubscribeError {
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+    #[error("There is no subscriber associated with the provided link.")]
+    UnknownSubscriber,
+    #[error(transparent)]
+    InvalidLink(#[from] LinkError),
+    #[error(transparent)]
+    IllegalTransition(#[from] IllegalTransition),
+}
+
+impl UnsubscribeError {
+    fn status(&self) -> StatusCode {
+        match self {
+            Self::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::UnknownSubscriber => StatusCode::NOT_FOUND,
+            Self::InvalidLink(e) => link_error_status(e),
+            // e.g. a subscriber whose address bounced.
+            Self::IllegalTransition(_) => StatusCode::CONFLICT,
+        }
+    }
+
+    fn page(&self) -> ConfirmationPage {
+        match self {
+            Self::UnexpectedError(_) => ConfirmationPage::UnsubscribeFailed,
+            Self::InvalidLink(_) => ConfirmationPage::InvalidUnsubscribeLink,
+            Self::UnknownSubscriber | Self::IllegalTransition(_) => ConfirmationPage::NotSubscribed,
+        }
+    }
+
+    fn log(&self) {
+        match self {
+            Self::UnexpectedError(error) => tracing::error!("Got an unexpected one: {:?}", error),
+            Self::InvalidLink(e) => tracing::warn!("Refused an unsubscribe link: {}", e),
+            Self::IllegalTransition(e) => tracing::warn!("Refused to unsubscribe: {}", e),
+            Self::UnknownSubscriber => {}
+        }
+    }
+
+    /// Browsers get a page, everyone else JSON.
+    fn respond(self, pages: &ConfirmationPages, headers: &HeaderMap) -> Response {
+        if prefers_html(headers) {
+            self.log();
+            pages.respond(self.page(), self.status(), headers)
+        } else {
+            self.into_response()
+        }
+    }
+}
+
+impl IntoResponse for UnsubscribeError {
+    fn into_response(self) -> Response {
+        #[derive(serde::Serialize)]
+        struct Error {
+            message: String,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            request_id: Option<RequestId>,
+        }
+
+        self.log();
+        let english = self.to_string();
+        let message = match &self {
+            Self::UnexpectedError(_) => localize("error-unexpected", &[], || english),
+            Self::UnknownSubscriber => localize("error-unknown-subscriber", &[], || english),
+            Self::InvalidLink(e) => link_error_message(e),
+            Self::IllegalTransition(_) => localize("error-status-conflict", &[], || english),
+        };
+        (
+            self.status(),
+            Json(Error {
+                message,
+                request_id: RequestId::current(),
+            }),
+        )
+            .into_response()
+    }
+}
+
+/// So caches don't hand a browser's page to an API client, or the other way round.
+fn vary_on_accept(mut response: Response) -> Response {
+    response
+        .headers_mut()
+        .insert(VARY, HeaderValue::from_static("accept"));
+    response
+}
+
+/// Following the link only shows a form, unsubscribing takes a POST.
+#[tracing::instrument(
+    name = "Show the unsubscribe page",
+    skip(parameters, links, pages, headers)
+)]
+pub async fn unsubscribe_page(
+    State(links): State<Arc<LinkSigner>>,
+    State(pages): State<Arc<ConfirmationPages>>,
+    headers: HeaderMap,
+    Query(parameters): Query<Parameters>,
+) -> Response {
+    let response = match links.verify(LinkAction::Unsubscribe, &parameters.link) {
+        Ok(_) => pages.unsubscribe_form(&parameters.link, &headers),
+        Err(e) => UnsubscribeError::from(e).respond(&pages, &headers),
+    };
+    vary_on_accept(response)
+}
+
+/// Both the page's form and mail clients' one-click unsubscribe (RFC 8058) end up here.
+#[tracing::instrument(
+    name = "Unsubscribe a subscriber",
+    skip(parameters, subscribers, links, pages, headers)
+)]
+pub async fn unsubscribe(
+    State(subscribers): State<Arc<dyn SubscriberRepository>>,
+    State(links): State<Arc<LinkSigner>>,
+    State(pages): State<Arc<ConfirmationPages>>,
+    headers: HeaderMap,
+    Query(parameters): Query<Parameters>,
+) -> Response {
+    let response = match unsubscribe_subscriber(subscribers.as_ref(), &links, parameters).await {
+        Ok(()) if prefers_html(&headers) => pages.unsubscribed(&headers),
+        Ok(()) => StatusCode::OK.into_response(),
+        Err(error) => error.respond(&pages, &headers),
+    };
+    vary_on_accept(response)
+}
+
+async fn unsubscribe_subscriber(
+    subscribers: &dyn SubscriberRepository,
+    links: &LinkSigner,
+    parameters: Parameters,
+) -> Result<(), UnsubscribeError> {
+    let subscriber_id = links.verify(LinkAction::Unsubscribe, &parameters.link)?;
+
+    match subscribers
+        .change_status(subscriber_id, SubscriptionStatus::Unsubscribed)
+        .await
+    {
+        Ok(_) => Ok(()),
+        Err(StatusChangeError::UnknownSubscriber) => Err(UnsubscribeError::UnknownSubscriber),
+        Err(StatusChangeError::IllegalTransition(e)) => Err(e.into()),
+        Err(StatusChangeError::UnexpectedError(e)) => {
+            Err(e.context("Failed to unsubscribe subscriber.").into())
+        }
+    }
+}
@@ -1,10 +1,22 @@
-use anyhow::anyhow;
+use anyhow::{anyhow, Context};
 use aws_sdk_sesv2::{
-    types::{Body, Content, Destination, EmailContent, Message},
+    config::{
+        http::HttpRequest, interceptors::BeforeTransmitInterceptorContextMut, ConfigBag, Intercept,
+        RuntimeComponents,
+    },
+    error::{BoxError, SdkError},
+    operation::send_email::SendEmailError,
+    types::{Body, Content, Destination, EmailContent, Message, MessageHeader, MessageTag},
     Client,
 };
+use opentelemetry::propagation::Injector;
 
-use crate::domain::SubscriberEmail;
+use crate::{domain::SubscriberEmail, request_id::RequestId, telemetry::inject_trace_context};
+
+/// Attached to send errors caused by SES sending quotas.
+#[derive(Debug, thiserror::Error)]
+#[error("SES is throttling our sends")]
+pub struct Throttled;
 
 pub struct SESWorkflow {
     client: Client,
@@ -19,12 +31,60 @@ impl SESWorkflow {
         }
     }
 
+    #[tracing::instrument(name = "Sending an email via SES", skip_all)]
     pub async fn send_email(
         &self,
         recipient: &SubscriberEmail,
         subject: &str,
         text_content: &str,
         html_content: &str,
+    ) -> Result<(), anyhow::Error> {
+        self.send(recipient, subject, text_content, html_content, vec![])
+            .await
+    }
+
+    /// Like `send_email`, with the headers mail clients need to offer one-click
+    /// unsubscribing (RFC 8058). `unsubscribe_url` has to accept a POST.
+    #[tracing::instrument(name = "Sending a list email via SES", skip_all)]
+    pub async fn send_list_email(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        text_content: &str,
+        html_content: &str,
+        unsubscribe_url: &str,
+    ) -> Result<(), anyhow::Error> {
+        let headers = vec![
+            MessageHeader::builder()
+                .name("List-Unsubscribe")
+                .value(format!("<{}>", unsubscribe_url))
+                .build()?,
+            MessageHeader::builder()
+                .name("List-Unsubscribe-Post")
+                .value("List-Unsubscribe=One-Click")
+                .build()?,
+        ];
+        self.send(recipient, subject, text_content, html_content, headers)
+            .await
+    }
+
+    /// Cheapest authenticated call SES offers, used by the readiness probe.
+    pub async fn check_reachable(&self) -> Result<(), anyhow::Error> {
+        self.client
+            .get_account()
+            .send()
+            .await
+            .context("SES is unreachable")?;
+        Ok(())
+    }
+
+    async fn send(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        text_content: &str,
+        html_content: &str,
+        headers: Vec<MessageHeader>,
     ) -> Result<(), anyhow::Error> {
         let email_content = EmailContent::builder()
             .simple(
@@ -36,13 +96,25 @@ impl SESWorkflow {
                             .text(Content::builder().data(text_content).build()?)
                             .build(),
                     )
+                    .set_headers(Some(headers).filter(|headers| !headers.is_empty()))
                     .build(),
             )
             .build();
 
+        // Lets support find the request behind a bounce or complaint.
+        let request_id_tag = RequestId::current()
+            .map(|id| {
+                MessageTag::builder()
+                    .name("request_id")
+                    .value(id.as_str())
+                    .build()
+            })
+            .transpose()?;
+
         let res = self
             .client
             .send_email()
+            .set_email_tags(request_id_tag.map(|tag| vec![tag]))
             .from_email_address(self.verified_email.as_ref())
             .destination(
                 Destination::builder()
@@ -50,6 +122,8 @@ impl SESWorkflow {
                     .build(),
             )
             .content(email_content)
+            .customize()
+            .interceptor(TraceContextInterceptor)
             .send()
             .await;
 
@@ -62,28 +136,83 @@ impl SESWorkflow {
                     Err(anyhow!("Message sent, but no message ID was returned"))
                 }
             }
-            Err(e) => Err(anyhow!(
-                "Error sending welcome email to {}: {:?}",
-                recipient.as_ref(),
-                e
-            )),
+            Err(e) => Err(send_error(recipient, e)),
         }
     }
 }
 
+/// Forwards the trace context of the calling span as `traceparent` on SES requests.
+#[derive(Debug)]
+struct TraceContextInterceptor;
+
+impl Intercept for TraceContextInterceptor {
+    fn name(&self) -> &'static str {
+        "TraceContextInterceptor"
+    }
+
+    fn modify_before_signing(
+        &self,
+        context: &mut BeforeTransmitInterceptorContextMut<'_>,
+        _runtime_components: &RuntimeComponents,
+        _cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        inject_trace_context(&mut RequestHeaders(context.request_mut()));
+        Ok(())
+    }
+}
+
+struct RequestHeaders<'a>(&'a mut HttpRequest);
+
+impl Injector for RequestHeaders<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        if let Err(e) = self.0.headers_mut().try_insert(key.to_string(), value) {
+            tracing::warn!("Failed to forward trace context header {}: {}", key, e);
+        }
+    }
+}
+
+/// Marks the error with [`Throttled`] when SES refused the send because of a quota.
+fn send_error<R: std::fmt::Debug>(
+    recipient: &SubscriberEmail,
+    e: SdkError<SendEmailError, R>,
+) -> anyhow::Error {
+    let throttled = is_throttling(&e);
+    let error = anyhow!("Error sending email to {}: {:?}", recipient.as_ref(), e);
+    if throttled {
+        error.context(Throttled)
+    } else {
+        error
+    }
+}
+
+fn is_throttling<R>(error: &SdkError<SendEmailError, R>) -> bool {
+    matches!(
+        error.as_service_error(),
+        Some(
+            SendEmailError::TooManyRequestsException(_) | SendEmailError::LimitExceededException(_)
+        )
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use std::sync::{Arc, Mutex};
 
     use anyhow::Result;
     use aws_sdk_sesv2::{
+        error::SdkError,
         operation::send_email::{SendEmailError, SendEmailOutput},
-        types::error::MailFromDomainNotVerifiedException,
+        types::error::{
+            LimitExceededException, MailFromDomainNotVerifiedException, TooManyRequestsException,
+        },
         Client,
     };
     use aws_smithy_mocks_experimental::{mock, mock_client, RuleMode};
 
-    use crate::{domain::SubscriberEmail, ses_workflow::SESWorkflow};
+    use crate::{
+        domain::SubscriberEmail,
+        ses_workflow::{is_throttling, send_error, SESWorkflow, Throttled},
+    };
 
     #[tokio::test]
     async fn send_email_successes() -> Result<()> {
@@ -151,13 +280,55 @@ mod tests {
 
         // Check that the error is propagated
         assert!(result.is_err());
-        assert!(
-            format!("{result:?}").contains("Error sending welcome email to recipient@example.com:")
-        );
+        assert!(format!("{result:?}").contains("Error sending email to recipient@example.com:"));
 
         Ok(())
     }
 
+    // Built by hand: the mock client's `then_error` panics in the orchestrator.
+    fn service_error(error: SendEmailError) -> SdkError<SendEmailError, ()> {
+        SdkError::service_error(error, ())
+    }
+
+    #[test]
+    fn throttled_sends_are_marked_as_such() {
+        let recipient = SubscriberEmail::parse("recipient@example.com".to_string()).unwrap();
+
+        let throttled = send_error(
+            &recipient,
+            service_error(SendEmailError::TooManyRequestsException(
+                TooManyRequestsException::builder().build(),
+            )),
+        );
+        let rejected = send_error(
+            &recipient,
+            service_error(SendEmailError::MailFromDomainNotVerifiedException(
+                MailFromDomainNotVerifiedException::builder().build(),
+            )),
+        );
+
+        assert!(throttled.downcast_ref::<Throttled>().is_some());
+        assert!(rejected.downcast_ref::<Throttled>().is_none());
+    }
+
+    #[test]
+    fn only_quota_errors_count_as_throttling() {
+        assert!(is_throttling(&service_error(
+            SendEmailError::TooManyRequestsException(TooManyRequestsException::builder().build())
+        )));
+        assert!(is_throttling(&service_error(
+            SendEmailError::LimitExceededException(LimitExceededException::builder().build())
+        )));
+        assert!(!is_throttling(&service_error(
+            SendEmailError::MailFromDomainNotVerifiedException(
+                MailFromDomainNotVerifiedException::builder().build(),
+            )
+        )));
+        assert!(!is_throttling(
+            &SdkError::<SendEmailError, ()>::timeout_error("SES took too long")
+        ));
+    }
+
     #[tokio::test]
     async fn send_email_sends_expected_content() -> Result<()> {
         let captured_request_content = Arc::new(Mutex::new(None));
@@ -0,0 +1,166 @@
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+};
+
+use tokio::{sync::watch, task::JoinHandle, time::Instant};
+
+type Workers = Vec<(&'static str, JoinHandle<()>)>;
+
+/// Fans a shutdown request out to the HTTP server and background workers.
+#[derive(Clone)]
+pub struct Shutdown {
+    sender: Arc<watch::Sender<bool>>,
+    workers: Arc<Mutex<Workers>>,
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self {
+            sender: Arc::new(watch::channel(false).0),
+            workers: Arc::default(),
+        }
+    }
+
+    pub fn trigger(&self) {
+        self.sender.send_replace(true);
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        *self.sender.borrow()
+    }
+
+    /// Resolves once shutdown has been requested, right away if it already was.
+    pub async fn triggered(&self) {
+        let mut receiver = self.sender.subscribe();
+        // The sender lives as long as `self`, so this can't fail.
+        let _ = receiver.wait_for(|triggered| *triggered).await;
+    }
+
+    /// Runs a background worker that is expected to return once `triggered` resolves.
+    pub fn spawn_worker<F, Fut>(&self, name: &'static str, worker: F)
+    where
+        F: FnOnce(Shutdown) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let handle = tokio::spawn(worker(self.clone()));
+        self.workers.lock().unwrap().push((name, handle));
+    }
+
+    /// Waits for the workers until `deadline` and aborts the rest.
+    /// Returns the names of the workers that had to be aborted.
+    pub async fn stop_workers(&self, deadline: Instant) -> Vec<&'static str> {
+        self.trigger();
+        let workers = std::mem::take(&mut *self.workers.lock().unwrap());
+
+        let mut unfinished = vec![];
+        for (name, mut handle) in workers {
+            match tokio::time::timeout_at(deadline, &mut handle).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => tracing::error!(worker = name, "Worker crashed: {:?}", e),
+                Err(_) => {
+                    handle.abort();
+                    unfinished.push(name);
+                }
+            }
+        }
+        unfinished
+    }
+}
+
+/// Resolves on SIGINT (Ctrl+C) or SIGTERM.
+pub async fn os_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to install the Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install the SIGTERM handler")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+/// What was still going on when the drain timeout ran out.
+#[derive(Debug, Default, PartialEq)]
+pub struct ShutdownSummary {
+    pub requests_cut_off: i64,
+    pub deliveries_not_sent: i64,
+    pub workers_aborted: Vec<&'static str>,
+}
+
+impl std::fmt::Display for ShutdownSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if *self == Self::default() {
+            return write!(f, "everything finished in time");
+        }
+        write!(
+            f,
+            "{} request(s) cut off, {} newsletter delivery(ies) not sent, workers aborted: [{}]",
+            self.requests_cut_off,
+            self.deliveries_not_sent,
+            self.workers_aborted.join(", ")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::time::Instant;
+
+    use super::{Shutdown, ShutdownSummary};
+
+    #[tokio::test]
+    async fn cooperative_workers_stop_in_time() {
+        let shutdown = Shutdown::new();
+        shutdown.spawn_worker("reminders", |shutdown| async move {
+            shutdown.triggered().await;
+        });
+
+        let aborted = shutdown
+            .stop_workers(Instant::now() + Duration::from_secs(1))
+            .await;
+
+        assert!(aborted.is_empty());
+        assert!(shutdown.is_triggered());
+    }
+
+    #[tokio::test]
+    async fn workers_ignoring_the_signal_are_aborted_at_the_deadline() {
+        let shutdown = Shutdown::new();
+        shutdown.spawn_worker("stuck", |_| std::future::pending());
+
+        let aborted = shutdown
+            .stop_workers(Instant::now() + Duration::from_millis(20))
+            .await;
+
+        assert_eq!(aborted, vec!["stuck"]);
+    }
+
+    #[test]
+    fn a_clean_shutdown_says_so() {
+        assert_eq!(
+            ShutdownSummary::default().to_string(),
+            "everything finished in time"
+        );
+    }
+}
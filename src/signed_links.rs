@@ -0,0 +1,405 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, SecretString};
+use sha2::Sha256;
+use uuid::Uuid;
+
+use crate::configuration::config::SignedLinksConfiguration;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// What a link lets its holder do. It is signed along with the rest, so a link made for
+/// one action can't be replayed for another. New kinds of links get their own variant.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LinkAction {
+    Confirm,
+    Unsubscribe,
+    /// Opens an issue in the browser, bound to the issue id.
+    ViewInBrowser,
+    /// Counts a click on a link in an issue before redirecting, bound to the target URL.
+    Track,
+}
+
+impl LinkAction {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Confirm => "confirm",
+            Self::Unsubscribe => "unsubscribe",
+            Self::ViewInBrowser => "view",
+            Self::Track => "track",
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum LinkError {
+    #[error("The link is malformed.")]
+    Malformed,
+    #[error("The link was signed with a key that is no longer accepted.")]
+    UnknownKey,
+    #[error("The link signature does not match.")]
+    BadSignature,
+    #[error("The link has expired.")]
+    Expired,
+}
+
+/// Signs and checks links that carry a subscriber id and an expiry, so handlers can trust
+/// them without a database lookup.
+///
+/// A link token looks like `<key id>.<subscriber id>.<expires at>.<hex hmac>`. Every
+/// configured key verifies but only `signing_key` signs, so keys can rotate without breaking
+/// links already sent. Links about something besides the subscriber, an issue or a URL, are
+/// signed `bound_to` it: the value travels next to the token and has to match to verify.
+pub struct LinkSigner {
+    keys: HashMap<String, SecretString>,
+    signing_key: Option<String>,
+    confirmation_links: bool,
+    confirm_ttl: Duration,
+    unsubscribe_ttl: Duration,
+    track_clicks: bool,
+}
+
+impl LinkSigner {
+    pub fn new(configuration: &SignedLinksConfiguration) -> Self {
+        Self {
+            keys: configuration.keys.clone(),
+            signing_key: configuration.signing_key.clone(),
+            confirmation_links: configuration.confirmation_links,
+            confirm_ttl: Duration::from_secs(configuration.confirm_ttl_seconds),
+            unsubscribe_ttl: Duration::from_secs(configuration.unsubscribe_ttl_seconds),
+            track_clicks: configuration.track_clicks,
+        }
+    }
+
+    /// Whether links can be signed at all, i.e. a signing key is configured.
+    pub fn signs_links(&self) -> bool {
+        self.signing_key.is_some()
+    }
+
+    /// Whether confirmation emails carry a signed link instead of a stored token.
+    pub fn signs_confirmation_links(&self) -> bool {
+        self.confirmation_links && self.signs_links()
+    }
+
+    /// `None` without a signing key.
+    pub fn confirmation_url(&self, base_url: &str, subscriber_id: Uuid) -> Option<String> {
+        let link = self.sign(LinkAction::Confirm, subscriber_id, self.confirm_ttl)?;
+        Some(format!("{}/subscriptions/confirm?link={}", base_url, link))
+    }
+
+    /// `None` without a signing key.
+    pub fn unsubscribe_url(&self, base_url: &str, subscriber_id: Uuid) -> Option<String> {
+        let link = self.sign(LinkAction::Unsubscribe, subscriber_id, self.unsubscribe_ttl)?;
+        Some(format!(
+            "{}/subscriptions/unsubscribe?link={}",
+            base_url, link
+        ))
+    }
+
+    /// Whether links in issues go through `tracking_url`.
+    pub fn tracks_clicks(&self) -> bool {
+        self.track_clicks && self.signs_links()
+    }
+
+    /// Opens `issue_id` in the browser, in the subscriber's `locale`. Lasts as long as
+    /// unsubscribe links, it sits in the same issue. `None` without a signing key.
+    pub fn view_in_browser_url(
+        &self,
+        base_url: &str,
+        subscriber_id: Uuid,
+        issue_id: Uuid,
+        locale: &str,
+    ) -> Option<String> {
+        let issue_id = issue_id.simple().to_string();
+        let link = self.sign_bound(
+            LinkAction::ViewInBrowser,
+            subscriber_id,
+            &issue_id,
+            self.unsubscribe_ttl,
+        )?;
+        let query = serde_urlencoded::to_string([("locale", locale), ("link", &link)]).ok()?;
+        Some(format!(
+            "{}/newsletters/issues/{}?{}",
+            base_url, issue_id, query
+        ))
+    }
+
+    /// Redirects to `target` once the click is counted. `None` without a signing key.
+    pub fn tracking_url(
+        &self,
+        base_url: &str,
+        subscriber_id: Uuid,
+        target: &str,
+    ) -> Option<String> {
+        let link = self.sign_bound(
+            LinkAction::Track,
+            subscriber_id,
+            target,
+            self.unsubscribe_ttl,
+        )?;
+        let query = serde_urlencoded::to_string([("url", target), ("link", &link)]).ok()?;
+        Some(format!("{}/newsletters/click?{}", base_url, query))
+    }
+
+    pub fn sign(&self, action: LinkAction, subscriber_id: Uuid, ttl: Duration) -> Option<String> {
+        self.sign_at(action, subscriber_id, now() + ttl.as_secs())
+    }
+
+    pub fn sign_at(
+        &self,
+        action: LinkAction,
+        subscriber_id: Uuid,
+        expires_at: u64,
+    ) -> Option<String> {
+        self.sign_bound_at(action, subscriber_id, "", expires_at)
+    }
+
+    pub fn sign_bound(
+        &self,
+        action: LinkAction,
+        subscriber_id: Uuid,
+        bound_to: &str,
+        ttl: Duration,
+    ) -> Option<String> {
+        self.sign_bound_at(action, subscriber_id, bound_to, now() + ttl.as_secs())
+    }
+
+    pub fn sign_bound_at(
+        &self,
+        action: LinkAction,
+        subscriber_id: Uuid,
+        bound_to: &str,
+        expires_at: u64,
+    ) -> Option<String> {
+        let key_id = self.signing_key.as_ref()?;
+        let secret = self.keys.get(key_id)?;
+        let signature = mac(secret, action, subscriber_id, bound_to, expires_at)
+            .finalize()
+            .into_bytes();
+        Some(format!(
+            "{}.{}.{}.{}",
+            key_id,
+            subscriber_id.simple(),
+            expires_at,
+            hex::encode(signature)
+        ))
+    }
+
+    /// The subscriber the link was signed for.
+    pub fn verify(&self, action: LinkAction, link: &str) -> Result<Uuid, LinkError> {
+        self.verify_at(action, link, now())
+    }
+
+    pub fn verify_at(&self, action: LinkAction, link: &str, now: u64) -> Result<Uuid, LinkError> {
+        self.verify_bound_at(action, link, "", now)
+    }
+
+    /// The subscriber the link was signed for, if it was signed for `bound_to`.
+    pub fn verify_bound(
+        &self,
+        action: LinkAction,
+        link: &str,
+        bound_to: &str,
+    ) -> Result<Uuid, LinkError> {
+        self.verify_bound_at(action, link, bound_to, now())
+    }
+
+    pub fn verify_bound_at(
+        &self,
+        action: LinkAction,
+        link: &str,
+        bound_to: &str,
+        now: u64,
+    ) -> Result<Uuid, LinkError> {
+        let mut parts = link.split('.');
+        let (Some(key_id), Some(subscriber_id), Some(expires_at), Some(signature), None) = (
+            parts.next(),
+            parts.next(),
+            parts.next(),
+            parts.next(),
+            parts.next(),
+        ) else {
+            return Err(LinkError::Malformed);
+        };
+        let subscriber_id = Uuid::try_parse(subscriber_id).map_err(|_| LinkError::Malformed)?;
+        let expires_at: u64 = expires_at.parse().map_err(|_| LinkError::Malformed)?;
+        let signature = hex::decode(signature).map_err(|_| LinkError::Malformed)?;
+        let secret = self.keys.get(key_id).ok_or(LinkError::UnknownKey)?;
+
+        // `verify_slice` compares in constant time.
+        mac(secret, action, subscriber_id, bound_to, expires_at)
+            .verify_slice(&signature)
+            .map_err(|_| LinkError::BadSignature)?;
+
+        if now > expires_at {
+            return Err(LinkError::Expired);
+        }
+        Ok(subscriber_id)
+    }
+}
+
+fn mac(
+    secret: &SecretString,
+    action: LinkAction,
+    subscriber_id: Uuid,
+    bound_to: &str,
+    expires_at: u64,
+) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(secret.expose_secret().as_bytes())
+        .expect("HMAC can take a key of any size");
+    mac.update(format!("{}\n{}\n{}", action.as_str(), subscriber_id, expires_at).as_bytes());
+    // Left out when empty, so confirm and unsubscribe links sent before binding existed
+    // still verify.
+    if !bound_to.is_empty() {
+        mac.update(format!("\n{}", bound_to).as_bytes());
+    }
+    mac
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("System clock is before the UNIX epoch")
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use claim::{assert_none, assert_ok_eq};
+    use secrecy::SecretString;
+    use uuid::Uuid;
+
+    use super::{LinkAction, LinkError, LinkSigner};
+    use crate::configuration::config::SignedLinksConfiguration;
+
+    fn signer(keys: &[(&str, &str)], signing_key: Option<&str>) -> LinkSigner {
+        LinkSigner::new(&SignedLinksConfiguration {
+            confirmation_links: true,
+            keys: keys
+                .iter()
+                .map(|(id, secret)| (id.to_string(), SecretString::from(*secret)))
+                .collect::<HashMap<_, _>>(),
+            signing_key: signing_key.map(String::from),
+            confirm_ttl_seconds: 60,
+            unsubscribe_ttl_seconds: 60,
+            track_clicks: false,
+        })
+    }
+
+    #[test]
+    fn a_link_verifies_until_it_expires() {
+        let signer = signer(&[("2024-12", "secret")], Some("2024-12"));
+        let subscriber_id = Uuid::new_v4();
+        let link = signer
+            .sign_at(LinkAction::Confirm, subscriber_id, 1_000)
+            .unwrap();
+
+        assert_ok_eq!(
+            signer.verify_at(LinkAction::Confirm, &link, 1_000),
+            subscriber_id
+        );
+        assert_eq!(
+            signer.verify_at(LinkAction::Confirm, &link, 1_001),
+            Err(LinkError::Expired)
+        );
+    }
+
+    #[test]
+    fn a_link_only_works_for_its_action() {
+        let signer = signer(&[("2024-12", "secret")], Some("2024-12"));
+        let link = signer
+            .sign_at(LinkAction::Confirm, Uuid::new_v4(), 1_000)
+            .unwrap();
+
+        assert_eq!(
+            signer.verify_at(LinkAction::Unsubscribe, &link, 900),
+            Err(LinkError::BadSignature)
+        );
+    }
+
+    #[test]
+    fn a_bound_link_only_works_for_what_it_was_bound_to() {
+        let signer = signer(&[("2024-12", "secret")], Some("2024-12"));
+        let subscriber_id = Uuid::new_v4();
+        let link = signer
+            .sign_bound_at(
+                LinkAction::Track,
+                subscriber_id,
+                "https://example.com/a",
+                1_000,
+            )
+            .unwrap();
+
+        assert_ok_eq!(
+            signer.verify_bound_at(LinkAction::Track, &link, "https://example.com/a", 900),
+            subscriber_id
+        );
+        assert_eq!(
+            signer.verify_bound_at(LinkAction::Track, &link, "https://evil.example/", 900),
+            Err(LinkError::BadSignature)
+        );
+        assert_eq!(
+            signer.verify_at(LinkAction::Track, &link, 900),
+            Err(LinkError::BadSignature)
+        );
+    }
+
+    #[test]
+    fn a_tampered_link_is_rejected() {
+        let signer = signer(&[("2024-12", "secret")], Some("2024-12"));
+        let link = signer
+            .sign_at(LinkAction::Confirm, Uuid::new_v4(), 1_000)
+            .unwrap()
+            .replacen(".1000.", ".9999.", 1);
+
+        assert_eq!(
+            signer.verify_at(LinkAction::Confirm, &link, 900),
+            Err(LinkError::BadSignature)
+        );
+    }
+
+    #[test]
+    fn links_signed_with_a_retired_key_verify_during_the_rotation() {
+        let old = signer(&[("2024-11", "old secret")], Some("2024-11"));
+        let link = old
+            .sign_at(LinkAction::Confirm, Uuid::new_v4(), 1_000)
+            .unwrap();
+
+        let rotating = signer(
+            &[("2024-11", "old secret"), ("2024-12", "new secret")],
+            Some("2024-12"),
+        );
+        assert!(rotating.verify_at(LinkAction::Confirm, &link, 900).is_ok());
+
+        let rotated = signer(&[("2024-12", "new secret")], Some("2024-12"));
+        assert_eq!(
+            rotated.verify_at(LinkAction::Confirm, &link, 900),
+            Err(LinkError::UnknownKey)
+        );
+    }
+
+    #[test]
+    fn nothing_is_signed_without_a_signing_key() {
+        let signer = signer(&[("2024-12", "secret")], None);
+
+        assert_none!(signer.sign_at(LinkAction::Confirm, Uuid::new_v4(), 1_000));
+        assert!(!signer.signs_confirmation_links());
+    }
+
+    #[test]
+    fn garbage_is_rejected_as_malformed() {
+        let signer = signer(&[("2024-12", "secret")], Some("2024-12"));
+        for link in ["", "2024-12", "2024-12.not-a-uuid.1000.00", "a.b.c.d.e"] {
+            assert_eq!(
+                signer.verify_at(LinkAction::Confirm, link, 900),
+                Err(LinkError::Malformed)
+            );
+        }
+    }
+}
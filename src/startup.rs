@@ -1,25 +1,76 @@
-use aws_config::Region;
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+
+use anyhow::Context;
+use aws_config::{identity::IdentityCache, sts::AssumeRoleProvider, BehaviorVersion, Region};
 use aws_sdk_sesv2::{config::SharedCredentialsProvider, Client};
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo},
+    server::conn::auto::Builder,
+    service::TowerToHyperService,
+};
+use opentelemetry_sdk::trace::TracerProvider;
+use tokio::task::JoinSet;
+use tower::Service;
+use tracing_subscriber::fmt::MakeWriter;
 
 use crate::{
-    configuration::{aws_credentials::StaticCredentials, config::Configuration},
-    telemetry::{get_subscriber, init_subscriber},
+    configuration::{
+        aws_credentials::StaticCredentials,
+        config::{AwsCredentialsMode, Configuration},
+    },
+    metrics::Metrics,
+    shutdown::{os_signal, Shutdown, ShutdownSummary},
+    telemetry::{
+        get_subscriber_with_tracer, init_subscriber, install_tracer_provider, otlp_tracer_provider,
+    },
 };
 
-pub fn configure_sdk_config(
+/// Resolves AWS credentials according to `aws.credentials_mode`. In `static` mode the keys come
+/// from `static_credentials`, keep a clone of it to rotate them later.
+pub async fn configure_sdk_config(
     configuration: &Configuration,
+    static_credentials: Option<StaticCredentials>,
 ) -> Result<aws_config::SdkConfig, anyhow::Error> {
-    let region = Region::new(configuration.aws.region.clone());
+    let aws = &configuration.aws;
+    let region = Region::new(aws.region.clone());
 
-    let credentials_provider = SharedCredentialsProvider::new(StaticCredentials::new(
-        configuration.aws.access_key_id.clone(),
-        configuration.aws.secret_access_key.clone(),
-    ));
+    let mut loader = aws_config::defaults(BehaviorVersion::latest()).region(region.clone());
+    if let Some(endpoint_url) = &aws.endpoint_url {
+        loader = loader.endpoint_url(endpoint_url);
+    }
 
-    let sdk_config = aws_config::SdkConfig::builder()
-        .region(region)
-        .credentials_provider(credentials_provider)
-        .build();
+    let sdk_config = match aws.credentials_mode {
+        AwsCredentialsMode::Static => {
+            let credentials = static_credentials
+                .context("`static` AWS credentials need an access key id and a secret key")?;
+            loader
+                .credentials_provider(credentials)
+                // Reading them is free, and caching would delay rotated keys.
+                .identity_cache(IdentityCache::no_cache())
+                .load()
+                .await
+        }
+        AwsCredentialsMode::DefaultChain => loader.load().await,
+        AwsCredentialsMode::AssumeRole => {
+            let role_arn = aws
+                .role_arn
+                .clone()
+                .context("`assume_role` AWS credentials need a role ARN")?;
+            // The role is assumed with whatever the default chain finds.
+            let base_config = loader.load().await;
+            let mut role = AssumeRoleProvider::builder(role_arn)
+                .session_name(aws.role_session_name.clone())
+                .region(region);
+            if let Some(external_id) = &aws.external_id {
+                role = role.external_id(external_id.clone());
+            }
+            let provider = role.configure(&base_config).build().await;
+            base_config
+                .into_builder()
+                .credentials_provider(SharedCredentialsProvider::new(provider))
+                .build()
+        }
+    };
 
     Ok(sdk_config)
 }
@@ -29,20 +80,46 @@ pub fn create_aws_client(sdk_config: &aws_config::SdkConfig) -> Result<Client, a
     Ok(aws_client)
 }
 
-pub fn init_logging(configuration: &Configuration) -> Result<(), anyhow::Error> {
-    let subscriber = get_subscriber(
-        configuration.application.logger_name.clone(),
+/// Returns the OTLP tracer provider when export is enabled, shut it down before exiting.
+pub fn init_logging<Sink>(
+    configuration: &Configuration,
+    sink: Sink,
+) -> Result<Option<TracerProvider>, anyhow::Error>
+where
+    Sink: for<'a> MakeWriter<'a> + Sync + Send + 'static,
+{
+    let name = configuration.application.logger_name.clone();
+
+    let provider = if configuration.telemetry.otlp_enabled {
+        Some(otlp_tracer_provider(
+            &configuration.telemetry,
+            name.clone(),
+        )?)
+    } else {
+        None
+    };
+    let tracer = provider
+        .clone()
+        .map(|provider| install_tracer_provider(provider, name.clone()));
+
+    let subscriber = get_subscriber_with_tracer(
+        name,
         configuration.application.default_env_filter.clone(),
-        std::io::stdout,
+        sink,
+        tracer,
     );
     init_subscriber(subscriber);
-    Ok(())
+    Ok(provider)
 }
 
+/// Serves until SIGINT/SIGTERM or `shutdown` is triggered, then drains in-flight
+/// requests and stops the workers within `shutdown_timeout_seconds`.
 pub async fn start_server(
     configuration: &Configuration,
     app: axum::Router,
-) -> Result<(), anyhow::Error> {
+    shutdown: Shutdown,
+    metrics: Arc<Metrics>,
+) -> Result<ShutdownSummary, anyhow::Error> {
     let listener = tokio::net::TcpListener::bind(format!(
         "{}:{}",
         configuration.application.host, configuration.application.port
@@ -58,6 +135,82 @@ pub async fn start_server(
 
     tracing::debug!("listening on {}", listener.local_addr().unwrap());
 
-    axum::serve(listener, app).await.unwrap();
-    Ok(())
+    // `axum::serve` detaches its connection tasks, these are kept so they can be aborted.
+    let mut make_service = app.into_make_service_with_connect_info::<SocketAddr>();
+    let mut connections = JoinSet::new();
+    let os_signal = os_signal();
+    tokio::pin!(os_signal);
+    loop {
+        let accepted = tokio::select! {
+            accepted = listener.accept() => accepted,
+            Some(_) = connections.join_next(), if !connections.is_empty() => continue,
+            _ = &mut os_signal => {
+                shutdown.trigger();
+                break;
+            }
+            _ = shutdown.triggered() => break,
+        };
+        let (stream, remote_addr) = match accepted {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                // e.g. out of file descriptors, give the open connections a chance to close.
+                tracing::warn!("Failed to accept a connection: {}", e);
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                continue;
+            }
+        };
+        let service = make_service
+            .call(remote_addr)
+            .await
+            .unwrap_or_else(|never| match never {});
+        let shutdown = shutdown.clone();
+        connections.spawn(async move {
+            let builder = Builder::new(TokioExecutor::new());
+            let connection = builder.serve_connection_with_upgrades(
+                TokioIo::new(stream),
+                TowerToHyperService::new(service),
+            );
+            tokio::pin!(connection);
+
+            // Asks the client to close the connection once shutdown is triggered.
+            let mut draining = false;
+            loop {
+                tokio::select! {
+                    result = connection.as_mut() => {
+                        if let Err(e) = result {
+                            tracing::debug!("Failed to serve a connection: {:#}", e);
+                        }
+                        return;
+                    }
+                    _ = shutdown.triggered(), if !draining => {
+                        draining = true;
+                        connection.as_mut().graceful_shutdown();
+                    }
+                }
+            }
+        });
+    }
+    drop(listener);
+
+    let drain_timeout = Duration::from_secs(configuration.application.shutdown_timeout_seconds);
+    tracing::info!(
+        drain_timeout_seconds = drain_timeout.as_secs(),
+        "Shutting down, draining in-flight requests"
+    );
+    let deadline = tokio::time::Instant::now() + drain_timeout;
+
+    let mut summary = ShutdownSummary::default();
+    let drained = tokio::time::timeout_at(deadline, async {
+        while connections.join_next().await.is_some() {}
+    })
+    .await;
+    if drained.is_err() {
+        // Read before aborting, the handlers' guards reset the gauges when dropped.
+        summary.requests_cut_off = metrics.requests_in_flight();
+        summary.deliveries_not_sent = metrics.deliveries_pending();
+        connections.shutdown().await;
+    }
+    summary.workers_aborted = shutdown.stop_workers(deadline).await;
+
+    Ok(summary)
 }
@@ -2,17 +2,62 @@ use std::sync::Arc;
 
 use axum::extract::FromRef;
 
-use crate::{database::db::Database, ses_workflow::SESWorkflow};
+use crate::{
+    bot_protection::BotProtection,
+    database::{db::Database, subscribers::SubscriberRepository},
+    email_validation::EmailValidator,
+    health::ReadinessProbe,
+    i18n::Localizer,
+    metrics::Metrics,
+    rate_limit::middleware::RateLimits,
+    routes::confirmation_page::ConfirmationPages,
+    ses_workflow::SESWorkflow,
+    signed_links::LinkSigner,
+};
 
 #[derive(Clone)]
 pub struct AppState {
     pub db: Arc<Database>,
+    pub subscribers: Arc<dyn SubscriberRepository>,
     pub workflow: Arc<SESWorkflow>,
+    pub bot_protection: Arc<BotProtection>,
+    pub rate_limits: RateLimits,
+    pub email_validator: Arc<EmailValidator>,
+    pub localizer: Arc<Localizer>,
+    pub metrics: Arc<Metrics>,
+    pub readiness: Arc<ReadinessProbe>,
+    pub links: Arc<LinkSigner>,
+    pub pages: Arc<ConfirmationPages>,
 }
 
 impl AppState {
-    pub fn new(db: Arc<Database>, workflow: Arc<SESWorkflow>) -> Self {
-        Self { db, workflow }
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        db: Arc<Database>,
+        subscribers: Arc<dyn SubscriberRepository>,
+        workflow: Arc<SESWorkflow>,
+        bot_protection: Arc<BotProtection>,
+        rate_limits: RateLimits,
+        email_validator: Arc<EmailValidator>,
+        localizer: Arc<Localizer>,
+        metrics: Arc<Metrics>,
+        readiness: Arc<ReadinessProbe>,
+        links: Arc<LinkSigner>,
+        pages: Arc<ConfirmationPages>,
+    ) -> Self {
+        Self {
+            db,
+            subscribers,
+            workflow,
+            bot_protection,
+            rate_limits,
+            email_validator,
+            localizer,
+            metrics,
+            readiness,
+            links,
+            pages,
+        }
     }
 }
 
@@ -22,8 +67,62 @@ impl FromRef<AppState> for Arc<Database> {
     }
 }
 
+impl FromRef<AppState> for Arc<dyn SubscriberRepository> {
+    fn from_ref(app_state: &AppState) -> Arc<dyn SubscriberRepository> {
+        app_state.subscribers.clone()
+    }
+}
+
 impl FromRef<AppState> for Arc<SESWorkflow> {
     fn from_ref(app_state: &AppState) -> Arc<SESWorkflow> {
         app_state.workflow.clone()
     }
 }
+
+impl FromRef<AppState> for Arc<BotProtection> {
+    fn from_ref(app_state: &AppState) -> Arc<BotProtection> {
+        app_state.bot_protection.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<EmailValidator> {
+    fn from_ref(app_state: &AppState) -> Arc<EmailValidator> {
+        app_state.email_validator.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<Localizer> {
+    fn from_ref(app_state: &AppState) -> Arc<Localizer> {
+        app_state.localizer.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<Metrics> {
+    fn from_ref(app_state: &AppState) -> Arc<Metrics> {
+        app_state.metrics.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<ReadinessProbe> {
+    fn from_ref(app_state: &AppState) -> Arc<ReadinessProbe> {
+        app_state.readiness.clone()
+    }
+}
+
+impl FromRef<AppState> for RateLimits {
+    fn from_ref(app_state: &AppState) -> RateLimits {
+        app_state.rate_limits.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<LinkSigner> {
+    fn from_ref(app_state: &AppState) -> Arc<LinkSigner> {
+        app_state.links.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<ConfirmationPages> {
+    fn from_ref(app_state: &AppState) -> Arc<ConfirmationPages> {
+        app_state.pages.clone()
+    }
+}
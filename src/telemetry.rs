@@ -1,8 +1,25 @@
+use axum::http::HeaderMap;
+use opentelemetry::{
+    global,
+    propagation::{Extractor, Injector},
+    trace::{TraceError, TracerProvider as _},
+    Context, KeyValue,
+};
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{
+    propagation::TraceContextPropagator,
+    runtime,
+    trace::{Tracer, TracerProvider},
+    Resource,
+};
 use tracing::{dispatcher::set_global_default, Subscriber};
 use tracing_bunyan_formatter::{BunyanFormattingLayer, JsonStorageLayer};
 use tracing_log::LogTracer;
+use tracing_opentelemetry::{OpenTelemetryLayer, PreSampledTracer};
 use tracing_subscriber::{fmt::MakeWriter, layer::SubscriberExt, EnvFilter, Registry};
 
+use crate::configuration::config::TelemetryConfiguration;
+
 pub fn get_subscriber<Sink>(
     name: String,
     env_filter: String,
@@ -10,6 +27,20 @@ pub fn get_subscriber<Sink>(
 ) -> impl Subscriber + Sync + Send
 where
     Sink: for<'a> MakeWriter<'a> + Sync + Send + 'static,
+{
+    get_subscriber_with_tracer(name, env_filter, sink, None::<Tracer>)
+}
+
+/// Same as [`get_subscriber`], additionally handing spans to `tracer` when there is one.
+pub fn get_subscriber_with_tracer<Sink, T>(
+    name: String,
+    env_filter: String,
+    sink: Sink,
+    tracer: Option<T>,
+) -> impl Subscriber + Sync + Send
+where
+    Sink: for<'a> MakeWriter<'a> + Sync + Send + 'static,
+    T: opentelemetry::trace::Tracer + PreSampledTracer + Send + Sync + 'static,
 {
     let env_filter =
         EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(env_filter));
@@ -19,9 +50,62 @@ where
         .with(env_filter)
         .with(JsonStorageLayer)
         .with(formatting_layer)
+        .with(tracer.map(|tracer| OpenTelemetryLayer::new(tracer)))
 }
 
 pub fn init_subscriber(subscriber: impl Subscriber + Sync + Send) {
     LogTracer::init().expect("Failed to set logger");
     set_global_default(subscriber.into()).expect("Failed to set subscriber");
 }
+
+/// Builds a provider that batches spans to the configured OTLP/HTTP collector.
+///
+/// The provider has to be shut down on exit, otherwise the last batch is lost.
+pub fn otlp_tracer_provider(
+    configuration: &TelemetryConfiguration,
+    service_name: String,
+) -> Result<TracerProvider, TraceError> {
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(configuration.otlp_endpoint.clone())
+        .build()?;
+
+    Ok(TracerProvider::builder()
+        .with_batch_exporter(exporter, runtime::Tokio)
+        .with_resource(Resource::new([KeyValue::new("service.name", service_name)]))
+        .build())
+}
+
+/// Installs `provider` globally and returns the tracer the subscriber should use.
+pub fn install_tracer_provider(provider: TracerProvider, name: String) -> Tracer {
+    let tracer = provider.tracer(name);
+    global::set_tracer_provider(provider);
+    // W3C `traceparent`/`tracestate`, both for incoming requests and SES calls.
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    tracer
+}
+
+/// Trace context carried by an incoming request, empty when there is none.
+pub fn extract_trace_context(headers: &HeaderMap) -> Context {
+    global::get_text_map_propagator(|propagator| propagator.extract(&HeaderExtractor(headers)))
+}
+
+/// Writes the context of the current span into outgoing headers.
+pub fn inject_trace_context(injector: &mut dyn Injector) {
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+    let context = tracing::Span::current().context();
+    global::get_text_map_propagator(|propagator| propagator.inject_context(&context, injector));
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|key| key.as_str()).collect()
+    }
+}
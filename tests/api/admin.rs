@@ -0,0 +1,161 @@
+use newsletter::{
+    admin::{
+        self,
+        subscribers::{add_subscriber, confirm_subscriber_by_email},
+    },
+    cli::Command,
+    database::subscribers::{
+        postgres::PostgresSubscriberRepository, AlreadySubscribed, SubscriberRepository,
+    },
+    domain::{NewSubscriber, SubscriberEmail, SubscriberName, SubscriptionStatus},
+    signed_links::LinkSigner,
+};
+use sqlx::PgPool;
+
+use crate::helpers::test_configuration;
+
+const BASE_URL: &str = "http://127.0.0.1";
+
+fn repository(pool: &PgPool) -> PostgresSubscriberRepository {
+    PostgresSubscriberRepository::new(pool.clone())
+}
+
+fn links() -> LinkSigner {
+    LinkSigner::new(&test_configuration().signed_links)
+}
+
+fn new_subscriber(email: &str) -> NewSubscriber {
+    NewSubscriber {
+        email: SubscriberEmail::parse(email.to_string()).unwrap(),
+        name: SubscriberName::parse("Ursula Le Guin".to_string()).unwrap(),
+        locale: "en".to_string(),
+    }
+}
+
+#[sqlx::test]
+async fn added_subscribers_are_pending_with_a_token_unless_confirmed(pool: PgPool) {
+    // Act
+    let link = add_subscriber(
+        &repository(&pool),
+        &links(),
+        BASE_URL,
+        &new_subscriber("pending@example.com"),
+        false,
+    )
+    .await
+    .unwrap();
+    let no_link = add_subscriber(
+        &repository(&pool),
+        &links(),
+        BASE_URL,
+        &new_subscriber("confirmed@example.com"),
+        true,
+    )
+    .await
+    .unwrap();
+
+    // Assert
+    assert!(link.unwrap().contains("subscription_token="));
+    assert!(no_link.is_none());
+    let confirmed = repository(&pool)
+        .list(Some(SubscriptionStatus::Confirmed))
+        .await
+        .unwrap();
+    assert_eq!(confirmed.len(), 1);
+    assert_eq!(confirmed[0].email, "confirmed@example.com");
+    assert_eq!(repository(&pool).list(None).await.unwrap().len(), 2);
+}
+
+#[sqlx::test]
+async fn adding_an_existing_subscriber_fails(pool: PgPool) {
+    // Arrange
+    add_subscriber(
+        &repository(&pool),
+        &links(),
+        BASE_URL,
+        &new_subscriber("ursula@example.com"),
+        true,
+    )
+    .await
+    .unwrap();
+
+    // Act
+    let result = add_subscriber(
+        &repository(&pool),
+        &links(),
+        BASE_URL,
+        &new_subscriber("Ursula@Example.com"),
+        true,
+    )
+    .await;
+
+    // Assert
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "Failed to add Ursula@Example.com, is it subscribed already?"
+    );
+    let duplicate = repository(&pool)
+        .insert_pending(&new_subscriber("URSULA@example.com"), None)
+        .await
+        .unwrap_err();
+    assert!(duplicate.is::<AlreadySubscribed>(), "{:?}", duplicate);
+}
+
+#[sqlx::test]
+async fn subscribers_are_confirmed_and_removed_by_email(pool: PgPool) {
+    // Arrange
+    add_subscriber(
+        &repository(&pool),
+        &links(),
+        BASE_URL,
+        &new_subscriber("ursula@example.com"),
+        false,
+    )
+    .await
+    .unwrap();
+    let email = SubscriberEmail::parse("URSULA@example.com".to_string()).unwrap();
+
+    // Act - Part 1 - Confirm
+    assert!(confirm_subscriber_by_email(&repository(&pool), &email)
+        .await
+        .unwrap());
+
+    // Assert - Part 1
+    let subscribers = repository(&pool).list(None).await.unwrap();
+    assert_eq!(subscribers[0].status, "confirmed");
+
+    // Act - Part 2 - Remove, tokens included
+    assert!(repository(&pool).remove(&email).await.unwrap());
+
+    // Assert - Part 2
+    assert!(repository(&pool).list(None).await.unwrap().is_empty());
+    let tokens: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM subscription_tokens")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(tokens, 0);
+}
+
+#[sqlx::test]
+async fn unknown_subscribers_are_reported(pool: PgPool) {
+    // Arrange
+    let email = SubscriberEmail::parse("nobody@example.com".to_string()).unwrap();
+
+    // Act & Assert
+    assert!(!confirm_subscriber_by_email(&repository(&pool), &email)
+        .await
+        .unwrap());
+    assert!(!repository(&pool).remove(&email).await.unwrap());
+}
+
+#[tokio::test]
+async fn serve_is_not_a_one_off_task() {
+    // Act
+    let outcome = admin::run(&test_configuration(), Command::Serve).await;
+
+    // Assert
+    assert!(outcome
+        .unwrap_err()
+        .to_string()
+        .starts_with("`serve` starts the server"));
+}
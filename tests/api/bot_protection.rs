@@ -0,0 +1,263 @@
+use std::{
+    sync::{Arc, RwLock},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use axum::http::{header::RETRY_AFTER, StatusCode};
+use claim::assert_ok;
+use http_body_util::BodyExt;
+use secrecy::SecretString;
+use sqlx::PgPool;
+
+use newsletter::{
+    bot_protection::{
+        captcha::{CaptchaVerifier, StubCaptchaVerifier},
+        BotCheckError, BotProtection,
+    },
+    configuration::config::{BotProtectionConfiguration, Configuration},
+    rate_limit::postgres::PostgresRateLimiter,
+};
+
+use crate::helpers::{
+    mock_aws_sesv2, mock_aws_sesv2_no_requests, mock_aws_sesv2_with_request_capture,
+    spawn_test_app_with, test_configuration, TestOverrides,
+};
+
+fn strict_configuration() -> Configuration {
+    let mut configuration = test_configuration();
+    configuration.bot_protection = BotProtectionConfiguration {
+        form_secret: SecretString::from("test-secret"),
+        require_form_token: true,
+        min_submit_seconds: 3,
+        max_form_age_seconds: 3600,
+        per_ip_limit: 100,
+        per_domain_limit: 1,
+        rate_limit_window_seconds: 3600,
+    };
+    configuration
+}
+
+fn ten_seconds_ago() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+        - 10
+}
+
+#[sqlx::test]
+async fn a_filled_honeypot_is_silently_dropped(pool: PgPool) {
+    // Arrange
+    let client = mock_aws_sesv2_no_requests();
+    let app = spawn_test_app_with(
+        pool,
+        client,
+        strict_configuration(),
+        TestOverrides::default(),
+    )
+    .await
+    .unwrap();
+    let form_data = "name=Bot&email=bot%40example.com&website=http%3A%2F%2Fspam.example.com";
+
+    // Act
+    let response = app.post("/subscriptions", form_data).await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    let saved = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_optional(&app.db.pool)
+        .await
+        .unwrap();
+    assert!(saved.is_none());
+}
+
+#[sqlx::test]
+async fn subscribe_without_a_form_token_is_rejected(pool: PgPool) {
+    // Arrange
+    let client = mock_aws_sesv2_no_requests();
+    let app = spawn_test_app_with(
+        pool,
+        client,
+        strict_configuration(),
+        TestOverrides::default(),
+    )
+    .await
+    .unwrap();
+    let form_data = "name=Andrii%20Konotop&email=aws.test.receiver@gmail.com";
+
+    // Act
+    let response = app.post("/subscriptions", form_data).await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[sqlx::test]
+async fn a_form_submitted_right_after_rendering_is_rejected(pool: PgPool) {
+    // Arrange
+    let client = mock_aws_sesv2_no_requests();
+    let app = spawn_test_app_with(
+        pool,
+        client,
+        strict_configuration(),
+        TestOverrides::default(),
+    )
+    .await
+    .unwrap();
+
+    let response = app.get("/subscriptions/form-token").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let form_token = body["form_token"].as_str().unwrap();
+
+    // Act
+    let form_data = format!(
+        "name=Andrii%20Konotop&email=aws.test.receiver@gmail.com&form_token={}",
+        form_token
+    );
+    let response = app.post("/subscriptions", &form_data).await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[sqlx::test]
+async fn a_form_submitted_after_the_minimum_delay_is_accepted(pool: PgPool) {
+    // Arrange
+    let client = mock_aws_sesv2();
+    let app = spawn_test_app_with(
+        pool,
+        client,
+        strict_configuration(),
+        TestOverrides::default(),
+    )
+    .await
+    .unwrap();
+    let form_token = app.bot_protection.form_tokens.issue_at(ten_seconds_ago());
+
+    // Act
+    let form_data = format!(
+        "name=Andrii%20Konotop&email=aws.test.receiver@gmail.com&form_token={}",
+        form_token
+    );
+    let response = app.post("/subscriptions", &form_data).await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[sqlx::test]
+async fn too_many_signups_for_one_domain_are_rate_limited(pool: PgPool) {
+    // Arrange
+    let captured_request_content = Arc::new(RwLock::new(None));
+    let client = mock_aws_sesv2_with_request_capture(captured_request_content);
+    let app = spawn_test_app_with(
+        pool,
+        client,
+        strict_configuration(),
+        TestOverrides::default(),
+    )
+    .await
+    .unwrap();
+    let form_token = app.bot_protection.form_tokens.issue_at(ten_seconds_ago());
+
+    // Act
+    let first = format!(
+        "name=Andrii%20Konotop&email=aws.test.receiver@gmail.com&form_token={}",
+        form_token
+    );
+    let second = format!(
+        "name=Someone%20Else&email=someone.else@gmail.com&form_token={}",
+        form_token
+    );
+    let first_response = app.post("/subscriptions", &first).await;
+    let second_response = app.post("/subscriptions", &second).await;
+
+    // Assert
+    assert_eq!(first_response.status(), StatusCode::OK);
+    assert_eq!(second_response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(second_response.headers().contains_key(RETRY_AFTER));
+}
+
+#[sqlx::test]
+async fn domain_limits_are_shared_between_instances_through_postgres(pool: PgPool) {
+    // Arrange
+    let configuration = strict_configuration().bot_protection;
+    let first_instance = BotProtection::new(
+        &configuration,
+        Arc::new(PostgresRateLimiter::new(pool.clone())),
+        None,
+    );
+    let second_instance = BotProtection::new(
+        &configuration,
+        Arc::new(PostgresRateLimiter::new(pool)),
+        None,
+    );
+
+    // Act
+    let first = first_instance.check_domain("gmail.com").await;
+    let second = second_instance.check_domain("gmail.com").await;
+
+    // Assert
+    assert_ok!(first);
+    assert!(matches!(second, Err(BotCheckError::RateLimited(_))));
+}
+
+#[sqlx::test]
+async fn a_wrong_captcha_answer_is_rejected(pool: PgPool) {
+    // Arrange
+    let client = mock_aws_sesv2_no_requests();
+    let captcha: Arc<dyn CaptchaVerifier> = Arc::new(StubCaptchaVerifier::new("solved"));
+    let app = spawn_test_app_with(
+        pool,
+        client,
+        strict_configuration(),
+        TestOverrides {
+            captcha: Some(captcha),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+    let form_token = app.bot_protection.form_tokens.issue_at(ten_seconds_ago());
+
+    // Act
+    let form_data = format!(
+        "name=Andrii%20Konotop&email=aws.test.receiver@gmail.com&form_token={}&captcha_token=wrong",
+        form_token
+    );
+    let response = app.post("/subscriptions", &form_data).await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[sqlx::test]
+async fn a_correct_captcha_answer_is_accepted(pool: PgPool) {
+    // Arrange
+    let client = mock_aws_sesv2();
+    let captcha: Arc<dyn CaptchaVerifier> = Arc::new(StubCaptchaVerifier::new("solved"));
+    let app = spawn_test_app_with(
+        pool,
+        client,
+        strict_configuration(),
+        TestOverrides {
+            captcha: Some(captcha),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+    let form_token = app.bot_protection.form_tokens.issue_at(ten_seconds_ago());
+
+    // Act
+    let form_data = format!(
+        "name=Andrii%20Konotop&email=aws.test.receiver@gmail.com&form_token={}&captcha_token=solved",
+        form_token
+    );
+    let response = app.post("/subscriptions", &form_data).await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+}
@@ -0,0 +1,83 @@
+use sqlx::{types::chrono::Utc, PgPool};
+use uuid::Uuid;
+
+use newsletter::database::dedup::merge_duplicate_subscribers;
+
+async fn insert_legacy_subscriber(pool: &PgPool, email: &str, status: &str) -> Uuid {
+    // Rows written before `email_canonical` existed
+    let id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+          INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+          VALUES ($1, $2, 'Legacy', $3, $4)
+        "#,
+        id,
+        email,
+        Utc::now(),
+        status
+    )
+    .execute(pool)
+    .await
+    .unwrap();
+    sqlx::query!(
+        r#"INSERT INTO subscription_tokens (token_hash, subscriber_id) VALUES ($1, $2)"#,
+        id.to_string(),
+        id
+    )
+    .execute(pool)
+    .await
+    .unwrap();
+    id
+}
+
+#[sqlx::test]
+async fn duplicates_are_merged_into_the_confirmed_subscriber(pool: PgPool) {
+    // Arrange
+    let pending =
+        insert_legacy_subscriber(&pool, "Alice@Example.com", "pending_confirmation").await;
+    let confirmed = insert_legacy_subscriber(&pool, "alice@example.com", "confirmed").await;
+    let other = insert_legacy_subscriber(&pool, "bob@example.com", "confirmed").await;
+
+    // Act
+    let report = merge_duplicate_subscribers(&pool).await.unwrap();
+
+    // Assert
+    assert_eq!(report.merged.len(), 1);
+    assert_eq!(report.merged[0].kept.0, confirmed);
+    assert_eq!(report.merged[0].removed[0].0, pending);
+
+    let remaining = sqlx::query!("SELECT id, email_canonical FROM subscriptions ORDER BY email")
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(remaining.len(), 2);
+    assert_eq!(remaining[0].id, confirmed);
+    assert_eq!(
+        remaining[0].email_canonical.as_deref(),
+        Some("alice@example.com")
+    );
+    assert_eq!(remaining[1].id, other);
+
+    let orphan_tokens = sqlx::query!(
+        "SELECT token_hash FROM subscription_tokens WHERE subscriber_id = $1",
+        pending
+    )
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    assert!(orphan_tokens.is_empty());
+}
+
+#[sqlx::test]
+async fn running_the_dedup_twice_is_a_no_op(pool: PgPool) {
+    // Arrange
+    insert_legacy_subscriber(&pool, "Alice@Example.com", "pending_confirmation").await;
+    insert_legacy_subscriber(&pool, "alice@example.com", "confirmed").await;
+    merge_duplicate_subscribers(&pool).await.unwrap();
+
+    // Act
+    let report = merge_duplicate_subscribers(&pool).await.unwrap();
+
+    // Assert
+    assert!(report.merged.is_empty());
+}
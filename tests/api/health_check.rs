@@ -1,8 +1,20 @@
-use axum::{body::Body, extract::Request, http::StatusCode};
+use aws_sdk_sesv2::{
+    config::{Config, Region},
+    Client,
+};
+use axum::{body::Body, extract::Request, http::StatusCode, response::Response};
+use http_body_util::BodyExt;
 use sqlx::PgPool;
 use tower::util::ServiceExt;
 
-use crate::helpers::{mock_aws_sesv2, spawn_test_app};
+use crate::helpers::{
+    mock_aws_sesv2, spawn_test_app, spawn_test_app_with, test_configuration, TestOverrides,
+};
+
+async fn json_body(response: Response) -> serde_json::Value {
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    serde_json::from_slice(&body).unwrap()
+}
 
 #[sqlx::test]
 async fn health_check_works(pool: PgPool) {
@@ -22,3 +34,109 @@ async fn health_check_works(pool: PgPool) {
 
     assert_eq!(response.status(), StatusCode::OK);
 }
+
+#[sqlx::test]
+async fn liveness_does_not_depend_on_the_database(pool: PgPool) {
+    // Arrange
+    let app = spawn_test_app(pool, mock_aws_sesv2()).await.unwrap();
+    app.db.pool.close().await;
+
+    // Act
+    let response = app.get("/health/live").await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[sqlx::test]
+async fn readiness_reports_every_dependency(pool: PgPool) {
+    // Arrange
+    let app = spawn_test_app(pool, mock_aws_sesv2()).await.unwrap();
+
+    // Act
+    let response = app.get("/health/ready").await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    let report = json_body(response).await;
+    assert_eq!(report["status"], "ok");
+    assert_eq!(report["checks"]["database"]["status"], "ok");
+    assert_eq!(report["checks"]["migrations"]["status"], "ok");
+    assert!(report["checks"]["database"]["latency_ms"].is_u64());
+    // SES is only probed when asked for.
+    assert!(report["checks"].get("ses").is_none());
+}
+
+#[sqlx::test]
+async fn readiness_fails_with_503_when_postgres_is_down(pool: PgPool) {
+    // Arrange
+    let app = spawn_test_app(pool, mock_aws_sesv2()).await.unwrap();
+    app.db.pool.close().await;
+
+    // Act
+    let response = app.get("/health/ready").await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let report = json_body(response).await;
+    assert_eq!(report["status"], "unavailable");
+    assert_eq!(report["checks"]["database"]["status"], "unavailable");
+    assert!(report["checks"]["database"]["error"].is_string());
+}
+
+/// Nothing listens on port 1, so every request fails to connect.
+fn unreachable_aws_sesv2() -> Client {
+    Client::from_conf(
+        Config::builder()
+            .with_test_defaults()
+            .region(Region::from_static("eu-central-1"))
+            .endpoint_url("http://127.0.0.1:1")
+            .build(),
+    )
+}
+
+#[sqlx::test]
+async fn an_unreachable_ses_only_degrades_readiness(pool: PgPool) {
+    // Arrange
+    let client = unreachable_aws_sesv2();
+    let mut configuration = test_configuration();
+    configuration.health.check_ses = true;
+    let app = spawn_test_app_with(pool, client, configuration, TestOverrides::default())
+        .await
+        .unwrap();
+
+    // Act
+    let response = app.get("/health/ready").await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    let report = json_body(response).await;
+    assert_eq!(report["status"], "degraded");
+    assert_eq!(report["checks"]["ses"]["status"], "degraded");
+    assert_eq!(report["checks"]["ses"]["critical"], false);
+    // The SDK error stays in the logs.
+    assert_eq!(report["checks"]["ses"]["error"], "SES is unreachable");
+}
+
+#[sqlx::test]
+async fn readiness_fails_when_a_migration_is_missing(pool: PgPool) {
+    // Arrange
+    let app = spawn_test_app(pool, mock_aws_sesv2()).await.unwrap();
+    sqlx::query(
+        "DELETE FROM _sqlx_migrations WHERE version = (SELECT MAX(version) FROM _sqlx_migrations)",
+    )
+    .execute(&app.db.pool)
+    .await
+    .unwrap();
+
+    // Act
+    let response = app.get("/health/ready").await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let report = json_body(response).await;
+    assert_eq!(report["checks"]["database"]["status"], "ok");
+    let error = report["checks"]["migrations"]["error"].as_str().unwrap();
+    assert!(error.starts_with("Schema is at version"), "{}", error);
+    assert!(error.ends_with("(1 migration(s) not applied)"), "{}", error);
+}
@@ -10,14 +10,24 @@ use axum::{
 };
 use once_cell::sync::Lazy;
 use serde_json::to_string;
-use sqlx::PgPool;
+use sqlx::{postgres::PgPoolOptions, PgPool};
 use tower::ServiceExt;
 
 use newsletter::{
-    configuration::config::get_configuration,
-    database::db::Database,
-    routes::router::router,
+    bot_protection::{captcha::CaptchaVerifier, BotProtection},
+    configuration::config::{get_configuration, Configuration},
+    database::{
+        db::Database,
+        subscribers::{postgres::PostgresSubscriberRepository, SubscriberRepository},
+    },
+    email_validation::{mx::MxResolver, EmailValidator},
+    health::ReadinessProbe,
+    i18n::Localizer,
+    metrics::Metrics,
+    rate_limit::{memory::InMemoryRateLimiter, middleware::RateLimits},
+    routes::{confirmation_page::ConfirmationPages, router::router},
     ses_workflow::SESWorkflow,
+    signed_links::LinkSigner,
     state::AppState,
     telemetry::{get_subscriber, init_subscriber},
 };
@@ -45,10 +55,15 @@ pub struct ConfirmationLinks {
 
 pub struct TestApp {
     pub db: Arc<Database>,
+    pub bot_protection: Arc<BotProtection>,
     pub router: Router,
 }
 
 impl TestApp {
+    pub async fn send(&self, request: Request<Body>) -> Response {
+        self.router.clone().oneshot(request).await.unwrap()
+    }
+
     pub async fn get(&self, uri: &str) -> Response {
         self.router
             .clone()
@@ -67,7 +82,23 @@ impl TestApp {
             .unwrap()
     }
 
-    pub async fn post(&self, uri: &str, form_data: &'static str) -> Response {
+    /// Like a browser following a link: asks for HTML first.
+    pub async fn get_as_browser(&self, uri: &str) -> Response {
+        self.send(
+            Request::builder()
+                .method(http::Method::GET)
+                .uri(uri)
+                .header(
+                    http::header::ACCEPT,
+                    "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8",
+                )
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+    }
+
+    pub async fn post(&self, uri: &str, form_data: &str) -> Response {
         self.router
             .clone()
             .oneshot(
@@ -78,7 +109,7 @@ impl TestApp {
                         http::header::CONTENT_TYPE,
                         "application/x-www-form-urlencoded",
                     )
-                    .body(Body::from(form_data))
+                    .body(Body::from(form_data.to_owned()))
                     .unwrap(),
             )
             .await
@@ -194,22 +225,99 @@ pub fn mock_aws_sesv2_no_requests() -> Client {
     mock_client!(aws_sdk_sesv2, RuleMode::Sequential, [&mock_send_email])
 }
 
+pub fn test_configuration() -> Configuration {
+    let mut c = get_configuration().expect("Failed to read configuration.");
+    c.aws.verified_email = "sender@example.com".to_string();
+    // Most tests submit the form directly, the bot defences get their own tests
+    c.bot_protection.require_form_token = false;
+    c.bot_protection.per_ip_limit = u32::MAX;
+    c.bot_protection.per_domain_limit = u32::MAX;
+    c
+}
+
+/// Stand-ins for the pluggable third-party services.
+#[derive(Default)]
+pub struct TestOverrides {
+    pub captcha: Option<Arc<dyn CaptchaVerifier>>,
+    pub mx_resolver: Option<Arc<dyn MxResolver>>,
+    pub subscribers: Option<Arc<dyn SubscriberRepository>>,
+}
+
 pub async fn spawn_test_app(pool: PgPool, client: Client) -> Result<TestApp, anyhow::Error> {
-    Lazy::force(&TRACING);
+    spawn_test_app_with(pool, client, test_configuration(), TestOverrides::default()).await
+}
 
-    let configuration = {
-        let mut c = get_configuration().expect("Failed to read configuration.");
-        c.aws.verified_email = "sender@example.com".to_string();
-        c
+/// For handlers that only need subscribers: the pool is never connected.
+pub async fn spawn_test_app_without_database(
+    client: Client,
+    subscribers: Arc<dyn SubscriberRepository>,
+) -> Result<TestApp, anyhow::Error> {
+    let configuration = test_configuration();
+    let pool = PgPoolOptions::new().connect_lazy_with(configuration.database.with_db());
+    let overrides = TestOverrides {
+        subscribers: Some(subscribers),
+        ..Default::default()
     };
+    spawn_test_app_with(pool, client, configuration, overrides).await
+}
+
+pub async fn spawn_test_app_with(
+    pool: PgPool,
+    client: Client,
+    configuration: Configuration,
+    overrides: TestOverrides,
+) -> Result<TestApp, anyhow::Error> {
+    Lazy::force(&TRACING);
 
     let db = Arc::new(Database { pool });
     let ses = Arc::new(SESWorkflow::new(client, configuration.aws.verified_email));
+    let readiness = Arc::new(ReadinessProbe::new(
+        &configuration.health,
+        db.clone(),
+        ses.clone(),
+    ));
+    let rate_limit_store = Arc::new(InMemoryRateLimiter::new());
+    let bot_protection = Arc::new(BotProtection::new(
+        &configuration.bot_protection,
+        rate_limit_store.clone(),
+        overrides.captcha,
+    ));
+    let email_validator = Arc::new(EmailValidator::new(
+        &configuration.email_validation,
+        overrides.mx_resolver,
+    ));
     let base_url = Arc::new(configuration.application.base_url);
+    let links = Arc::new(LinkSigner::new(&configuration.signed_links));
+    let localizer = Arc::new(Localizer::new());
+
+    let rate_limits = RateLimits::new(&configuration.rate_limit, rate_limit_store);
+
+    let subscribers = overrides
+        .subscribers
+        .unwrap_or_else(|| Arc::new(PostgresSubscriberRepository::new(db.pool.clone())));
 
-    let state = AppState::new(db.clone(), ses);
+    let state = AppState::new(
+        db.clone(),
+        subscribers,
+        ses,
+        bot_protection.clone(),
+        rate_limits,
+        email_validator,
+        localizer.clone(),
+        Arc::new(Metrics::new()),
+        readiness,
+        links,
+        Arc::new(ConfirmationPages::new(
+            localizer,
+            configuration.application.confirmed_redirect_url,
+        )),
+    );
 
     let router = router(state, base_url);
 
-    Ok(TestApp { db, router })
+    Ok(TestApp {
+        db,
+        bot_protection,
+        router,
+    })
 }
@@ -1,5 +1,18 @@
+mod admin;
+mod bot_protection;
+mod dedup;
 mod health_check;
 mod helpers;
+mod metrics;
+mod migrations;
 mod newsletter;
+mod pending_cleanup;
+mod rate_limit;
+mod reload;
+mod request_id;
+mod shutdown;
+mod signed_links;
 mod subscriptions;
 mod subscriptions_confirm;
+mod subscriptions_resend;
+mod telemetry;
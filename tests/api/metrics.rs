@@ -0,0 +1,72 @@
+use axum::http::{header::CONTENT_TYPE, StatusCode};
+use http_body_util::BodyExt;
+use sqlx::PgPool;
+
+use crate::helpers::{mock_aws_sesv2, spawn_test_app, TestApp};
+
+async fn scrape(app: &TestApp) -> String {
+    let response = app.get("/metrics").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers()[CONTENT_TYPE]
+        .to_str()
+        .unwrap()
+        .starts_with("text/plain"));
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    String::from_utf8(body.to_vec()).unwrap()
+}
+
+#[sqlx::test]
+async fn metrics_count_requests_per_matched_route(pool: PgPool) {
+    // Arrange
+    let app = spawn_test_app(pool, mock_aws_sesv2()).await.unwrap();
+
+    // Act
+    app.get("/health_check").await;
+    app.get("/subscriptions/confirm?subscription_token=abc")
+        .await;
+    let output = scrape(&app).await;
+
+    // Assert
+    assert!(output
+        .contains(r#"http_requests_total{method="GET",route="/health_check",status="200"} 1"#));
+    assert!(output.contains(r#"route="/subscriptions/confirm""#));
+    assert!(!output.contains("subscription_token=abc"));
+}
+
+#[sqlx::test]
+async fn metrics_report_emails_and_subscribers_by_status(pool: PgPool) {
+    // Arrange
+    let app = spawn_test_app(pool, mock_aws_sesv2()).await.unwrap();
+    let form_data = "name=Andrii%20Konotop&email=aws.test.receiver@gmail.com";
+
+    // Act
+    app.post("/subscriptions", form_data).await;
+    let output = scrape(&app).await;
+
+    // Assert
+    assert!(output.contains(r#"emails_total{kind="confirmation",outcome="sent"} 1"#));
+    assert!(output.contains(r#"subscribers{status="pending_confirmation"} 1"#));
+    assert!(output.contains("email_deliveries_pending 0"));
+    assert!(output.contains("db_pool_max_connections"));
+}
+
+#[sqlx::test]
+async fn metrics_are_served_without_subscriber_counts_when_they_cannot_be_queried(pool: PgPool) {
+    // Arrange
+    let app = spawn_test_app(pool.clone(), mock_aws_sesv2())
+        .await
+        .unwrap();
+    app.get("/health_check").await;
+    sqlx::query("ALTER TABLE subscriptions RENAME TO subscriptions_gone")
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    // Act
+    let output = scrape(&app).await;
+
+    // Assert
+    assert!(output.contains(r#"route="/health_check""#));
+    assert!(output.contains("db_pool_max_connections"));
+    assert!(!output.contains("subscribers{"));
+}
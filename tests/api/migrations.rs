@@ -0,0 +1,192 @@
+use newsletter::{
+    database::{
+        dedup::merge_duplicate_subscribers,
+        migrations::{migrate_down, migrate_up, schema_status, MIGRATOR},
+        subscribers::{postgres::PostgresSubscriberRepository, SubscriberRepository},
+    },
+    domain::SubscriberEmail,
+};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+async fn has_column(pool: &PgPool, column: &str) -> bool {
+    sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM information_schema.columns \
+         WHERE table_name = 'subscriptions' AND column_name = $1)",
+    )
+    .bind(column)
+    .fetch_one(pool)
+    .await
+    .unwrap()
+}
+
+fn versions() -> Vec<i64> {
+    MIGRATOR
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .map(|migration| migration.version)
+        .collect()
+}
+
+#[sqlx::test]
+async fn a_migrated_database_has_nothing_pending(pool: PgPool) {
+    // Act
+    let status = schema_status(&pool).await.unwrap();
+
+    // Assert
+    assert_eq!(status.pending(), 0);
+    assert_eq!(status.current_version(), status.expected_version());
+    assert!(status.dirty_version.is_none());
+}
+
+#[sqlx::test]
+async fn migrating_down_and_up_again_round_trips(pool: PgPool) {
+    // Arrange
+    // The migration right before `locale` was added.
+    let target = 20241202090000;
+    let reverted = versions().into_iter().filter(|v| *v > target).count();
+
+    // Act
+    migrate_down(&pool, target).await.unwrap();
+
+    // Assert
+    let status = schema_status(&pool).await.unwrap();
+    assert_eq!(status.pending(), reverted);
+    assert_eq!(status.current_version(), Some(target));
+    assert!(!has_column(&pool, "locale").await);
+
+    // Act
+    migrate_up(&pool).await.unwrap();
+
+    // Assert
+    assert_eq!(schema_status(&pool).await.unwrap().pending(), 0);
+    assert!(has_column(&pool, "locale").await);
+}
+
+#[sqlx::test]
+async fn migrating_down_to_an_unknown_version_is_refused(pool: PgPool) {
+    // Act
+    let result = migrate_down(&pool, 42).await;
+
+    // Assert
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "42 is not a known migration version"
+    );
+    assert_eq!(schema_status(&pool).await.unwrap().pending(), 0);
+}
+
+#[sqlx::test(migrations = false)]
+async fn concurrent_runners_take_turns(pool: PgPool) {
+    // Arrange
+    assert_eq!(
+        schema_status(&pool).await.unwrap().pending(),
+        versions().len()
+    );
+
+    // Act
+    let runners: Vec<_> = (0..3)
+        .map(|_| {
+            let pool = pool.clone();
+            tokio::spawn(async move { migrate_up(&pool).await })
+        })
+        .collect();
+    for runner in runners {
+        runner.await.unwrap().unwrap();
+    }
+
+    // Assert
+    let status = schema_status(&pool).await.unwrap();
+    assert_eq!(status.pending(), 0);
+}
+
+#[sqlx::test]
+async fn tokens_stored_before_hashing_keep_working(pool: PgPool) {
+    // Arrange
+    // The migration right before tokens were hashed.
+    migrate_down(&pool, 20241204090000).await.unwrap();
+    let subscriber_id = Uuid::new_v4();
+    sqlx::query(
+        "INSERT INTO subscriptions (id, email, email_canonical, name, locale, subscribed_at, status) \
+         VALUES ($1, 'ursula@example.com', 'ursula@example.com', 'Ursula', 'en', now(), 'pending_confirmation')",
+    )
+    .bind(subscriber_id)
+    .execute(&pool)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO subscription_tokens (subscription_token, subscriber_id) VALUES ('legacy0token0from0inbox', $1)",
+    )
+    .bind(subscriber_id)
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    // Act
+    migrate_up(&pool).await.unwrap();
+
+    // Assert
+    let repository = PostgresSubscriberRepository::new(pool);
+    assert_eq!(
+        repository
+            .subscriber_id_from_token("legacy0token0from0inbox")
+            .await
+            .unwrap(),
+        Some(subscriber_id)
+    );
+}
+
+#[sqlx::test]
+async fn emails_stored_before_canonicalisation_get_the_same_canonical_form_once_deduplicated(
+    pool: PgPool,
+) {
+    // Arrange
+    // The migration right before `email_canonical` was added.
+    migrate_down(&pool, 20241201120000).await.unwrap();
+    for email in ["Ana@Bücher.de", "Bob@Example.COM", " carol@example.com "] {
+        sqlx::query(
+            "INSERT INTO subscriptions (id, email, name, subscribed_at, status) \
+             VALUES ($1, $2, 'Legacy', now(), 'confirmed')",
+        )
+        .bind(Uuid::new_v4())
+        .bind(email)
+        .execute(&pool)
+        .await
+        .unwrap();
+    }
+
+    migrate_up(&pool).await.unwrap();
+    // SQL can't trim or punycode these, that is left to the dedup routine.
+    let missing: Vec<String> = sqlx::query_scalar(
+        "SELECT email FROM subscriptions WHERE email_canonical IS NULL ORDER BY email",
+    )
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    assert_eq!(missing, vec![" carol@example.com ", "Ana@Bücher.de"]);
+
+    // Act
+    merge_duplicate_subscribers(&pool).await.unwrap();
+
+    // Assert
+    let canonical: Vec<Option<String>> =
+        sqlx::query_scalar("SELECT email_canonical FROM subscriptions ORDER BY email_canonical")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+    assert_eq!(
+        canonical,
+        vec![
+            Some("ana@xn--bcher-kva.de".to_string()),
+            Some("bob@example.com".to_string()),
+            Some("carol@example.com".to_string()),
+        ]
+    );
+    let repository = PostgresSubscriberRepository::new(pool);
+    let email = SubscriberEmail::parse("ana@bücher.de".to_string()).unwrap();
+    assert!(repository
+        .subscriber_by_email(&email)
+        .await
+        .unwrap()
+        .is_some());
+}
@@ -1,7 +1,15 @@
 use std::sync::{Arc, RwLock};
 
 use axum::http::StatusCode;
+use http_body_util::BodyExt;
+use newsletter::{
+    database::subscribers::{
+        postgres::PostgresSubscriberRepository, ConfirmedSubscribers, SubscriberRepository,
+    },
+    domain::{NewSubscriber, SubscriberEmail, SubscriberName},
+};
 use sqlx::PgPool;
+use uuid::Uuid;
 
 use crate::helpers::{
     get_confirmation_links, mock_aws_sesv2, mock_aws_sesv2_with_request_capture, spawn_test_app,
@@ -102,3 +110,109 @@ async fn newsletters_returs_422_for_invalid_data(pool: PgPool) {
         );
     }
 }
+
+#[sqlx::test]
+async fn newsletters_are_delivered_in_the_subscriber_locale(pool: PgPool) {
+    // Arrange
+    let captured_request_content = Arc::new(RwLock::new(None));
+    let client = mock_aws_sesv2_with_request_capture(captured_request_content.clone());
+
+    let app = spawn_test_app(pool, client).await.unwrap();
+    create_confirmed_subscriber(&app, captured_request_content.clone()).await;
+    sqlx::query!("UPDATE subscriptions SET locale = 'uk'")
+        .execute(&app.db.pool)
+        .await
+        .unwrap();
+
+    let body = serde_json::json!({
+    "title": "Newsletter title",
+    "content": {
+    "text": "Newsletter body as plain text",
+    "html": "<p>Newsletter body as HTML</p>",
+    },
+    "translations": {
+    "uk": {
+    "title": "Заголовок розсилки",
+    "content": {
+    "text": "Текст розсилки",
+    "html": "<p>Текст розсилки</p>",
+    }
+    }
+    }
+    });
+
+    // Act
+    let response = app.post_json("/newsletters", body).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let (html, text) = captured_request_content.read().unwrap().clone().unwrap();
+    assert_eq!(text, "Текст розсилки");
+    assert_eq!(html, "<p>Текст розсилки</p>");
+}
+
+#[sqlx::test]
+async fn confirmed_subscribers_with_an_invalid_email_are_skipped_and_counted(pool: PgPool) {
+    // Arrange
+    let captured_request_content = Arc::new(RwLock::new(None));
+    let client = mock_aws_sesv2_with_request_capture(captured_request_content.clone());
+
+    let app = spawn_test_app(pool, client).await.unwrap();
+    create_confirmed_subscriber(&app, captured_request_content.clone()).await;
+    sqlx::query!(
+        r#"
+          INSERT INTO subscriptions (id, email, email_canonical, name, locale, subscribed_at, status)
+          VALUES ($1, 'not-an-email', 'not-an-email', 'Broken', 'en', now(), 'confirmed')
+        "#,
+        Uuid::new_v4()
+    )
+    .execute(&app.db.pool)
+    .await
+    .unwrap();
+
+    let body = serde_json::json!({
+    "title": "Newsletter title",
+    "content": {
+    "text": "Newsletter body as plain text",
+    "html": "<p>Newsletter body as HTML</p>",
+    }
+    });
+
+    // Act
+    let response = app.post_json("/newsletters", body).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let metrics = app.get("/metrics").await;
+    let metrics = metrics.into_body().collect().await.unwrap().to_bytes();
+    let metrics = String::from_utf8(metrics.to_vec()).unwrap();
+    assert!(metrics.contains("newsletter_invalid_subscribers_total 1"));
+    assert!(metrics.contains(r#"emails_total{kind="newsletter",outcome="sent"} 1"#));
+}
+
+#[sqlx::test]
+async fn confirmed_subscribers_are_read_a_page_at_a_time(pool: PgPool) {
+    // Arrange
+    let repository = PostgresSubscriberRepository::new(pool);
+    for i in 0..5 {
+        let new_subscriber = NewSubscriber {
+            email: SubscriberEmail::parse(format!("reader{}@example.com", i)).unwrap(),
+            name: SubscriberName::parse("Reader".to_string()).unwrap(),
+            locale: "en".to_string(),
+        };
+        repository.insert_confirmed(&new_subscriber).await.unwrap();
+    }
+
+    // Act
+    let mut pages = ConfirmedSubscribers::new(&repository, 2);
+    let mut emails = vec![];
+    while let Some(page) = pages.next_page().await.unwrap() {
+        assert!(page.len() <= 2);
+        emails.extend(page.into_iter().map(|s| s.unwrap().email.to_string()));
+    }
+
+    // Assert
+    emails.sort();
+    let expected: Vec<_> = (0..5).map(|i| format!("reader{}@example.com", i)).collect();
+    assert_eq!(emails, expected);
+}
@@ -0,0 +1,134 @@
+use std::{
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use aws_sdk_sesv2::Client;
+use newsletter::{
+    database::subscribers::{postgres::PostgresSubscriberRepository, SubscriberRepository},
+    domain::{NewSubscriber, SubscriberEmail, SubscriberName},
+    i18n::Localizer,
+    metrics::Metrics,
+    pending_cleanup::{CleanupReport, PendingCleanup},
+    ses_workflow::SESWorkflow,
+    signed_links::LinkSigner,
+};
+use sqlx::{types::chrono::Utc, PgPool};
+
+use crate::helpers::{
+    get_confirmation_links, mock_aws_sesv2_no_requests, mock_aws_sesv2_with_request_capture,
+    test_configuration,
+};
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+fn cleanup(pool: &PgPool, client: Client) -> PendingCleanup {
+    let configuration = test_configuration();
+    PendingCleanup::new(
+        &configuration.pending_cleanup,
+        Arc::new(PostgresSubscriberRepository::new(pool.clone())),
+        Arc::new(LinkSigner::new(&configuration.signed_links)),
+        Arc::new(SESWorkflow::new(client, configuration.aws.verified_email)),
+        Arc::new(Localizer::new()),
+        Arc::new(Metrics::new()),
+        configuration.application.base_url,
+    )
+}
+
+fn new_subscriber(email: &str) -> NewSubscriber {
+    NewSubscriber {
+        email: SubscriberEmail::parse(email.to_string()).unwrap(),
+        name: SubscriberName::parse("Andrii Konotop".to_string()).unwrap(),
+        locale: "en".to_string(),
+    }
+}
+
+#[sqlx::test]
+async fn stale_pending_subscribers_get_a_single_reminder(pool: PgPool) {
+    // Arrange
+    let captured_request_content = Arc::new(RwLock::new(None));
+    let aws_client = mock_aws_sesv2_with_request_capture(captured_request_content.clone());
+    let cleanup = cleanup(&pool, aws_client);
+    PostgresSubscriberRepository::new(pool.clone())
+        .insert_pending(
+            &new_subscriber("aws.test.receiver@gmail.com"),
+            Some("token"),
+        )
+        .await
+        .unwrap();
+    let in_three_days = Utc::now() + DAY * 3;
+
+    // Act
+    let first = cleanup.run_at(in_three_days).await.unwrap();
+    let second = cleanup.run_at(in_three_days).await.unwrap();
+
+    // Assert
+    assert_eq!(
+        first,
+        CleanupReport {
+            reminded: 1,
+            ..Default::default()
+        }
+    );
+    assert_eq!(second, CleanupReport::default());
+    let reminder_link = get_confirmation_links(captured_request_content).plain_text;
+    assert_eq!(reminder_link.path(), "/subscriptions/confirm");
+    let reminded_at = sqlx::query_scalar!("SELECT reminded_at FROM subscriptions")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert!(reminded_at.is_some());
+}
+
+#[sqlx::test]
+async fn pending_subscribers_are_purged_after_the_retention_period(pool: PgPool) {
+    // Arrange
+    let cleanup = cleanup(&pool, mock_aws_sesv2_no_requests());
+    let repository = PostgresSubscriberRepository::new(pool.clone());
+    repository
+        .insert_pending(&new_subscriber("pending@example.com"), Some("token"))
+        .await
+        .unwrap();
+    repository
+        .insert_confirmed(&new_subscriber("confirmed@example.com"))
+        .await
+        .unwrap();
+
+    // Act
+    let report = cleanup.run_at(Utc::now() + DAY * 31).await.unwrap();
+
+    // Assert
+    assert_eq!(
+        report,
+        CleanupReport {
+            purged: 1,
+            ..Default::default()
+        }
+    );
+    let emails = sqlx::query_scalar!("SELECT email FROM subscriptions")
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(emails, vec!["confirmed@example.com"]);
+    let tokens = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM subscription_tokens"#)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(tokens, 0);
+}
+
+#[sqlx::test]
+async fn recent_pending_subscribers_are_left_alone(pool: PgPool) {
+    // Arrange
+    let cleanup = cleanup(&pool, mock_aws_sesv2_no_requests());
+    PostgresSubscriberRepository::new(pool.clone())
+        .insert_pending(&new_subscriber("pending@example.com"), Some("token"))
+        .await
+        .unwrap();
+
+    // Act
+    let report = cleanup.run_once().await.unwrap();
+
+    // Assert
+    assert_eq!(report, CleanupReport::default());
+}
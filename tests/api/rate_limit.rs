@@ -0,0 +1,204 @@
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+
+use axum::{
+    body::Body,
+    extract::ConnectInfo,
+    http::{self, header::RETRY_AFTER, Request, StatusCode},
+};
+use claim::{assert_err, assert_ok};
+use sqlx::PgPool;
+
+use newsletter::{
+    configuration::config::{Configuration, RouteRateLimitConfiguration},
+    rate_limit::{postgres::PostgresRateLimiter, Cooldown, Quota, RateLimitStore},
+};
+
+use crate::helpers::{mock_aws_sesv2, spawn_test_app_with, test_configuration, TestOverrides};
+
+fn configuration_with_limit(limit: u32) -> Configuration {
+    let mut configuration = test_configuration();
+    configuration.rate_limit.trusted_proxies = vec!["10.0.0.0/8".parse().unwrap()];
+    configuration.rate_limit.subscriptions = RouteRateLimitConfiguration {
+        limit,
+        window_seconds: 60,
+    };
+    configuration
+}
+
+// An empty form is rejected by the handler, so no email is ever sent.
+fn subscribe_from(peer: &str, forwarded_for: Option<&str>) -> Request<Body> {
+    let peer: SocketAddr = format!("{}:40000", peer).parse().unwrap();
+    let mut builder = Request::builder()
+        .method(http::Method::POST)
+        .uri("/subscriptions")
+        .header(
+            http::header::CONTENT_TYPE,
+            "application/x-www-form-urlencoded",
+        )
+        .extension(ConnectInfo(peer));
+    if let Some(forwarded_for) = forwarded_for {
+        builder = builder.header("x-forwarded-for", forwarded_for);
+    }
+    builder.body(Body::empty()).unwrap()
+}
+
+#[sqlx::test]
+async fn requests_over_the_quota_get_a_429_with_retry_after(pool: PgPool) {
+    // Arrange
+    let app = spawn_test_app_with(
+        pool,
+        mock_aws_sesv2(),
+        configuration_with_limit(2),
+        TestOverrides::default(),
+    )
+    .await
+    .unwrap();
+
+    // Act
+    let first = app.send(subscribe_from("1.2.3.4", None)).await;
+    let second = app.send(subscribe_from("1.2.3.4", None)).await;
+    let third = app.send(subscribe_from("1.2.3.4", None)).await;
+
+    // Assert
+    assert_eq!(first.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(second.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(third.status(), StatusCode::TOO_MANY_REQUESTS);
+    let retry_after: u64 = third.headers()[RETRY_AFTER]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!((1..=60).contains(&retry_after));
+}
+
+#[sqlx::test]
+async fn each_client_ip_gets_its_own_quota(pool: PgPool) {
+    // Arrange
+    let app = spawn_test_app_with(
+        pool,
+        mock_aws_sesv2(),
+        configuration_with_limit(1),
+        TestOverrides::default(),
+    )
+    .await
+    .unwrap();
+
+    // Act
+    let first = app.send(subscribe_from("1.2.3.4", None)).await;
+    let second = app.send(subscribe_from("5.6.7.8", None)).await;
+
+    // Assert
+    assert_ne!(first.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_ne!(second.status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[sqlx::test]
+async fn clients_behind_a_trusted_proxy_are_told_apart(pool: PgPool) {
+    // Arrange
+    let app = spawn_test_app_with(
+        pool,
+        mock_aws_sesv2(),
+        configuration_with_limit(1),
+        TestOverrides::default(),
+    )
+    .await
+    .unwrap();
+
+    // Act
+    let first = app.send(subscribe_from("10.0.0.1", Some("1.2.3.4"))).await;
+    let second = app.send(subscribe_from("10.0.0.1", Some("5.6.7.8"))).await;
+    let third = app.send(subscribe_from("10.0.0.1", Some("1.2.3.4"))).await;
+
+    // Assert
+    assert_ne!(first.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_ne!(second.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(third.status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[sqlx::test]
+async fn an_untrusted_peer_cannot_spoof_forwarded_for(pool: PgPool) {
+    // Arrange
+    let app = spawn_test_app_with(
+        pool,
+        mock_aws_sesv2(),
+        configuration_with_limit(1),
+        TestOverrides::default(),
+    )
+    .await
+    .unwrap();
+
+    // Act
+    let first = app.send(subscribe_from("9.9.9.9", Some("1.2.3.4"))).await;
+    let second = app.send(subscribe_from("9.9.9.9", Some("5.6.7.8"))).await;
+
+    // Assert
+    assert_ne!(first.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(second.status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[sqlx::test]
+async fn the_postgres_store_shares_counters_between_instances(pool: PgPool) {
+    // Arrange
+    let first_instance = PostgresRateLimiter::new(pool.clone());
+    let second_instance: Arc<dyn RateLimitStore> = Arc::new(PostgresRateLimiter::new(pool));
+    let quota = Quota::new(2, Duration::from_secs(60));
+
+    // Act
+    let first = first_instance.hit("ip:1.2.3.4", quota).await.unwrap();
+    let second = second_instance.hit("ip:1.2.3.4", quota).await.unwrap();
+    let third = first_instance.hit("ip:1.2.3.4", quota).await.unwrap();
+
+    // Assert
+    assert_ok!(first);
+    assert_ok!(second);
+    assert_err!(third);
+}
+
+#[sqlx::test]
+async fn the_postgres_store_deletes_expired_windows(pool: PgPool) {
+    // Arrange
+    let store = PostgresRateLimiter::new(pool.clone());
+    let quota = Quota::new(1, Duration::from_secs(60));
+    store.hit("ip:1.2.3.4", quota).await.unwrap().unwrap();
+    sqlx::query!(
+        r#"
+          INSERT INTO rate_limits (key, window_started_at, hits, expires_at)
+          VALUES ('ip:5.6.7.8', now() - INTERVAL '2 hours', 3, now() - INTERVAL '1 hour')
+        "#
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    // Act
+    let deleted = store.prune_expired().await.unwrap();
+
+    // Assert
+    assert_eq!(deleted, 1);
+    let keys: Vec<String> = sqlx::query_scalar!("SELECT key FROM rate_limits")
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(keys, vec!["ip:1.2.3.4".to_string()]);
+}
+
+#[sqlx::test]
+async fn cooldowns_do_not_store_the_address(pool: PgPool) {
+    // Arrange
+    let store: Arc<dyn RateLimitStore> = Arc::new(PostgresRateLimiter::new(pool.clone()));
+    let cooldown = Cooldown::new("resend_cooldown", Duration::from_secs(60), store);
+
+    // Act
+    let first = cooldown.start("ursula@example.com").await.unwrap();
+    let second = cooldown.start("ursula@example.com").await.unwrap();
+
+    // Assert
+    assert!(first);
+    assert!(!second);
+    let keys: Vec<String> = sqlx::query_scalar!("SELECT key FROM rate_limits")
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(keys.len(), 1);
+    assert!(!keys[0].contains("ursula"));
+}
@@ -0,0 +1,27 @@
+use aws_credential_types::provider::ProvideCredentials;
+use newsletter::{configuration::aws_credentials::StaticCredentials, reload::CredentialReloader};
+use secrecy::SecretString;
+use sqlx::PgPool;
+
+use crate::helpers::test_configuration;
+
+#[sqlx::test]
+async fn reloading_rotates_database_and_ses_credentials(pool: PgPool) {
+    // Arrange
+    let ses_credentials =
+        StaticCredentials::new(&SecretString::from("old-id"), &SecretString::from("old"));
+    let reloader = CredentialReloader::new(pool.clone(), Some(ses_credentials.clone()));
+    let mut configuration = test_configuration();
+    configuration.database.username = "rotated".into();
+    configuration.aws.access_key_id = Some(SecretString::from("new-id"));
+    configuration.aws.secret_access_key = Some(SecretString::from("new"));
+
+    // Act
+    reloader.apply(&configuration);
+
+    // Assert
+    assert_eq!(pool.connect_options().get_username(), "rotated");
+    let provided = ses_credentials.provide_credentials().await.unwrap();
+    assert_eq!(provided.access_key_id(), "new-id");
+    assert_eq!(provided.secret_access_key(), "new");
+}
@@ -0,0 +1,138 @@
+use std::sync::{Arc, RwLock};
+
+use aws_sdk_sesv2::{operation::send_email::SendEmailOutput, Client};
+use aws_smithy_mocks_experimental::{mock, mock_client, RuleMode};
+use axum::{
+    body::Body,
+    http::{self, Request, Response, StatusCode},
+};
+use http_body_util::BodyExt;
+use sqlx::PgPool;
+
+use crate::helpers::{mock_aws_sesv2, spawn_test_app};
+
+fn subscribe_request(request_id: Option<&str>, form_data: &'static str) -> Request<Body> {
+    let mut builder = Request::builder()
+        .method(http::Method::POST)
+        .uri("/subscriptions")
+        .header(
+            http::header::CONTENT_TYPE,
+            "application/x-www-form-urlencoded",
+        );
+    if let Some(request_id) = request_id {
+        builder = builder.header("x-request-id", request_id);
+    }
+    builder.body(Body::from(form_data)).unwrap()
+}
+
+fn request_id_of(response: &Response<Body>) -> String {
+    response.headers()["x-request-id"]
+        .to_str()
+        .unwrap()
+        .to_string()
+}
+
+async fn json_body(response: Response<Body>) -> serde_json::Value {
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    serde_json::from_slice(&body).unwrap()
+}
+
+fn mock_aws_sesv2_capturing_tags(captured_tags: Arc<RwLock<Vec<(String, String)>>>) -> Client {
+    let mock_send_email = mock!(Client::send_email)
+        .match_requests(move |req| {
+            *captured_tags.write().unwrap() = req
+                .email_tags()
+                .iter()
+                .map(|tag| (tag.name().to_string(), tag.value().to_string()))
+                .collect();
+            true
+        })
+        .then_output(|| {
+            SendEmailOutput::builder()
+                .message_id("newsletter-email")
+                .build()
+        });
+    mock_client!(aws_sdk_sesv2, RuleMode::Sequential, [&mock_send_email])
+}
+
+#[sqlx::test]
+async fn a_request_id_is_generated_when_none_is_sent(pool: PgPool) {
+    // Arrange
+    let app = spawn_test_app(pool, mock_aws_sesv2()).await.unwrap();
+
+    // Act
+    let response = app.get("/health_check").await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(uuid::Uuid::parse_str(&request_id_of(&response)).is_ok());
+}
+
+#[sqlx::test]
+async fn an_incoming_request_id_is_echoed_in_the_error_body(pool: PgPool) {
+    // Arrange
+    let app = spawn_test_app(pool, mock_aws_sesv2()).await.unwrap();
+
+    // Act
+    let response = app
+        .send(subscribe_request(
+            Some("support-ticket_42"),
+            "name=&email=not-an-email",
+        ))
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(request_id_of(&response), "support-ticket_42");
+    assert_eq!(json_body(response).await["request_id"], "support-ticket_42");
+}
+
+#[sqlx::test]
+async fn a_malformed_request_id_is_replaced(pool: PgPool) {
+    // Arrange
+    let app = spawn_test_app(pool, mock_aws_sesv2()).await.unwrap();
+
+    // Act
+    let response = app
+        .get("/subscriptions/confirm?subscription_token=unknown")
+        .await;
+    let generated = request_id_of(&response);
+    let response = app
+        .send(
+            Request::builder()
+                .uri("/subscriptions/confirm?subscription_token=unknown")
+                .header("x-request-id", "not allowed; <script>")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+
+    // Assert
+    let replaced = request_id_of(&response);
+    assert_ne!(replaced, "not allowed; <script>");
+    assert_ne!(replaced, generated);
+    assert_eq!(json_body(response).await["request_id"], replaced.as_str());
+}
+
+#[sqlx::test]
+async fn the_request_id_is_attached_to_outgoing_emails(pool: PgPool) {
+    // Arrange
+    let captured_tags = Arc::new(RwLock::new(vec![]));
+    let client = mock_aws_sesv2_capturing_tags(captured_tags.clone());
+    let app = spawn_test_app(pool, client).await.unwrap();
+
+    // Act
+    let response = app
+        .send(subscribe_request(
+            Some("trace-me"),
+            "name=Andrii%20Konotop&email=aws.test.receiver@gmail.com",
+        ))
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        *captured_tags.read().unwrap(),
+        vec![("request_id".to_string(), "trace-me".to_string())]
+    );
+}
@@ -0,0 +1,140 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use axum::{middleware, routing::get, Router};
+use tokio::sync::{mpsc, oneshot};
+
+use newsletter::{
+    metrics::{middleware::track_http_metrics, Metrics},
+    shutdown::{Shutdown, ShutdownSummary},
+    startup::start_server,
+};
+
+use crate::helpers::test_configuration;
+
+#[tokio::test]
+async fn an_idle_server_shuts_down_cleanly() {
+    // Arrange
+    let mut configuration = test_configuration();
+    configuration.application.host = "127.0.0.1".into();
+    configuration.application.port = 0;
+    let shutdown = Shutdown::new();
+    let app = Router::new().route("/", get(|| async { "OK" }));
+
+    let server = tokio::spawn({
+        let shutdown = shutdown.clone();
+        async move { start_server(&configuration, app, shutdown, Arc::new(Metrics::new())).await }
+    });
+
+    // Act
+    shutdown.trigger();
+    let summary = server.await.unwrap().unwrap();
+
+    // Assert
+    assert_eq!(summary, ShutdownSummary::default());
+}
+
+#[tokio::test]
+async fn workers_that_outlive_the_drain_timeout_are_reported() {
+    // Arrange
+    let mut configuration = test_configuration();
+    configuration.application.host = "127.0.0.1".into();
+    configuration.application.port = 0;
+    configuration.application.shutdown_timeout_seconds = 1;
+    let shutdown = Shutdown::new();
+    shutdown.spawn_worker("stuck", |_| std::future::pending());
+    shutdown.spawn_worker("polite", |shutdown| async move {
+        shutdown.triggered().await;
+    });
+    let app = Router::new().route("/", get(|| async { "OK" }));
+
+    let server = tokio::spawn({
+        let shutdown = shutdown.clone();
+        async move { start_server(&configuration, app, shutdown, Arc::new(Metrics::new())).await }
+    });
+
+    // Act
+    shutdown.trigger();
+    let summary = server.await.unwrap().unwrap();
+
+    // Assert
+    assert_eq!(summary.workers_aborted, vec!["stuck"]);
+    assert_eq!(summary.requests_cut_off, 0);
+}
+
+/// Tells the test when the handler holding it is dropped, i.e. cancelled.
+struct DropSignal(Option<oneshot::Sender<()>>);
+
+impl Drop for DropSignal {
+    fn drop(&mut self) {
+        let _ = self.0.take().unwrap().send(());
+    }
+}
+
+#[tokio::test]
+async fn slow_requests_are_cut_off_at_the_drain_timeout() {
+    // Arrange
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let mut configuration = test_configuration();
+    configuration.application.host = "127.0.0.1".into();
+    configuration.application.port = port;
+    configuration.application.shutdown_timeout_seconds = 1;
+    let shutdown = Shutdown::new();
+    let metrics = Arc::new(Metrics::new());
+    let (started_tx, mut started) = mpsc::channel(1);
+    let (dropped_tx, dropped) = oneshot::channel();
+    let dropped_tx = Arc::new(std::sync::Mutex::new(Some(dropped_tx)));
+    let app = Router::new()
+        .route(
+            "/slow",
+            get(move || {
+                let started_tx = started_tx.clone();
+                let guard = DropSignal(dropped_tx.lock().unwrap().take());
+                async move {
+                    let _guard = guard;
+                    started_tx.send(()).await.unwrap();
+                    std::future::pending::<()>().await
+                }
+            }),
+        )
+        .layer(middleware::from_fn_with_state(
+            metrics.clone(),
+            track_http_metrics,
+        ));
+
+    let server = tokio::spawn({
+        let shutdown = shutdown.clone();
+        let metrics = metrics.clone();
+        async move { start_server(&configuration, app, shutdown, metrics).await }
+    });
+    let client =
+        tokio::spawn(async move { reqwest::get(format!("http://127.0.0.1:{}/slow", port)).await });
+    tokio::time::timeout(Duration::from_secs(5), started.recv())
+        .await
+        .expect("The slow request never reached its handler");
+
+    // Act
+    let shutdown_started = Instant::now();
+    shutdown.trigger();
+    let summary = tokio::time::timeout(Duration::from_secs(5), server)
+        .await
+        .expect("Shutdown outlived the drain timeout")
+        .unwrap()
+        .unwrap();
+
+    // Assert
+    assert!(shutdown_started.elapsed() < Duration::from_secs(3));
+    assert_eq!(summary.requests_cut_off, 1);
+    assert_eq!(metrics.requests_in_flight(), 0);
+    tokio::time::timeout(Duration::from_secs(1), dropped)
+        .await
+        .expect("The slow handler is still running")
+        .unwrap();
+    assert!(client.await.unwrap().is_err());
+}
@@ -0,0 +1,445 @@
+use std::sync::{Arc, RwLock};
+
+use aws_sdk_sesv2::{
+    operation::send_email::{SendEmailInput, SendEmailOutput},
+    Client,
+};
+use aws_smithy_mocks_experimental::{mock, mock_client, RuleMode};
+use axum::{
+    body::Body,
+    http::{self, Request, StatusCode},
+};
+use http_body_util::BodyExt;
+use newsletter::{
+    configuration::config::Configuration,
+    database::subscribers::{postgres::PostgresSubscriberRepository, SubscriberRepository},
+    domain::{NewSubscriber, SubscriberEmail, SubscriberName},
+    signed_links::{LinkAction, LinkSigner},
+};
+use secrecy::SecretString;
+use sqlx::PgPool;
+
+use crate::helpers::{
+    extract_link, get_confirmation_links, mock_aws_sesv2, mock_aws_sesv2_with_request_capture,
+    spawn_test_app_with, test_configuration, TestOverrides,
+};
+
+fn signed_links_configuration() -> Configuration {
+    let mut c = test_configuration();
+    c.signed_links.confirmation_links = true;
+    c.signed_links.keys.insert(
+        "2024-12".to_string(),
+        SecretString::from("a-test-key-that-is-long-enough-to-sign"),
+    );
+    c.signed_links.signing_key = Some("2024-12".to_string());
+    c
+}
+
+async fn confirmed_subscriber(pool: &PgPool) -> uuid::Uuid {
+    PostgresSubscriberRepository::new(pool.clone())
+        .insert_confirmed(&NewSubscriber {
+            email: SubscriberEmail::parse("ursula@example.com".to_string()).unwrap(),
+            name: SubscriberName::parse("Ursula Le Guin".to_string()).unwrap(),
+            locale: "en".to_string(),
+        })
+        .await
+        .unwrap()
+}
+
+#[sqlx::test]
+async fn signed_confirmation_links_need_no_stored_token(pool: PgPool) {
+    // Arrange
+    let captured_request_content = Arc::new(RwLock::new(None));
+    let aws_client = mock_aws_sesv2_with_request_capture(captured_request_content.clone());
+    let app = spawn_test_app_with(
+        pool,
+        aws_client,
+        signed_links_configuration(),
+        TestOverrides::default(),
+    )
+    .await
+    .unwrap();
+    let form_data = "name=Andrii%20Konotop&email=aws.test.receiver@gmail.com";
+
+    // Act
+    let _ = app.post("/subscriptions", form_data).await;
+    let confirmation_link = get_confirmation_links(captured_request_content.clone()).plain_text;
+    let response = app.get(confirmation_link.as_str()).await;
+
+    // Assert
+    assert!(confirmation_link
+        .query()
+        .unwrap()
+        .starts_with("link=2024-12."));
+    assert_eq!(response.status(), StatusCode::OK);
+    let tokens = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM subscription_tokens"#)
+        .fetch_one(&app.db.pool)
+        .await
+        .unwrap();
+    assert_eq!(tokens, 0);
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db.pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "confirmed");
+}
+
+#[sqlx::test]
+async fn expired_and_tampered_links_are_rejected(pool: PgPool) {
+    // Arrange
+    let configuration = signed_links_configuration();
+    let signer = LinkSigner::new(&configuration.signed_links);
+    let subscriber_id = confirmed_subscriber(&pool).await;
+    let app = spawn_test_app_with(
+        pool,
+        mock_aws_sesv2(),
+        configuration,
+        TestOverrides::default(),
+    )
+    .await
+    .unwrap();
+    let expired = signer
+        .sign_at(LinkAction::Unsubscribe, subscriber_id, 1)
+        .unwrap();
+    let tampered = signer
+        .unsubscribe_url("", uuid::Uuid::nil())
+        .unwrap()
+        .replace(
+            &uuid::Uuid::nil().simple().to_string(),
+            &subscriber_id.simple().to_string(),
+        );
+
+    // Act
+    let expired = app
+        .get(&format!("/subscriptions/unsubscribe?link={}", expired))
+        .await;
+    let tampered = app.get(&tampered).await;
+
+    // Assert
+    assert_eq!(expired.status(), StatusCode::GONE);
+    assert_eq!(tampered.status(), StatusCode::BAD_REQUEST);
+}
+
+#[sqlx::test]
+async fn browsers_following_an_expired_link_are_told_so(pool: PgPool) {
+    // Arrange
+    let configuration = signed_links_configuration();
+    let signer = LinkSigner::new(&configuration.signed_links);
+    let subscriber_id = confirmed_subscriber(&pool).await;
+    let app = spawn_test_app_with(
+        pool,
+        mock_aws_sesv2(),
+        configuration,
+        TestOverrides::default(),
+    )
+    .await
+    .unwrap();
+    let expired = signer
+        .sign_at(LinkAction::Confirm, subscriber_id, 1)
+        .unwrap();
+
+    // Act
+    let response = app
+        .get_as_browser(&format!("/subscriptions/confirm?link={}", expired))
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::GONE);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    assert!(String::from_utf8_lossy(&body).contains("This link has expired"));
+}
+
+#[sqlx::test]
+async fn a_signed_link_unsubscribes(pool: PgPool) {
+    // Arrange
+    let configuration = signed_links_configuration();
+    let signer = LinkSigner::new(&configuration.signed_links);
+    let subscriber_id = confirmed_subscriber(&pool).await;
+    let app = spawn_test_app_with(
+        pool,
+        mock_aws_sesv2(),
+        configuration,
+        TestOverrides::default(),
+    )
+    .await
+    .unwrap();
+    let unsubscribe_link = signer.unsubscribe_url("", subscriber_id).unwrap();
+
+    // Act
+    let response = app
+        .post(&unsubscribe_link, "List-Unsubscribe=One-Click")
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db.pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "unsubscribed");
+}
+
+#[sqlx::test]
+async fn following_an_unsubscribe_link_only_asks_first(pool: PgPool) {
+    // Arrange
+    let configuration = signed_links_configuration();
+    let signer = LinkSigner::new(&configuration.signed_links);
+    let subscriber_id = confirmed_subscriber(&pool).await;
+    let app = spawn_test_app_with(
+        pool,
+        mock_aws_sesv2(),
+        configuration,
+        TestOverrides::default(),
+    )
+    .await
+    .unwrap();
+    let unsubscribe_link = signer.unsubscribe_url("", subscriber_id).unwrap();
+
+    // Act
+    let response = app.get_as_browser(&unsubscribe_link).await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body = String::from_utf8_lossy(&body);
+    assert!(body.contains(r#"<form method="post""#), "{}", body);
+    assert!(body.contains(&unsubscribe_link), "{}", body);
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db.pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "confirmed");
+}
+
+#[sqlx::test]
+async fn browsers_get_a_page_when_unsubscribing_fails(pool: PgPool) {
+    // Arrange
+    let configuration = signed_links_configuration();
+    let signer = LinkSigner::new(&configuration.signed_links);
+    let app = spawn_test_app_with(
+        pool,
+        mock_aws_sesv2(),
+        configuration,
+        TestOverrides::default(),
+    )
+    .await
+    .unwrap();
+    let unknown_subscriber = signer.unsubscribe_url("", uuid::Uuid::new_v4()).unwrap();
+
+    // Act
+    let forged = app
+        .get_as_browser("/subscriptions/unsubscribe?link=forged")
+        .await;
+    let unknown = app
+        .send(
+            Request::builder()
+                .method(http::Method::POST)
+                .uri(&unknown_subscriber)
+                .header(http::header::ACCEPT, "text/html")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+
+    // Assert
+    for (response, status, title) in [
+        (forged, StatusCode::BAD_REQUEST, "This link doesn't work"),
+        (unknown, StatusCode::NOT_FOUND, "You're not subscribed"),
+    ] {
+        assert_eq!(response.status(), status);
+        assert_eq!(response.headers()["vary"], "accept");
+        assert!(response.headers()[http::header::CONTENT_TYPE]
+            .to_str()
+            .unwrap()
+            .starts_with("text/html"));
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body = String::from_utf8_lossy(&body);
+        assert!(body.contains(title), "{}", body);
+    }
+}
+
+/// Keeps the last email sent.
+fn capturing_client() -> (Client, Arc<RwLock<Option<SendEmailInput>>>) {
+    let captured = Arc::new(RwLock::new(None::<SendEmailInput>));
+    let capture = captured.clone();
+    let mock_send_email = mock!(Client::send_email)
+        .match_requests(move |request| {
+            *capture.write().unwrap() = Some(request.clone());
+            true
+        })
+        .then_output(|| SendEmailOutput::builder().message_id("issue").build());
+    let aws_client = mock_client!(aws_sdk_sesv2, RuleMode::MatchAny, [&mock_send_email]);
+    (aws_client, captured)
+}
+
+fn issue(html: &str) -> serde_json::Value {
+    serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "text": "Newsletter body as plain text",
+            "html": html,
+        }
+    })
+}
+
+#[sqlx::test]
+async fn newsletter_issues_carry_a_one_click_unsubscribe_link(pool: PgPool) {
+    // Arrange
+    let (aws_client, captured) = capturing_client();
+    confirmed_subscriber(&pool).await;
+    let app = spawn_test_app_with(
+        pool,
+        aws_client,
+        signed_links_configuration(),
+        TestOverrides::default(),
+    )
+    .await
+    .unwrap();
+
+    // Act
+    let response = app
+        .post_json("/newsletters", issue("<p>Newsletter body as HTML</p>"))
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    let request = captured.read().unwrap().clone().unwrap();
+    let simple = request.content().unwrap().simple().unwrap();
+    let headers: Vec<_> = simple
+        .headers()
+        .iter()
+        .map(|header| (header.name(), header.value()))
+        .collect();
+    let text_link = extract_link(simple.body().unwrap().text().unwrap().data());
+    assert_eq!(text_link.path(), "/subscriptions/unsubscribe");
+    assert_eq!(
+        headers,
+        vec![
+            ("List-Unsubscribe", format!("<{}>", text_link).as_str()),
+            ("List-Unsubscribe-Post", "List-Unsubscribe=One-Click"),
+        ]
+    );
+    let unsubscribe = app
+        .post(
+            &format!("{}?{}", text_link.path(), text_link.query().unwrap()),
+            "List-Unsubscribe=One-Click",
+        )
+        .await;
+    assert_eq!(unsubscribe.status(), StatusCode::OK);
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db.pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "unsubscribed");
+}
+
+#[sqlx::test]
+async fn a_confirmation_link_does_not_unsubscribe(pool: PgPool) {
+    // Arrange
+    let configuration = signed_links_configuration();
+    let signer = LinkSigner::new(&configuration.signed_links);
+    let subscriber_id = confirmed_subscriber(&pool).await;
+    let app = spawn_test_app_with(
+        pool,
+        mock_aws_sesv2(),
+        configuration,
+        TestOverrides::default(),
+    )
+    .await
+    .unwrap();
+    let confirmation_link = signer.confirmation_url("", subscriber_id).unwrap();
+
+    // Act
+    let response = app
+        .get(&confirmation_link.replace("/confirm?", "/unsubscribe?"))
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[sqlx::test]
+async fn newsletter_issues_can_be_viewed_in_the_browser(pool: PgPool) {
+    // Arrange
+    let (aws_client, captured) = capturing_client();
+    confirmed_subscriber(&pool).await;
+    let app = spawn_test_app_with(
+        pool,
+        aws_client,
+        signed_links_configuration(),
+        TestOverrides::default(),
+    )
+    .await
+    .unwrap();
+    let _ = app
+        .post_json("/newsletters", issue("<p>Newsletter body as HTML</p>"))
+        .await;
+    let request = captured.read().unwrap().clone().unwrap();
+    let text = request.content().unwrap().simple().unwrap().body().unwrap();
+    let view_link = text.text().unwrap().data().rsplit(' ').next().unwrap();
+    let view_link = reqwest::Url::parse(view_link).unwrap();
+    let view_path = format!("{}?{}", view_link.path(), view_link.query().unwrap());
+
+    // Act
+    let response = app.get_as_browser(&view_path).await;
+    let other_issue = app
+        .get(&view_path.replace(
+            view_link.path(),
+            &format!("/newsletters/issues/{}", uuid::Uuid::new_v4().simple()),
+        ))
+        .await;
+
+    // Assert
+    assert!(view_link.path().starts_with("/newsletters/issues/"));
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body = String::from_utf8_lossy(&body);
+    assert!(body.contains("<title>Newsletter title</title>"), "{}", body);
+    assert!(body.contains("<p>Newsletter body as HTML</p>"), "{}", body);
+    assert_eq!(other_issue.status(), StatusCode::BAD_REQUEST);
+}
+
+#[sqlx::test]
+async fn tracked_links_redirect_to_their_signed_target(pool: PgPool) {
+    // Arrange
+    let (aws_client, captured) = capturing_client();
+    confirmed_subscriber(&pool).await;
+    let mut configuration = signed_links_configuration();
+    configuration.signed_links.track_clicks = true;
+    let app = spawn_test_app_with(pool, aws_client, configuration, TestOverrides::default())
+        .await
+        .unwrap();
+    let _ = app
+        .post_json(
+            "/newsletters",
+            issue(r#"<p><a href="https://example.com/post">Read more</a></p>"#),
+        )
+        .await;
+    let request = captured.read().unwrap().clone().unwrap();
+    let html = request.content().unwrap().simple().unwrap().body().unwrap();
+    let html = html.html().unwrap().data();
+    let href = html
+        .split("href=\"")
+        .nth(1)
+        .unwrap()
+        .split('"')
+        .next()
+        .unwrap();
+    let tracked = reqwest::Url::parse(&href.replace("&amp;", "&")).unwrap();
+    let tracked = format!("{}?{}", tracked.path(), tracked.query().unwrap());
+
+    // Act
+    let response = app.get(&tracked).await;
+    let redirected_elsewhere = app
+        .get(&tracked.replace("example.com", "evil.example"))
+        .await;
+
+    // Assert
+    assert!(tracked.starts_with("/newsletters/click?"), "{}", tracked);
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    assert_eq!(response.headers()["location"], "https://example.com/post");
+    assert_eq!(redirected_elsewhere.status(), StatusCode::BAD_REQUEST);
+    let metrics = app.get("/metrics").await;
+    let metrics = metrics.into_body().collect().await.unwrap().to_bytes();
+    assert!(String::from_utf8_lossy(&metrics).contains("newsletter_link_clicks_total 1"));
+}
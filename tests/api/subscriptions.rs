@@ -1,10 +1,21 @@
-use std::sync::{Arc, RwLock};
+use std::{
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use axum::{
+    body::Body,
+    http::{self, Request, StatusCode},
+};
+use http_body_util::BodyExt;
+use sqlx::{types::chrono::Utc, PgPool};
 
-use axum::http::StatusCode;
-use sqlx::PgPool;
+use newsletter::email_validation::mx::StubMxResolver;
 
 use crate::helpers::{
-    get_confirmation_links, mock_aws_sesv2, mock_aws_sesv2_with_request_capture, spawn_test_app,
+    get_confirmation_links, mock_aws_sesv2, mock_aws_sesv2_no_requests,
+    mock_aws_sesv2_with_request_capture, spawn_test_app, spawn_test_app_with, test_configuration,
+    TestOverrides,
 };
 
 #[sqlx::test]
@@ -111,6 +122,65 @@ async fn subscribe_sends_a_confirmation_email_with_a_link(pool: PgPool) {
     assert_eq!(confirmation_links.html, confirmation_links.plain_text);
 }
 
+#[sqlx::test]
+async fn subscribe_localises_the_confirmation_email_from_accept_language(pool: PgPool) {
+    // Arrange
+    let captured_request_content = Arc::new(RwLock::new(None));
+    let client = mock_aws_sesv2_with_request_capture(captured_request_content.clone());
+    let app = spawn_test_app(pool, client).await.unwrap();
+
+    let request = Request::builder()
+        .method(http::Method::POST)
+        .uri("/subscriptions")
+        .header(
+            http::header::CONTENT_TYPE,
+            "application/x-www-form-urlencoded",
+        )
+        .header(http::header::ACCEPT_LANGUAGE, "de-AT, en;q=0.5")
+        .body(Body::from(
+            "name=Andrii%20Konotop&email=aws.test.receiver@gmail.com",
+        ))
+        .unwrap();
+
+    // Act
+    let response = app.send(request).await;
+
+    // Assert
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["message"], "Bitte schau in dein E-Mail-Postfach");
+
+    let (html, text) = captured_request_content.read().unwrap().clone().unwrap();
+    assert!(text.starts_with("Willkommen bei unserem Newsletter!"));
+    assert!(html.contains(r#"<html lang="de">"#));
+
+    let saved = sqlx::query!("SELECT locale FROM subscriptions")
+        .fetch_one(&app.db.pool)
+        .await
+        .expect("Failed to fetch saved subscription");
+    assert_eq!(saved.locale, "de");
+}
+
+#[sqlx::test]
+async fn subscribe_prefers_the_locale_picked_on_the_form(pool: PgPool) {
+    // Arrange
+    let app = spawn_test_app(pool, mock_aws_sesv2()).await.unwrap();
+
+    let form_data = "name=Andrii%20Konotop&email=aws.test.receiver@gmail.com&locale=uk";
+
+    // Act
+    let response = app.post("/subscriptions", form_data).await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let saved = sqlx::query!("SELECT locale FROM subscriptions")
+        .fetch_one(&app.db.pool)
+        .await
+        .expect("Failed to fetch saved subscription");
+    assert_eq!(saved.locale, "uk");
+}
+
 #[sqlx::test]
 async fn subscribe_fails_if_there_is_a_fatal_database_error(pool: PgPool) {
     // Arrange
@@ -119,7 +189,7 @@ async fn subscribe_fails_if_there_is_a_fatal_database_error(pool: PgPool) {
     let form_data = "name=Andrii%20Konotop&email=aws.test.receiver@gmail.com";
 
     // Act
-    sqlx::query!("ALTER TABLE subscription_tokens DROP COLUMN subscription_token;")
+    sqlx::query!("ALTER TABLE subscription_tokens DROP COLUMN token_hash;")
         .execute(&app.db.pool)
         .await
         .expect("Failed to drop subscriptions table.");
@@ -128,3 +198,219 @@ async fn subscribe_fails_if_there_is_a_fatal_database_error(pool: PgPool) {
     // Assert
     assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
 }
+
+#[sqlx::test]
+async fn subscribe_rejects_disposable_addresses(pool: PgPool) {
+    // Arrange
+    let client = mock_aws_sesv2_no_requests();
+    let app = spawn_test_app(pool, client).await.unwrap();
+    let form_data = "name=Andrii%20Konotop&email=andrii%40mailinator.com";
+
+    // Act
+    let response = app.post("/subscriptions", form_data).await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[sqlx::test]
+async fn subscribe_suggests_a_fix_for_a_mistyped_domain(pool: PgPool) {
+    // Arrange
+    let client = mock_aws_sesv2_no_requests();
+    let app = spawn_test_app(pool, client).await.unwrap();
+    let form_data = "name=Andrii%20Konotop&email=aws.test.receiver%40gmial.com";
+
+    // Act
+    let response = app.post("/subscriptions", form_data).await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["message"], "Did you mean aws.test.receiver@gmail.com?");
+}
+
+#[sqlx::test]
+async fn subscribe_rejects_domains_without_a_mail_server(pool: PgPool) {
+    // Arrange
+    let client = mock_aws_sesv2_no_requests();
+    let overrides = TestOverrides {
+        mx_resolver: Some(Arc::new(StubMxResolver::new(["gmail.com"]))),
+        ..Default::default()
+    };
+    let app = spawn_test_app_with(pool, client, test_configuration(), overrides)
+        .await
+        .unwrap();
+    let form_data = "name=Andrii%20Konotop&email=andrii%40no-mail-server.example";
+
+    // Act
+    let response = app.post("/subscriptions", form_data).await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[sqlx::test]
+async fn subscribe_treats_addresses_differing_in_case_as_the_same(pool: PgPool) {
+    // Arrange
+    let client = mock_aws_sesv2();
+    let app = spawn_test_app(pool, client).await.unwrap();
+
+    // Act
+    let first = app
+        .post(
+            "/subscriptions",
+            "name=Andrii%20Konotop&email=aws.test.receiver@gmail.com",
+        )
+        .await;
+    let second = app
+        .post(
+            "/subscriptions",
+            "name=Andrii%20Konotop&email=AWS.Test.Receiver@GMail.com",
+        )
+        .await;
+
+    // Assert
+    assert_eq!(first.status(), StatusCode::OK);
+    // The same answer as for a new address, not a server error.
+    assert_eq!(second.status(), StatusCode::OK);
+    let first = first.into_body().collect().await.unwrap().to_bytes();
+    let second = second.into_body().collect().await.unwrap().to_bytes();
+    assert_eq!(first, second);
+    let saved = sqlx::query!("SELECT email, email_canonical FROM subscriptions")
+        .fetch_all(&app.db.pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.len(), 1);
+    assert_eq!(
+        saved[0].email_canonical.as_deref(),
+        Some("aws.test.receiver@gmail.com")
+    );
+}
+
+#[sqlx::test]
+async fn signing_up_again_after_unsubscribing_starts_over(pool: PgPool) {
+    // Arrange
+    let captured_request_content = Arc::new(RwLock::new(None));
+    let client = mock_aws_sesv2_with_request_capture(captured_request_content.clone());
+    let app = spawn_test_app(pool, client).await.unwrap();
+    let form_data = "name=Andrii%20Konotop&email=aws.test.receiver@gmail.com";
+    let _ = app.post("/subscriptions", form_data).await;
+    sqlx::query!(
+        "UPDATE subscriptions SET status = 'unsubscribed', subscribed_at = now() - INTERVAL '90 days'"
+    )
+    .execute(&app.db.pool)
+    .await
+    .unwrap();
+
+    // Act
+    let response = app.post("/subscriptions", form_data).await;
+    let confirmation_link = get_confirmation_links(captured_request_content.clone()).plain_text;
+    let pending = sqlx::query!("SELECT status, subscribed_at FROM subscriptions")
+        .fetch_one(&app.db.pool)
+        .await
+        .unwrap();
+    let confirmed = app
+        .get(&format!(
+            "{}?{}",
+            confirmation_link.path(),
+            confirmation_link.query().unwrap()
+        ))
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(pending.status, "pending_confirmation");
+    assert!(pending.subscribed_at > Utc::now() - Duration::from_secs(60));
+    assert_eq!(confirmed.status(), StatusCode::OK);
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_all(&app.db.pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.len(), 1);
+    assert_eq!(saved[0].status, "confirmed");
+}
+
+#[sqlx::test]
+async fn error_messages_follow_accept_language(pool: PgPool) {
+    // Arrange
+    let app = spawn_test_app(pool, mock_aws_sesv2_no_requests())
+        .await
+        .unwrap();
+    let german = |method: http::Method, uri: &str, body: &'static str| {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(
+                http::header::CONTENT_TYPE,
+                "application/x-www-form-urlencoded",
+            )
+            .header(http::header::ACCEPT_LANGUAGE, "de")
+            .body(Body::from(body))
+            .unwrap()
+    };
+
+    // Act
+    let invalid_email = app
+        .send(german(
+            http::Method::POST,
+            "/subscriptions",
+            "name=Andrii%20Konotop&email=not-an-email",
+        ))
+        .await;
+    let invalid_link = app
+        .send(german(
+            http::Method::POST,
+            "/subscriptions/unsubscribe?link=forged",
+            "",
+        ))
+        .await;
+
+    // Assert
+    let mut messages = vec![];
+    for response in [invalid_email, invalid_link] {
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        messages.push(body["message"].as_str().unwrap().to_string());
+    }
+    assert_eq!(
+        messages,
+        vec![
+            "not-an-email ist keine gültige E-Mail-Adresse.",
+            "Dieser Link funktioniert nicht, vielleicht wurde er falsch abgetippt oder durch einen neueren ersetzt.",
+        ]
+    );
+}
+
+#[sqlx::test]
+async fn error_messages_follow_the_locale_picked_on_the_form(pool: PgPool) {
+    // Arrange
+    let app = spawn_test_app(pool, mock_aws_sesv2_no_requests())
+        .await
+        .unwrap();
+    let request = Request::builder()
+        .method(http::Method::POST)
+        .uri("/subscriptions")
+        .header(
+            http::header::CONTENT_TYPE,
+            "application/x-www-form-urlencoded",
+        )
+        .header(http::header::ACCEPT_LANGUAGE, "en")
+        .body(Body::from(
+            "name=Andrii%20Konotop&email=not-an-email&locale=de",
+        ))
+        .unwrap();
+
+    // Act
+    let response = app.send(request).await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(
+        body["message"],
+        "not-an-email ist keine gültige E-Mail-Adresse."
+    );
+}
@@ -1,12 +1,23 @@
 use std::sync::{Arc, RwLock};
 
-use axum::http::StatusCode;
+use axum::{body::Body, http::StatusCode, response::Response};
+use http_body_util::BodyExt;
+use newsletter::{
+    database::subscribers::{memory::InMemorySubscriberRepository, SubscriberRepository},
+    domain::subscription_token_digest,
+};
 use sqlx::PgPool;
 
 use crate::helpers::{
     get_confirmation_links, mock_aws_sesv2, mock_aws_sesv2_with_request_capture, spawn_test_app,
+    spawn_test_app_with, spawn_test_app_without_database, test_configuration, TestOverrides,
 };
 
+async fn body_text(response: Response<Body>) -> String {
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    String::from_utf8(bytes.to_vec()).unwrap()
+}
+
 #[sqlx::test]
 async fn confirmations_without_token_are_rejected_with_a_400(pool: PgPool) {
     // Arrange
@@ -65,3 +76,205 @@ async fn clicking_on_the_confirmation_link_confirms_a_subscriber(pool: PgPool) {
     assert_eq!(saved.email, "aws.test.receiver@gmail.com");
     assert_eq!(saved.status, "confirmed");
 }
+
+#[sqlx::test]
+async fn confirming_a_bounced_subscriber_is_rejected_with_a_409(pool: PgPool) {
+    // Arrange
+    let captured_request_content = Arc::new(RwLock::new(None));
+    let aws_client = mock_aws_sesv2_with_request_capture(captured_request_content.clone());
+    let app = spawn_test_app(pool, aws_client).await.unwrap();
+    let form_data = "name=Andrii%20Konotop&email=aws.test.receiver@gmail.com";
+    let _ = app.post("/subscriptions", form_data).await;
+    let confirmation_link = get_confirmation_links(captured_request_content.clone()).plain_text;
+    sqlx::query!("UPDATE subscriptions SET status = 'bounced'")
+        .execute(&app.db.pool)
+        .await
+        .unwrap();
+
+    // Act
+    let response = app.get(confirmation_link.as_str()).await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db.pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "bounced");
+}
+
+#[sqlx::test]
+async fn following_the_confirmation_link_twice_is_fine(pool: PgPool) {
+    // Arrange
+    let captured_request_content = Arc::new(RwLock::new(None));
+    let aws_client = mock_aws_sesv2_with_request_capture(captured_request_content.clone());
+    let app = spawn_test_app(pool, aws_client).await.unwrap();
+    let form_data = "name=Andrii%20Konotop&email=aws.test.receiver@gmail.com";
+    let _ = app.post("/subscriptions", form_data).await;
+    let confirmation_link = get_confirmation_links(captured_request_content.clone()).plain_text;
+
+    // Act
+    let first = app.get(confirmation_link.as_str()).await;
+    let second = app.get(confirmation_link.as_str()).await;
+
+    // Assert
+    assert_eq!(first.status(), StatusCode::OK);
+    assert_eq!(second.status(), StatusCode::OK);
+}
+
+#[sqlx::test]
+async fn the_database_rejects_unknown_statuses(pool: PgPool) {
+    // Arrange
+    let client = mock_aws_sesv2();
+    let app = spawn_test_app(pool, client).await.unwrap();
+    let _ = app
+        .post(
+            "/subscriptions",
+            "name=Andrii%20Konotop&email=aws.test.receiver@gmail.com",
+        )
+        .await;
+
+    // Act
+    let result = sqlx::query!("UPDATE subscriptions SET status = 'active'")
+        .execute(&app.db.pool)
+        .await;
+
+    // Assert
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn subscribing_and_confirming_work_without_a_database() {
+    // Arrange
+    let captured_request_content = Arc::new(RwLock::new(None));
+    let aws_client = mock_aws_sesv2_with_request_capture(captured_request_content.clone());
+    let subscribers = Arc::new(InMemorySubscriberRepository::new());
+    let app = spawn_test_app_without_database(aws_client, subscribers.clone())
+        .await
+        .unwrap();
+    let form_data = "name=Andrii%20Konotop&email=aws.test.receiver@gmail.com";
+
+    // Act
+    let subscribed = app.post("/subscriptions", form_data).await;
+    let confirmation_link = get_confirmation_links(captured_request_content.clone()).plain_text;
+    let confirmed = app.get(confirmation_link.as_str()).await;
+
+    // Assert
+    assert_eq!(subscribed.status(), StatusCode::OK);
+    assert_eq!(confirmed.status(), StatusCode::OK);
+    let page = subscribers
+        .confirmed_subscribers_page(None, 10)
+        .await
+        .unwrap();
+    assert_eq!(page.subscribers.len(), 1);
+}
+
+#[sqlx::test]
+async fn only_a_digest_of_the_token_is_stored(pool: PgPool) {
+    // Arrange
+    let captured_request_content = Arc::new(RwLock::new(None));
+    let aws_client = mock_aws_sesv2_with_request_capture(captured_request_content.clone());
+    let app = spawn_test_app(pool, aws_client).await.unwrap();
+    let form_data = "name=Andrii%20Konotop&email=aws.test.receiver@gmail.com";
+
+    // Act
+    let _ = app.post("/subscriptions", form_data).await;
+
+    // Assert
+    let confirmation_link = get_confirmation_links(captured_request_content.clone()).plain_text;
+    let (_, token) = confirmation_link
+        .query_pairs()
+        .find(|(key, _)| key == "subscription_token")
+        .unwrap();
+    assert!(token.starts_with("st1_"));
+    let stored = sqlx::query!("SELECT token_hash FROM subscription_tokens")
+        .fetch_one(&app.db.pool)
+        .await
+        .unwrap();
+    assert_ne!(stored.token_hash, token);
+    assert_eq!(stored.token_hash, subscription_token_digest(&token));
+}
+
+#[sqlx::test]
+async fn api_clients_get_the_outcome_as_json(pool: PgPool) {
+    // Arrange
+    let captured_request_content = Arc::new(RwLock::new(None));
+    let aws_client = mock_aws_sesv2_with_request_capture(captured_request_content.clone());
+    let app = spawn_test_app(pool, aws_client).await.unwrap();
+    let form_data = "name=Andrii%20Konotop&email=aws.test.receiver@gmail.com";
+    let _ = app.post("/subscriptions", form_data).await;
+    let confirmation_link = get_confirmation_links(captured_request_content.clone()).plain_text;
+
+    // Act
+    let first = app.get(confirmation_link.as_str()).await;
+    let second = app.get(confirmation_link.as_str()).await;
+    let unknown = app
+        .get("/subscriptions/confirm?subscription_token=st1_unknown")
+        .await;
+
+    // Assert
+    for response in [&first, &second, &unknown] {
+        assert_eq!(response.headers()["vary"], "accept");
+    }
+    assert_eq!(body_text(first).await, r#"{"status":"confirmed"}"#);
+    assert_eq!(body_text(second).await, r#"{"status":"already_confirmed"}"#);
+    assert_eq!(unknown.status(), StatusCode::NOT_FOUND);
+    assert!(body_text(unknown).await.contains("no subscriber"));
+}
+
+#[sqlx::test]
+async fn browsers_get_a_page_for_each_outcome(pool: PgPool) {
+    // Arrange
+    let captured_request_content = Arc::new(RwLock::new(None));
+    let aws_client = mock_aws_sesv2_with_request_capture(captured_request_content.clone());
+    let app = spawn_test_app(pool, aws_client).await.unwrap();
+    let form_data = "name=Andrii%20Konotop&email=aws.test.receiver@gmail.com";
+    let _ = app.post("/subscriptions", form_data).await;
+    let confirmation_link = get_confirmation_links(captured_request_content.clone()).plain_text;
+
+    // Act
+    let first = app.get_as_browser(confirmation_link.as_str()).await;
+    let second = app.get_as_browser(confirmation_link.as_str()).await;
+    let unknown = app
+        .get_as_browser("/subscriptions/confirm?subscription_token=st1_unknown")
+        .await;
+
+    // Assert
+    for response in [&first, &second, &unknown] {
+        assert_eq!(response.headers()["vary"], "accept");
+    }
+    assert_eq!(first.status(), StatusCode::OK);
+    assert!(first.headers()["content-type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/html"));
+    assert!(body_text(first).await.contains("subscribed!"));
+    assert!(body_text(second).await.contains("already subscribed"));
+    assert_eq!(unknown.status(), StatusCode::NOT_FOUND);
+    assert!(body_text(unknown).await.contains("doesn't work"));
+}
+
+#[sqlx::test]
+async fn browsers_can_be_sent_to_a_marketing_page_once_confirmed(pool: PgPool) {
+    // Arrange
+    let mut configuration = test_configuration();
+    configuration.application.confirmed_redirect_url = Some("https://example.com/welcome".into());
+    let captured_request_content = Arc::new(RwLock::new(None));
+    let aws_client = mock_aws_sesv2_with_request_capture(captured_request_content.clone());
+    let app = spawn_test_app_with(pool, aws_client, configuration, TestOverrides::default())
+        .await
+        .unwrap();
+    let form_data = "name=Andrii%20Konotop&email=aws.test.receiver@gmail.com";
+    let _ = app.post("/subscriptions", form_data).await;
+    let confirmation_link = get_confirmation_links(captured_request_content.clone()).plain_text;
+
+    // Act
+    let browser = app.get_as_browser(confirmation_link.as_str()).await;
+    let api_client = app.get(confirmation_link.as_str()).await;
+
+    // Assert
+    assert_eq!(browser.status(), StatusCode::SEE_OTHER);
+    assert_eq!(browser.headers()["location"], "https://example.com/welcome");
+    assert_eq!(browser.headers()["vary"], "accept");
+    assert_eq!(api_client.status(), StatusCode::OK);
+}
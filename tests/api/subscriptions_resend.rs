@@ -0,0 +1,191 @@
+use std::{
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use aws_sdk_sesv2::{
+    config::{Config, Region},
+    Client,
+};
+use axum::http::StatusCode;
+use http_body_util::BodyExt;
+use newsletter::{
+    database::subscribers::{postgres::PostgresSubscriberRepository, SubscriberRepository},
+    domain::{NewSubscriber, SubscriberEmail, SubscriberName},
+};
+use sqlx::PgPool;
+use tokio::net::TcpListener;
+
+use crate::helpers::{
+    get_confirmation_links, mock_aws_sesv2, mock_aws_sesv2_with_request_capture, spawn_test_app,
+    CapturedRequestContent,
+};
+
+const FORM_DATA: &str = "name=Andrii%20Konotop&email=aws.test.receiver@gmail.com";
+const RESEND_DATA: &str = "email=aws.test.receiver@gmail.com";
+
+async fn status(pool: &PgPool) -> String {
+    sqlx::query_scalar!("SELECT status FROM subscriptions")
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+/// Resent links go out after the response.
+async fn wait_for_email(captured_request_content: &CapturedRequestContent) {
+    for _ in 0..500 {
+        if captured_request_content.read().unwrap().is_some() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("No email was sent");
+}
+
+fn new_subscriber(email: &str) -> NewSubscriber {
+    NewSubscriber {
+        email: SubscriberEmail::parse(email.to_string()).unwrap(),
+        name: SubscriberName::parse("Ursula Le Guin".to_string()).unwrap(),
+        locale: "en".to_string(),
+    }
+}
+
+#[sqlx::test]
+async fn a_resent_link_replaces_the_old_one(pool: PgPool) {
+    // Arrange
+    let captured_request_content = Arc::new(RwLock::new(None));
+    let aws_client = mock_aws_sesv2_with_request_capture(captured_request_content.clone());
+    let app = spawn_test_app(pool, aws_client).await.unwrap();
+    app.post("/subscriptions", FORM_DATA).await;
+    let old_link = get_confirmation_links(captured_request_content.clone()).plain_text;
+    *captured_request_content.write().unwrap() = None;
+
+    // Act
+    let response = app
+        .post("/subscriptions/resend-confirmation", RESEND_DATA)
+        .await;
+    wait_for_email(&captured_request_content).await;
+    let new_link = get_confirmation_links(captured_request_content.clone()).plain_text;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_ne!(old_link, new_link);
+
+    app.get(old_link.as_str()).await;
+    assert_eq!(status(&app.db.pool).await, "pending_confirmation");
+
+    let confirmed = app.get(new_link.as_str()).await;
+    assert_eq!(confirmed.status(), StatusCode::OK);
+    assert_eq!(status(&app.db.pool).await, "confirmed");
+}
+
+#[sqlx::test]
+async fn the_response_is_the_same_whatever_the_address(pool: PgPool) {
+    // Arrange
+    let app = spawn_test_app(pool.clone(), mock_aws_sesv2())
+        .await
+        .unwrap();
+    let repository = PostgresSubscriberRepository::new(pool);
+    repository
+        .insert_pending(&new_subscriber("pending@example.com"), Some("token"))
+        .await
+        .unwrap();
+    repository
+        .insert_confirmed(&new_subscriber("confirmed@example.com"))
+        .await
+        .unwrap();
+
+    let mut responses = vec![];
+    for email in [
+        "pending%40example.com",
+        "confirmed%40example.com",
+        "unknown%40example.com",
+    ] {
+        // Act
+        let response = app
+            .post(
+                "/subscriptions/resend-confirmation",
+                &format!("email={}", email),
+            )
+            .await;
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        responses.push((status, body));
+    }
+
+    // Assert
+    assert_eq!(responses[0].0, StatusCode::OK);
+    assert_eq!(responses[0], responses[1]);
+    assert_eq!(responses[0], responses[2]);
+}
+
+#[sqlx::test]
+async fn the_response_does_not_wait_for_the_email(pool: PgPool) {
+    // Arrange
+    // Accepts connections but never answers, so every SES call hangs.
+    let silent = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let client = Client::from_conf(
+        Config::builder()
+            .with_test_defaults()
+            .region(Region::from_static("eu-central-1"))
+            .endpoint_url(format!("http://{}", silent.local_addr().unwrap()))
+            .build(),
+    );
+    let app = spawn_test_app(pool.clone(), client).await.unwrap();
+    PostgresSubscriberRepository::new(pool)
+        .insert_pending(&new_subscriber("pending@example.com"), Some("token"))
+        .await
+        .unwrap();
+
+    // Act
+    let response = tokio::time::timeout(
+        Duration::from_secs(2),
+        app.post(
+            "/subscriptions/resend-confirmation",
+            "email=pending%40example.com",
+        ),
+    )
+    .await
+    .expect("The response waited for SES");
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[sqlx::test]
+async fn an_address_cools_down_between_resends(pool: PgPool) {
+    // Arrange
+    let captured_request_content = Arc::new(RwLock::new(None));
+    let aws_client = mock_aws_sesv2_with_request_capture(captured_request_content.clone());
+    let app = spawn_test_app(pool, aws_client).await.unwrap();
+    app.post("/subscriptions", FORM_DATA).await;
+    *captured_request_content.write().unwrap() = None;
+    app.post("/subscriptions/resend-confirmation", RESEND_DATA)
+        .await;
+    wait_for_email(&captured_request_content).await;
+    *captured_request_content.write().unwrap() = None;
+
+    // Act
+    let response = app
+        .post("/subscriptions/resend-confirmation", RESEND_DATA)
+        .await;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(captured_request_content.read().unwrap().is_none());
+}
+
+#[sqlx::test]
+async fn resending_to_an_invalid_address_is_rejected_with_a_400(pool: PgPool) {
+    // Arrange
+    let app = spawn_test_app(pool, mock_aws_sesv2()).await.unwrap();
+
+    // Act
+    let response = app
+        .post("/subscriptions/resend-confirmation", "email=not-an-email")
+        .await;
+
+    // Assert
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,102 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    body::Body,
+    http::{self, Request, StatusCode},
+};
+use opentelemetry::{global, trace::TracerProvider as _};
+use opentelemetry_sdk::{
+    export::trace::{ExportResult, SpanData, SpanExporter},
+    propagation::TraceContextPropagator,
+    trace::TracerProvider,
+};
+use sqlx::PgPool;
+
+use newsletter::telemetry::get_subscriber_with_tracer;
+
+use crate::helpers::{mock_aws_sesv2, spawn_test_app};
+
+/// Stands in for an OTLP collector, keeping every exported span in memory.
+#[derive(Debug, Clone, Default)]
+struct CollectorStub {
+    spans: Arc<Mutex<Vec<SpanData>>>,
+}
+
+impl SpanExporter for CollectorStub {
+    fn export(
+        &mut self,
+        batch: Vec<SpanData>,
+    ) -> Pin<Box<dyn Future<Output = ExportResult> + Send + 'static>> {
+        self.spans.lock().unwrap().extend(batch);
+        Box::pin(std::future::ready(Ok(())))
+    }
+}
+
+#[sqlx::test]
+async fn spans_join_the_callers_trace_down_to_the_ses_call(pool: PgPool) {
+    // Arrange
+    let collector = CollectorStub::default();
+    let provider = TracerProvider::builder()
+        .with_simple_exporter(collector.clone())
+        .build();
+    let subscriber = get_subscriber_with_tracer(
+        "test".into(),
+        "info".into(),
+        std::io::sink,
+        Some(provider.tracer("test")),
+    );
+    let _guard = tracing::subscriber::set_default(subscriber);
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let app = spawn_test_app(pool, mock_aws_sesv2()).await.unwrap();
+
+    let request = Request::builder()
+        .method(http::Method::POST)
+        .uri("/subscriptions")
+        .header(
+            http::header::CONTENT_TYPE,
+            "application/x-www-form-urlencoded",
+        )
+        .header(
+            "traceparent",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        )
+        .body(Body::from(
+            "name=Andrii%20Konotop&email=aws.test.receiver@gmail.com",
+        ))
+        .unwrap();
+
+    // Act
+    let response = app.send(request).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    // The request span only closes once the response is gone.
+    drop(response);
+
+    // Assert
+    let spans = collector.spans.lock().unwrap();
+    for name in [
+        "request",
+        "Adding a new subscriber",
+        "Saving new subscriber details in the database",
+        "Store subscription token in the database",
+        "Sending an email via SES",
+    ] {
+        let span = spans
+            .iter()
+            .find(|span| span.name == name)
+            .unwrap_or_else(|| panic!("No `{}` span was exported", name));
+        assert_eq!(
+            span.span_context.trace_id().to_string(),
+            "4bf92f3577b34da6a3ce929d0e0e4736",
+            "`{}` is not part of the caller's trace",
+            name
+        );
+    }
+
+    let request_span = spans.iter().find(|span| span.name == "request").unwrap();
+    assert_eq!(request_span.parent_span_id.to_string(), "00f067aa0ba902b7");
+}